@@ -2,34 +2,70 @@
 // Integration benchmarks testing an entire binary.
 
 extern crate test;
-use std::{path::PathBuf, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use test::*;
 use tests::*;
 
-// Benchmarks
-#[bench]
-fn bench_stats_1k(b: &mut Bencher) {
-    build_test_dir(1_000).unwrap();
+/// Returns the path to the release binary, or None if it hasn't been built
+fn release_binary() -> Option<PathBuf> {
     let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
     path.push("target");
     path.push("release");
     if !path.exists() {
         // probably not benchmarking - `cargo test` was invoked instead
         eprintln!("{:?} doesn't exist - ignoring benchmark", path);
-        return;
+        return None;
     }
     path.push("psbattletools");
+    Some(path)
+}
+
+/// Benchmarks the statistics subcommand on the logs in `root`
+fn bench_stats(b: &mut Bencher, root: &Path, csv_path: &str) {
+    let path = match release_binary() {
+        Some(path) => path,
+        None => return,
+    };
     let mut cmd = Command::new(&path);
-    cmd.arg("statistics")
-        .arg(path)
-        .arg(&*TEST_ROOT_DIR)
-        .arg("--csv")
-        .arg("test-scratch/new.csv");
+    cmd.arg("statistics").arg(root).arg("--csv").arg(csv_path);
 
     b.iter(|| cmd.output().expect("Failed to run command"));
 }
 
+/// Benchmarks the search subcommand on the logs in `root`
+fn bench_search(b: &mut Bencher, root: &Path) {
+    let path = match release_binary() {
+        Some(path) => path,
+        None => return,
+    };
+    let mut cmd = Command::new(&path);
+    cmd.arg("search").arg("AnniKa").arg(root);
+
+    b.iter(|| cmd.output().expect("Failed to execute command"));
+}
+
+// Benchmarks
+#[bench]
+fn bench_stats_1k(b: &mut Bencher) {
+    build_test_dir(1_000).unwrap();
+    bench_stats(b, &TEST_ROOT_DIR, "test-scratch/new.csv");
+}
+
+// Same as bench_stats_1k, but with logs spread across 30 small day directories like PS's real log tree
+#[bench]
+fn bench_stats_multi_day_1k(b: &mut Bencher) {
+    build_multi_day_test_dir(&TEST_MULTI_DAY_ROOT_DIR, 30, 34).unwrap();
+    bench_stats(
+        b,
+        &TEST_MULTI_DAY_ROOT_DIR,
+        "test-scratch/new-multi-day.csv",
+    );
+}
+
 // Benchmark against the old `randbats-winrates` program
 #[cfg_attr(not(feature = "bench_old_winrates"), ignore)]
 #[bench]
@@ -50,20 +86,13 @@ fn bench_old_winrates_1k(b: &mut Bencher) {
 #[bench]
 fn bench_search_1k(b: &mut Bencher) {
     build_test_dir(1_000).unwrap();
-    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
-    path.push("target");
-    path.push("release");
-    if !path.exists() {
-        // probably not benchmarking - `cargo test` was invoked instead
-        eprintln!("{:?} doesn't exist - ignoring benchmark", path);
-        return;
-    }
-    path.push("psbattletools");
-
-    let mut cmd = Command::new(&path);
-    cmd.arg("search").arg("AnniKa").arg(&*TEST_ROOT_DIR);
+    bench_search(b, &TEST_ROOT_DIR);
+}
 
-    b.iter(|| cmd.output().expect("Failed to execute command"));
+#[bench]
+fn bench_search_multi_day_1k(b: &mut Bencher) {
+    build_multi_day_test_dir(&TEST_MULTI_DAY_ROOT_DIR, 30, 34).unwrap();
+    bench_search(b, &TEST_MULTI_DAY_ROOT_DIR);
 }
 
 // Benchmark against the old `battlesearch` program
@@ -81,15 +110,10 @@ fn bench_old_battlesearch_1k(b: &mut Bencher) {
 #[bench]
 fn bench_anonymize_1k(b: &mut Bencher) {
     build_test_dir(1_000).unwrap();
-    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
-    path.push("target");
-    path.push("release");
-    if !path.exists() {
-        // probably not benchmarking - `cargo test` was invoked instead
-        eprintln!("{:?} doesn't exist - ignoring benchmark", path);
-        return;
-    }
-    path.push("psbattletools");
+    let path = match release_binary() {
+        Some(path) => path,
+        None => return,
+    };
 
     let mut output_path = TEST_ROOT_DIR
         .parent()
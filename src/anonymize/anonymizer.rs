@@ -1,15 +1,20 @@
 // From https://github.com/AnnikaCodes/anonbattle/blob/main/src/anonymizer.rs
 
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Mutex;
 
+use indexmap::IndexMap;
 use lazy_static::*;
 use regex::Regex;
+use serde_json::value::{to_raw_value, RawValue};
 
 use crate::{
+    battle_log::{BattleLog, Rating, Side},
     id::{escape, to_id},
+    protocol::{Event, Line},
     BattleToolsError,
 };
 
@@ -51,6 +56,25 @@ impl SharedState {
     }
 }
 
+/// The parts of a rating that can't identify the player
+#[derive(Serialize)]
+struct AnonymizedRating {
+    elo: Option<f64>,
+    rpr: Option<f64>,
+    rprd: Option<f64>,
+}
+
+/// Rounds a rating: ELO and `rpr` to the nearest 50, and `rprd` to the nearest 10.
+/// Unrated battles still get a rating, with each value `null`.
+fn anonymize_rating(rating: Option<&Rating>) -> AnonymizedRating {
+    let round = |number: Option<f64>, nearest: f64| number.map(|n| (n / nearest).round() * nearest);
+    AnonymizedRating {
+        elo: round(rating.and_then(|rating| rating.elo), 50.0),
+        rpr: round(rating.and_then(|rating| rating.rpr), 50.0),
+        rprd: round(rating.and_then(|rating| rating.rprd), 10.0),
+    }
+}
+
 /// Anonymizes string JSON while tracking state
 pub struct Anonymizer {
     state: Mutex<SharedState>,
@@ -68,27 +92,23 @@ impl Anonymizer {
         }
     }
 
-    pub fn with_json(json: String, is_safe: bool, no_log: bool) -> Self {
-        let state: SharedState = serde_json::from_str(&json).unwrap();
-        Self {
+    pub fn with_json(json: String, is_safe: bool, no_log: bool) -> Result<Self, BattleToolsError> {
+        let state: SharedState = serde_json::from_str(&json)?;
+        Ok(Self {
             state: Mutex::new(state),
             is_safe,
             no_log,
-        }
+        })
     }
 
     /// Anonymizes a log.
     ///
     /// Returns a tuple: (json, battle_number, format)
     pub fn anonymize(&self, raw: &str) -> Result<(String, u32, String), BattleToolsError> {
-        let json = json::parse(raw)?;
-
-        let p1 = json["p1"]
-            .as_str()
-            .ok_or(format!("Bad JSON for p1 {}", json["p1"]))?;
-        let p2 = json["p2"]
-            .as_str()
-            .ok_or(format!("Bad JSON for p2 {}", json["p2"]))?;
+        let log = BattleLog::parse(raw)?;
+        // (these are usually borrowed from `raw`, so cloning them is cheap)
+        let (p1_name, p2_name) = (log.p1.clone(), log.p2.clone());
+        let (p1, p2) = (&*p1_name, &*p2_name);
 
         let p1_escaped = escape(p1);
         let p2_escaped = escape(p2);
@@ -96,102 +116,56 @@ impl Anonymizer {
         let p1_id = to_id(p1);
         let p2_id = to_id(p2);
 
-        let winner = json["winner"]
-            .as_str()
-            .ok_or(format!("Bad JSON for winner {}", json["winner"]))?
-            .to_owned();
-        // Don't anonymize an empty string (happens in tied battles)
-        let should_anonymize_winner = !winner.is_empty();
-
         let (p1_anon, p2_anon, winner_anon) = {
             let mut tracker = self.state.lock().unwrap();
             (
                 tracker.anonymize_player(p1.to_string()),
                 tracker.anonymize_player(p2.to_string()),
-                if should_anonymize_winner {
-                    tracker.anonymize_player(winner)
-                } else {
+                // Don't anonymize an empty string (happens in tied battles)
+                if log.is_tie() {
                     String::new()
+                } else {
+                    tracker.anonymize_player(log.winner.to_string())
                 },
             )
         };
 
-        let mut json_result = json.clone();
-        // Anonymize
-        json_result["p1"] = json::from(p1_anon.clone());
-        json_result["p2"] = json::from(p2_anon.clone());
-        json_result["winner"] = json::from(winner_anon);
-
-        // Does this need to work for multi battles?
-        for player_rating in ["p1rating", "p2rating"] {
-            // ELO rounded to the nearest 50
-            let anon_elo = match json[player_rating]["elo"].to_string().parse::<f64>() {
-                Ok(elo) => json::from((elo / 50.0).round() * 50.0),
-                Err(_) => json::JsonValue::Null,
-            };
-
-            // `rpr` rounded to the nearest 50
-            let anon_rpr = match json[player_rating]["rpr"].to_string().parse::<f64>() {
-                Ok(elo) => json::from((elo / 50.0).round() * 50.0),
-                Err(_) => json::JsonValue::Null,
-            };
-
-            // `rprd` rounded to the nearest 10
-            let anon_rprd = match json[player_rating]["rprd"].to_string().parse::<f64>() {
-                Ok(elo) => json::from((elo / 10.0).round() * 10.0),
-                Err(_) => json::JsonValue::Null,
-            };
-
-            json_result[player_rating] = json::object! {
-                "elo" => anon_elo,
-                "rpr" => anon_rpr,
-                "rprd" => anon_rprd,
-            };
-        }
-
-        json_result["roomid"] = json::JsonValue::Null;
-
-        // "Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)" -> "Sat Nov 21 2020 17"
-        let mut timestamp = json["timestamp"]
-            .as_str()
-            .ok_or(format!("Bad JSON for timestamp {}", json["timestamp"]))?
-            .split(':')
-            .collect::<Vec<&str>>()[0]
-            .to_owned();
-        timestamp.push_str(":XX");
+        // "Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)" -> "Sat Nov 21 2020 17:XX"
+        let timestamp = log.timestamp.as_deref().map(|timestamp| {
+            let mut hour = timestamp.split(':').next().unwrap_or_default().to_owned();
+            hour.push_str(":XX");
+            hour
+        });
 
-        json_result["timestamp"] = json::from(timestamp);
-
-        if self.no_log {
-            json_result["inputLog"] = json::array!();
+        let input_log = if self.no_log {
+            vec![]
         } else {
-            json_result["inputLog"] = json::from(
-                json["inputLog"]
-                    .members()
-                    .filter_map(|inputlog_part| {
-                        let inputlog_part_string: &str = inputlog_part.as_str().unwrap();
-                        if inputlog_part_string.starts_with(">player p1") {
-                            Some(format!(">player p1 {{\\\"\\\"name\\\":\\\"{}\\\"}}", p1))
-                        } else if inputlog_part_string.starts_with(">player p2") {
-                            Some(format!(">player p2 {{\\\"\\\"name\\\":\\\"{}\\\"}}", p2))
-                        } else if inputlog_part_string.starts_with(">chat ") {
-                            None
-                        } else {
-                            Some(
-                                inputlog_part
-                                    .as_str()
-                                    .expect("input log contained non-string values")
-                                    .to_string(),
-                            )
-                        }
-                    })
-                    .collect::<Vec<_>>(),
-            );
-        }
+            log.input_log
+                .iter()
+                .filter_map(|input_log_part| {
+                    if input_log_part.starts_with(">player p1") {
+                        Some(Cow::Owned(format!(
+                            r#">player p1 {{"name":"{}"}}"#,
+                            p1_anon
+                        )))
+                    } else if input_log_part.starts_with(">player p2") {
+                        Some(Cow::Owned(format!(
+                            r#">player p2 {{"name":"{}"}}"#,
+                            p2_anon
+                        )))
+                    } else if input_log_part.starts_with(">chat ") {
+                        None
+                    } else {
+                        Some(input_log_part.clone())
+                    }
+                })
+                .collect()
+        };
 
+        // Only used for lines that the protocol parser doesn't understand
         let p1regex = Regex::from_str(
             &[
-                "\\|p1[ab]?: (",
+                "(\\|p1[ab]?: )(",
                 &regex::escape(p1),
                 "|",
                 &regex::escape(&p1_id),
@@ -203,7 +177,7 @@ impl Anonymizer {
         )?;
         let p2regex = Regex::from_str(
             &[
-                "\\|p2[ab]?: (",
+                "(\\|p2[ab]?: )(",
                 &regex::escape(p2),
                 "|",
                 &regex::escape(&p2_id),
@@ -214,69 +188,114 @@ impl Anonymizer {
             .join(""),
         )?;
 
-        if self.no_log {
-            json_result["log"] = json::array!();
+        let battle_log = if self.no_log {
+            vec![]
         } else {
-            json_result["log"] = json::from(
-                json["log"]
-                    .members()
-                    .filter_map(|log_part| {
-                        let log_part_string: &str = &match log_part.as_str() {
-                            Some(a) => a.to_owned(),
-                            None => format!("Bad JSON for logpart {:#?}", log_part),
-                        };
-
+            log.log
+                .iter()
+                .filter_map(|log_part| {
+                    let mut line = Line::parse(log_part);
+                    match &line.event {
                         // Remove chat and timers (privacy threat)
-                        if log_part_string.starts_with("|c|")
-                            || log_part_string.starts_with("|c:|")
-                            || log_part_string.starts_with("|inactive|")
-                        {
-                            return None;
-                        }
-
-                        if log_part_string.starts_with("|j|")
-                            || log_part_string.starts_with("|J|")
-                            || log_part_string.starts_with("|l|")
-                            || log_part_string.starts_with("|L|")
-                            || log_part_string.starts_with("|N|")
-                            || log_part_string.starts_with("|n|")
-                            || log_part_string.starts_with("|win|")
-                            || log_part_string.starts_with("|tie|")
-                            || log_part_string.starts_with("|-message|")
-                            || log_part_string.starts_with("|raw|")
-                            || log_part_string.starts_with("|player|")
-                        {
-                            if log_part_string.contains("'s rating: ") {
+                        Event::Chat { .. }
+                        | Event::Inactive(_)
+                        | Event::Unknown {
+                            kind: "c" | "c:" | "inactive",
+                            ..
+                        } => return None,
+
+                        // Lines with free text that may mention the players
+                        Event::Join { .. }
+                        | Event::Leave { .. }
+                        | Event::Rename { .. }
+                        | Event::Win(_)
+                        | Event::Tie
+                        | Event::Message(_)
+                        | Event::Raw(_)
+                        | Event::Player { .. }
+                        | Event::Unknown {
+                            kind:
+                                "j" | "J" | "l" | "L" | "n" | "N" | "win" | "-message" | "raw"
+                                | "player",
+                            ..
+                        } => {
+                            if log_part.contains("'s rating: ") {
                                 return None;
                             }
 
-                            return Some(
-                                log_part_string
+                            return Some(Cow::Owned(
+                                log_part
                                     .replace(p1, &p1_anon)
                                     .replace(p2, &p2_anon)
                                     .replace(&p1_id, &p1_anon)
                                     .replace(&p2_id, &p2_anon)
                                     .replace(&p1_escaped, &p1_anon)
                                     .replace(&p2_escaped, &p2_anon),
-                            );
+                            ));
                         }
 
-                        return Some(
-                            p2regex
-                                .replace_all(
-                                    p1regex
-                                        .replace_all(log_part_string, &p1_anon as &str)
-                                        .as_ref(),
-                                    &p2_anon as &str,
-                                )
-                                .to_string(),
-                        );
-                    })
-                    .collect::<Vec<_>>(),
-            );
-        }
+                        Event::Unknown { .. } => {
+                            return Some(Cow::Owned(
+                                p2regex
+                                    .replace_all(
+                                        p1regex
+                                            .replace_all(log_part, format!("${{1}}{}", p1_anon))
+                                            .as_ref(),
+                                        format!("${{1}}{}", p2_anon),
+                                    )
+                                    .to_string(),
+                            ));
+                        }
+                        _ => {}
+                    }
+
+                    // Pokémon nicknamed after their trainer, and sides, are renamed
+                    let mut renamed = false;
+                    line.rename(|side, name| {
+                        let (names, anonymized_name) = match side {
+                            Side::P1 => ([p1, &p1_id, &p1_escaped], &p1_anon),
+                            Side::P2 => ([p2, &p2_id, &p2_escaped], &p2_anon),
+                        };
+                        if names.contains(name) {
+                            *name = anonymized_name;
+                            renamed = true;
+                        }
+                    });
+                    if renamed {
+                        Some(Cow::Owned(line.to_string()))
+                    } else {
+                        Some(log_part.clone())
+                    }
+                })
+                .collect()
+        };
 
-        let result = json::stringify(json_result);
+        // Only the properties that identify the players are rewritten (in place),
+        // so everything else is kept as it was, including properties that `BattleLog` doesn't model
+        let mut properties: IndexMap<String, Box<RawValue>> = serde_json::from_str(raw)?;
+        let mut rewritten = vec![
+            ("winner", to_raw_value(&winner_anon)?),
+            ("p1", to_raw_value(&p1_anon)?),
+            ("p2", to_raw_value(&p2_anon)?),
+            (
+                "p1rating",
+                to_raw_value(&anonymize_rating(log.rating(Side::P1)))?,
+            ),
+            (
+                "p2rating",
+                to_raw_value(&anonymize_rating(log.rating(Side::P2)))?,
+            ),
+            ("roomid", to_raw_value(&None::<&str>)?),
+            ("inputLog", to_raw_value(&input_log)?),
+            ("log", to_raw_value(&battle_log)?),
+        ];
+        if let Some(timestamp) = timestamp {
+            rewritten.push(("timestamp", to_raw_value(&timestamp)?));
+        }
+        for (property, value) in rewritten {
+            properties.insert(property.to_string(), value);
+        }
+        let result = serde_json::to_string(&properties)?;
 
         if self.is_safe
             && (result.contains(p1)
@@ -287,7 +306,10 @@ impl Anonymizer {
                 || result.contains(&p2_escaped))
         {
             return Err(BattleToolsError::IncompleteAnonymization(
-                json["roomid"].to_string(),
+                log.roomid
+                    .as_deref()
+                    .unwrap_or("(unknown room)")
+                    .to_string(),
             ));
         }
 
@@ -296,13 +318,22 @@ impl Anonymizer {
             tracker.current_battle_number += 1;
             tracker.current_battle_number
         };
-        Ok((result, battle_number, json["format"].to_string()))
+        Ok((result, battle_number, log.format_id().to_string()))
     }
 
-    // TODO: actually save this & load it
     pub fn get_state_json(&self) -> serde_json::Result<String> {
         self.state.lock().unwrap().to_json()
     }
+
+    pub fn get_state(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&*self.state.lock().unwrap())
+    }
+
+    /// Continues from a state returned by `get_state`, so players keep their IDs and battle numbers keep counting up
+    pub fn set_state(&mut self, state: serde_json::Value) -> serde_json::Result<()> {
+        *self.state.get_mut().unwrap() = serde_json::from_value(state)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -369,15 +400,119 @@ mod unit_tests {
             );
         }
 
-        for property in ["roomid"] {
-            let value = gjson::get(&json, property);
-            assert!(
-                !value.exists() || value.kind() == gjson::Kind::Null,
-                "Anonymized JSON includes potentially-identifying property '{}' (full JSON: '{}')",
-                property,
-                json
+        let value = gjson::get(&json, "roomid");
+        assert!(
+            !value.exists() || value.kind() == gjson::Kind::Null,
+            "Anonymized JSON includes potentially-identifying property 'roomid' (full JSON: '{}')",
+            json
+        );
+    }
+
+    #[test]
+    pub fn input_log_players() {
+        let json = SAMPLE_JSON.replace(
+            r#""inputLog":["#,
+            r#""inputLog":[">player p1 {\"name\":\"Annika\",\"avatar\":\"cynthia\"}",">chat hi","#,
+        );
+        let anonymizer = Anonymizer::new(true, false);
+        let (json, _, _) = anonymizer.anonymize(&json).unwrap();
+        let input_log = gjson::get(&json, "inputLog");
+        let input_log = input_log.array();
+        assert_eq!(input_log[0].str(), r#">player p1 {"name":"1"}"#);
+        assert_eq!(
+            input_log.len(),
+            2,
+            "chat should be removed from the input log"
+        );
+    }
+
+    #[test]
+    pub fn nicknames() {
+        // Pokémon nicknamed after their trainer keep their identifiers, with the nickname anonymized
+        let json = SAMPLE_JSON.replace(
+            r#""|rated|""#,
+            r#""|rated|","|switch|p1a: Annika|Rotom-Fan, L84|100/100","|-damage|p2a: Drednaw|50/100|[from] Rocky Helmet|[of] p1a: Annika","|-sidestart|p2: Rust Haters|move: Stealth Rock""#,
+        );
+        let anonymizer = Anonymizer::new(true, false);
+        let (json, _, _) = anonymizer.anonymize(&json).unwrap();
+        let log = gjson::get(&json, "log");
+        let log = log.array();
+        let lines: Vec<&str> = log[log.len() - 3..].iter().map(|line| line.str()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "|switch|p1a: 1|Rotom-Fan, L84|100/100",
+                "|-damage|p2a: Drednaw|50/100|[from] Rocky Helmet|[of] p1a: 1",
+                "|-sidestart|p2: 2|move: Stealth Rock",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn unknown_properties() {
+        let json = SAMPLE_JSON
+            .replace(
+                r#""turns":2,"#,
+                r#""turns":2,"extra":{"nested":[1,"two",null]},"#,
+            )
+            .replace(
+                r#""shiny":false,"#,
+                r#""shiny":false,"pokeball":"pokeball","#,
             );
-        }
+        let anonymizer = Anonymizer::new(true, false);
+        let (json, _, _) = anonymizer.anonymize(&json).unwrap();
+
+        // properties that aren't modeled are kept as they were, in the same place
+        assert_eq!(
+            gjson::get(&json, "extra").json(),
+            r#"{"nested":[1,"two",null]}"#
+        );
+        assert_eq!(gjson::get(&json, "p1team.0.pokeball").str(), "pokeball");
+        assert_eq!(
+            gjson::get(&json, "comment").str(),
+            gjson::get(&SAMPLE_JSON, "comment").str()
+        );
+        let properties: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&json).unwrap();
+        let keys = properties.keys().take(5).collect::<Vec<_>>();
+        assert_eq!(keys, ["winner", "seed", "turns", "extra", "p1"]);
+    }
+
+    #[test]
+    pub fn unrated() {
+        let start = SAMPLE_JSON.find(r#""p1rating":{"#).unwrap();
+        let end = start + SAMPLE_JSON[start..].find('}').unwrap() + 1;
+        let json = format!(
+            r#"{}"p1rating":null{}"#,
+            &SAMPLE_JSON[..start],
+            &SAMPLE_JSON[end..]
+        );
+        let anonymizer = Anonymizer::new(true, false);
+        let (json, _, _) = anonymizer.anonymize(&json).unwrap();
+        // like rated battles, the rating has `elo`, `rpr`, and `rprd`, but they're null
+        assert_eq!(
+            gjson::get(&json, "p1rating").json(),
+            r#"{"elo":null,"rpr":null,"rprd":null}"#
+        );
+        assert_eq!(
+            gjson::get(&json, "p2rating").json(),
+            r#"{"elo":1150.0,"rpr":1450.0,"rprd":110.0}"#
+        );
+    }
+
+    #[test]
+    pub fn missing_timestamp_and_format() {
+        let json = SAMPLE_JSON
+            .replace(
+                r#""timestamp":"Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)","#,
+                "",
+            )
+            .replace(r#","format":"gen8randombattle""#, "");
+        let anonymizer = Anonymizer::new(true, false);
+        let (json, _, format) = anonymizer.anonymize(&json).unwrap();
+        assert!(!gjson::get(&json, "timestamp").exists());
+        // the format is still known from the room ID
+        assert_eq!(format, "gen8randombattle");
     }
 
     // In ties, the `winner` property is an empty string.
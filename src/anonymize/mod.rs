@@ -3,7 +3,10 @@
 mod anonymizer;
 use std::path::PathBuf;
 
-use crate::{directory::LogParser, BattleToolsError};
+use crate::{
+    directory::{LogParser, ResumableParser},
+    BattleToolsError,
+};
 pub use anonymizer::Anonymizer;
 pub struct AnonymizingDirectoryParser {
     anonymizer: Anonymizer,
@@ -31,10 +34,13 @@ impl AnonymizingDirectoryParser {
 }
 
 impl LogParser<()> for AnonymizingDirectoryParser {
+    fn new_accumulator(&self) {}
+
     fn handle_log_file(
         &self,
         raw_json: String,
         _: &std::path::Path,
+        _: &mut (),
     ) -> Result<(), BattleToolsError> {
         let (json, battle_num, directory) = self.anonymizer.anonymize(&raw_json)?;
         let mut out_file = self.output_directory.clone();
@@ -45,16 +51,28 @@ impl LogParser<()> for AnonymizingDirectoryParser {
         Ok(())
     }
 
-    fn handle_results(&mut self, _: Vec<()>) -> Result<(), BattleToolsError> {
+    fn merge_results(&self, _: (), _: ()) {}
+
+    fn handle_results(&mut self, _: ()) -> Result<(), BattleToolsError> {
         Ok(())
     }
 }
 
+impl ResumableParser for AnonymizingDirectoryParser {
+    fn save_state(&self) -> Result<serde_json::Value, BattleToolsError> {
+        Ok(self.anonymizer.get_state()?)
+    }
+
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), BattleToolsError> {
+        Ok(self.anonymizer.set_state(state)?)
+    }
+}
+
 #[cfg(test)]
 mod unit_tests {
     extern crate test;
     use super::*;
-    use crate::directory::ParallelDirectoryParser;
+    use crate::directory::{DirectoryFilter, ParallelDirectoryParser, Progress};
     use lazy_static::lazy_static;
     use test::Bencher;
     use tests::*;
@@ -75,7 +93,11 @@ mod unit_tests {
         let mut searcher = AnonymizingDirectoryParser::new(false, false, OUTPUT_PATH.clone());
         b.iter(|| {
             searcher
-                .handle_directories(vec![TEST_ROOT_DIR.clone()], None)
+                .handle_directories(
+                    vec![TEST_ROOT_DIR.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
                 .unwrap()
         });
     }
@@ -0,0 +1,777 @@
+// Code for the contents of a battle log (`.log.json`) file, shared by every subcommand.
+use serde::de::{self, Deserialize, Deserializer, Unexpected, Visitor};
+use serde_derive::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt};
+
+use crate::{
+    id::to_id,
+    protocol::{Event, Line},
+    BattleToolsError,
+};
+
+/// One of the two players in a battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    P1,
+    P2,
+}
+
+impl serde::Serialize for Side {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl Side {
+    /// The side's ID in the battle protocol (`p1` or `p2`)
+    pub fn id(self) -> &'static str {
+        match self {
+            Side::P1 => "p1",
+            Side::P2 => "p2",
+        }
+    }
+
+    /// The other side in the battle
+    pub fn opponent(self) -> Self {
+        match self {
+            Side::P1 => Side::P2,
+            Side::P2 => Side::P1,
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "p1" => Some(Side::P1),
+            "p2" => Some(Side::P2),
+            _ => None,
+        }
+    }
+}
+
+/// A battle log, as written by PS when a battle ends.
+///
+/// Strings are borrowed from the raw JSON where possible (they're only copied if they contain escape sequences).
+/// Properties that aren't modeled here are ignored.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BattleLog<'a> {
+    /// The winner's name, or an empty string if the battle was a tie; see `BattleLog::winner`
+    #[serde(borrow)]
+    pub winner: Cow<'a, str>,
+    /// The PRNG seed, which is an array of numbers in older logs and a string in newer ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<serde_json::Value>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub turns: Option<u32>,
+    #[serde(borrow)]
+    pub p1: Cow<'a, str>,
+    #[serde(borrow)]
+    pub p2: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "nullable_team")]
+    pub p1team: Vec<PokemonSet<'a>>,
+    #[serde(borrow, default, deserialize_with = "nullable_team")]
+    pub p2team: Vec<PokemonSet<'a>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<Vec<u32>>,
+    #[serde(
+        borrow,
+        default,
+        rename = "inputLog",
+        deserialize_with = "borrowed_strings"
+    )]
+    pub input_log: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrowed_strings")]
+    pub log: Vec<Cow<'a, str>>,
+    /// Ratings from before the battle; missing for unrated battles
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub p1rating: Option<Rating<'a>>,
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub p2rating: Option<Rating<'a>>,
+    /// `normal` or `forfeit`, among others
+    #[serde(
+        borrow,
+        default,
+        rename = "endType",
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end_type: Option<Cow<'a, str>>,
+    /// e.g. "Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)"; missing from some old logs
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<Cow<'a, str>>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub roomid: Option<Cow<'a, str>>,
+    /// The format's ID; see `BattleLog::format_id`
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub format: Option<Cow<'a, str>>,
+}
+
+/// The parts of a battle log that describe the battle as a whole: who played in it and how it ended,
+/// plus the species on each team.
+///
+/// `log`, `inputLog`, and everything about the Pokémon other than their species are skipped without being copied,
+/// so this is much cheaper to parse than a `BattleLog`; it's used to reject battles before the rest of the log is parsed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BattleHeader<'a> {
+    #[serde(borrow)]
+    pub winner: Cow<'a, str>,
+    #[serde(default, deserialize_with = "lenient_count")]
+    pub turns: Option<u32>,
+    #[serde(borrow)]
+    pub p1: Cow<'a, str>,
+    #[serde(borrow)]
+    pub p2: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "nullable_team")]
+    pub p1team: Vec<TeamMember<'a>>,
+    #[serde(borrow, default, deserialize_with = "nullable_team")]
+    pub p2team: Vec<TeamMember<'a>>,
+    #[serde(borrow, default)]
+    pub p1rating: Option<Rating<'a>>,
+    #[serde(borrow, default)]
+    pub p2rating: Option<Rating<'a>>,
+    #[serde(
+        borrow,
+        default,
+        rename = "endType",
+        deserialize_with = "borrowed_option"
+    )]
+    pub end_type: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrowed_option")]
+    pub roomid: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrowed_option")]
+    pub format: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrowed_option")]
+    pub timestamp: Option<Cow<'a, str>>,
+}
+
+/// A Pokémon on a team in a `BattleHeader`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TeamMember<'a> {
+    #[serde(borrow)]
+    pub species: Cow<'a, str>,
+}
+
+/// A player's ladder rating.
+///
+/// PS writes some of these numbers as strings (like `"r":"1516.9377700433"`); they're parsed the same way as numbers.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Rating<'a> {
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub userid: Option<Cow<'a, str>>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub elo: Option<f64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub gxe: Option<f64>,
+    /// Glicko-1 rating
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub r: Option<f64>,
+    /// Glicko-1 rating deviation
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rd: Option<f64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rpr: Option<f64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rprd: Option<f64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub w: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub l: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub t: Option<u32>,
+}
+
+/// A Pokémon on a player's team
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PokemonSet<'a> {
+    #[serde(borrow, default)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub species: Cow<'a, str>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub gender: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shiny: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gigantamax: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub level: Option<u32>,
+    #[serde(borrow, default, deserialize_with = "borrowed_strings")]
+    pub moves: Vec<Cow<'a, str>>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ability: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evs: Option<StatTable>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ivs: Option<StatTable>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub item: Option<Cow<'a, str>>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nature: Option<Cow<'a, str>>,
+    #[serde(
+        borrow,
+        default,
+        rename = "teraType",
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tera_type: Option<Cow<'a, str>>,
+}
+
+impl PokemonSet<'_> {
+    /// Copies any borrowed strings, so the set can outlive what it was parsed from
+    pub fn into_owned(self) -> PokemonSet<'static> {
+        let owned = |s: Cow<str>| Cow::Owned(s.into_owned());
+        PokemonSet {
+            name: owned(self.name),
+            species: owned(self.species),
+            gender: self.gender.map(owned),
+            shiny: self.shiny,
+            gigantamax: self.gigantamax,
+            level: self.level,
+            moves: self.moves.into_iter().map(owned).collect(),
+            ability: self.ability.map(owned),
+            evs: self.evs,
+            ivs: self.ivs,
+            item: self.item.map(owned),
+            nature: self.nature.map(owned),
+            tera_type: self.tera_type.map(owned),
+        }
+    }
+}
+
+/// EVs or IVs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StatTable {
+    pub hp: u32,
+    pub atk: u32,
+    pub def: u32,
+    pub spa: u32,
+    pub spd: u32,
+    pub spe: u32,
+}
+
+/// Parses a battle log's JSON as `T`.
+///
+/// Invalid JSON gives a `JSONParsingError`; valid JSON that doesn't look like a battle log gives an
+/// `InvalidLog` error naming the property that's wrong.
+fn parse_log<'a, T: Deserialize<'a>>(raw_json: &'a str) -> Result<T, BattleToolsError> {
+    // Tracking the path makes parsing about twice as slow, so it's only done to explain an error.
+    serde_json::from_str(raw_json).map_err(|e| {
+        if e.is_syntax() || e.is_eof() {
+            return BattleToolsError::JSONParsingError(e);
+        }
+        let mut deserializer = serde_json::Deserializer::from_str(raw_json);
+        match serde_path_to_error::deserialize::<_, T>(&mut deserializer) {
+            Err(e) if e.path().to_string() != "." => {
+                BattleToolsError::InvalidLog(format!("`{}`: {}", e.path(), e.inner()))
+            }
+            _ => BattleToolsError::InvalidLog(e.to_string()),
+        }
+    })
+}
+
+/// Methods for the properties that `BattleLog` and `BattleHeader` share
+macro_rules! battle_properties {
+    ($battle:ident) => {
+        impl<'a> $battle<'a> {
+            /// Parses a battle log's JSON; see `parse_log` for the errors this gives
+            pub fn parse(raw_json: &'a str) -> Result<Self, BattleToolsError> {
+                parse_log(raw_json)
+            }
+
+            pub fn player(&self, side: Side) -> &str {
+                match side {
+                    Side::P1 => &self.p1,
+                    Side::P2 => &self.p2,
+                }
+            }
+
+            /// Which side the user with the given ID played on, if either
+            pub fn side_of(&self, user_id: &str) -> Option<Side> {
+                [Side::P1, Side::P2]
+                    .into_iter()
+                    .find(|&side| to_id(self.player(side)) == user_id)
+            }
+
+            pub fn rating(&self, side: Side) -> Option<&Rating<'a>> {
+                match side {
+                    Side::P1 => self.p1rating.as_ref(),
+                    Side::P2 => self.p2rating.as_ref(),
+                }
+            }
+
+            /// Whether the battle ended in a tie (PS writes an empty `winner`)
+            pub fn is_tie(&self) -> bool {
+                self.winner.is_empty()
+            }
+
+            /// The format's ID (like `gen8randombattle`).
+            ///
+            /// Logs without a `format` property fall back to the format in the room ID, or `unknown` if there isn't one.
+            pub fn format_id(&self) -> &str {
+                match (&self.format, &self.roomid) {
+                    (Some(format), _) => format,
+                    (None, Some(roomid)) => roomid
+                        .strip_prefix("battle-")
+                        .and_then(|rest| rest.rsplit_once('-'))
+                        .map_or("unknown", |(format, _)| format),
+                    (None, None) => "unknown",
+                }
+            }
+        }
+    };
+}
+
+battle_properties!(BattleLog);
+battle_properties!(BattleHeader);
+
+impl<'a> BattleHeader<'a> {
+    /// The side that won, or `None` if it was a tie (or the winner's name doesn't match either player's)
+    pub fn winner(&self) -> Option<Side> {
+        if self.is_tie() {
+            return None;
+        }
+        self.side_of(&to_id(&self.winner))
+    }
+
+    pub fn is_forfeit(&self) -> bool {
+        self.end_type.as_deref() == Some("forfeit")
+    }
+
+    pub fn team(&self, side: Side) -> &[TeamMember<'a>] {
+        match side {
+            Side::P1 => &self.p1team,
+            Side::P2 => &self.p2team,
+        }
+    }
+}
+
+impl<'a> BattleLog<'a> {
+    pub fn team(&self, side: Side) -> &[PokemonSet<'a>] {
+        match side {
+            Side::P1 => &self.p1team,
+            Side::P2 => &self.p2team,
+        }
+    }
+
+    /// The format's full name (like "[Gen 8] Random Battle") from the `|tier|` line, falling back to its ID
+    pub fn format_name(&self) -> &str {
+        self.log
+            .iter()
+            .find_map(|line| match Line::parse(line).event {
+                Event::Tier(tier) => Some(tier),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.format_id())
+    }
+
+    /// The IDs of every name each player had during the battle, starting with the name in `p1`/`p2`.
+    ///
+    /// Names are found in `|player|` lines and in `|N|` lines renaming one of a player's earlier names.
+    /// Players join the room with a `☆` before their name, so a `|j|` with a name that isn't known yet
+    /// is also counted if only one player hasn't been seen joining.
+    pub fn player_ids(&self) -> [Vec<String>; 2] {
+        let mut ids = [vec![to_id(&self.p1)], vec![to_id(&self.p2)]];
+        let mut joined = [false, false];
+        let add = |ids: &mut Vec<String>, id: String| {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        };
+
+        for line in &self.log {
+            match Line::parse(line).event {
+                Event::Player {
+                    side,
+                    name: Some(name),
+                    ..
+                } if !name.is_empty() => add(&mut ids[side as usize], to_id(name)),
+                Event::Rename { user, old_id, .. } => {
+                    let old_id = to_id(old_id);
+                    if let Some(side_ids) =
+                        ids.iter_mut().find(|side_ids| side_ids.contains(&old_id))
+                    {
+                        add(side_ids, to_id(user));
+                    }
+                }
+                Event::Join { user, .. } if user.starts_with('☆') => {
+                    let id = to_id(user);
+                    match ids.iter().position(|side_ids| side_ids.contains(&id)) {
+                        Some(side) => joined[side] = true,
+                        None => {
+                            let mut unjoined = (0..2).filter(|&side| !joined[side]);
+                            if let (Some(side), None) = (unjoined.next(), unjoined.next()) {
+                                add(&mut ids[side], id);
+                                joined[side] = true;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        ids
+    }
+}
+
+/// The number at the end of a room ID (like `battle-gen8ou-1234`), if there is one
+pub fn room_number(roomid: &str) -> Option<u64> {
+    roomid.rsplit('-').next()?.parse().ok()
+}
+
+/// A string that's borrowed from the JSON when possible.
+/// (Serde only borrows `Cow`s that are directly in a struct, not ones in an `Option` or `Vec`.)
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+pub fn borrowed_option<'de: 'a, 'a, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'a, str>>, D::Error> {
+    Ok(Option::<BorrowedStr>::deserialize(deserializer)?.map(|s| s.0))
+}
+
+/// Also accepts `null`, as an empty list
+fn borrowed_strings<'de: 'a, 'a, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Cow<'a, str>>, D::Error> {
+    Ok(Option::<Vec<BorrowedStr>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| s.0)
+        .collect())
+}
+
+fn nullable_team<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Accepts numbers, numeric strings, and `null`
+struct LenientNumberVisitor;
+
+impl<'de> Visitor<'de> for LenientNumberVisitor {
+    type Value = Option<f64>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number, a numeric string, or null")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(Some(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(Some(value as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Some(value as f64))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Some(number)),
+            _ => Err(E::invalid_value(Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+fn lenient_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    deserializer.deserialize_any(LenientNumberVisitor)
+}
+
+/// Like `lenient_number`, but the number has to be a non-negative integer
+fn lenient_count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    match lenient_number(deserializer)? {
+        Some(number) if number >= 0.0 && number.fract() == 0.0 && number <= u32::MAX as f64 => {
+            Ok(Some(number as u32))
+        }
+        Some(number) => Err(de::Error::invalid_value(
+            Unexpected::Float(number),
+            &"a non-negative integer",
+        )),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn sample_json() -> String {
+        std::fs::read_to_string("src/benchmark-data.json").unwrap()
+    }
+
+    #[test]
+    fn player_ids() {
+        let json = sample_json();
+        let mut log = BattleLog::parse(&json).unwrap();
+        // the sample's `|player|` line has an older name than `p2`
+        assert_eq!(
+            log.player_ids(),
+            [
+                vec![String::from("annika")],
+                vec![String::from("rusthaters"), String::from("rusthater")]
+            ]
+        );
+
+        log.log.push(Cow::Borrowed("|N|☆Annika Alt|annika"));
+        log.log.push(Cow::Borrowed("|N|☆Annika Alt 2|annikaalt"));
+        // not a rename of a player
+        log.log.push(Cow::Borrowed("|n| Spectator|annikaalt3"));
+        assert_eq!(
+            log.player_ids()[0],
+            vec![
+                String::from("annika"),
+                String::from("annikaalt"),
+                String::from("annikaalt2")
+            ]
+        );
+    }
+
+    #[test]
+    fn player_ids_from_joins() {
+        let json = sample_json();
+        let mut log = BattleLog::parse(&json).unwrap();
+        log.log = vec![
+            Cow::Borrowed("|j|☆Annika"),
+            Cow::Borrowed("|j| Spectator"),
+            Cow::Borrowed("|j|☆Old Name"),
+            Cow::Borrowed("|j|☆Someone Else"),
+        ];
+        assert_eq!(
+            log.player_ids(),
+            [
+                vec![String::from("annika")],
+                vec![String::from("rusthaters"), String::from("oldname")]
+            ]
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let json = sample_json();
+        let log = BattleLog::parse(&json).unwrap();
+
+        assert_eq!(log.player(Side::P1), "Annika");
+        assert_eq!(log.player(Side::P2), "Rust Haters");
+        assert_eq!(log.side_of("rusthaters"), Some(Side::P2));
+        assert_eq!(log.turns, Some(2));
+        assert_eq!(log.format_id(), "gen8randombattle");
+        assert_eq!(
+            log.timestamp.as_deref(),
+            Some("Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)")
+        );
+        assert_eq!(log.team(Side::P1)[0].species, "Rotom-Fan");
+        assert_eq!(log.team(Side::P2).len(), 6);
+
+        // numbers written as strings
+        let p2rating = log.rating(Side::P2).unwrap();
+        assert_eq!(p2rating.r, Some(1516.9377700433));
+        assert_eq!(p2rating.w, Some(4));
+        assert_eq!(log.rating(Side::P1).unwrap().elo, Some(1400.4859871929));
+
+        // strings without escapes are borrowed
+        assert!(matches!(log.p1, Cow::Borrowed(_)));
+        assert!(matches!(log.log[0], Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn header() {
+        let json = sample_json();
+        let header = BattleHeader::parse(&json).unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+
+        assert_eq!(header.player(Side::P1), log.player(Side::P1));
+        assert_eq!(header.winner(), Some(Side::P1));
+        assert!(!header.is_forfeit());
+        assert_eq!(header.turns, log.turns);
+        assert_eq!(header.format_id(), log.format_id());
+        assert_eq!(
+            header.rating(Side::P2).unwrap().elo,
+            log.rating(Side::P2).unwrap().elo
+        );
+        for side in [Side::P1, Side::P2] {
+            let header_species = header.team(side).iter().map(|pokemon| &pokemon.species);
+            let log_species = log.team(side).iter().map(|pokemon| &pokemon.species);
+            assert!(header_species.eq(log_species));
+        }
+
+        // errors are the same as for the full log
+        assert!(matches!(
+            BattleHeader::parse(r#"{"winner":"","p1":"A"}"#),
+            Err(BattleToolsError::InvalidLog(_))
+        ));
+        assert!(matches!(
+            BattleHeader::parse(json.trim_end().trim_end_matches('}')),
+            Err(BattleToolsError::JSONParsingError(_))
+        ));
+    }
+
+    #[test]
+    fn ties_and_missing_ratings() {
+        let json = sample_json()
+            .replace(r#""winner":"Annika""#, r#""winner":"""#)
+            .replace(r#""p1rating":{"#, r#""p1rating":null,"unused":{"#);
+        let log = BattleHeader::parse(&json).unwrap();
+        assert!(log.is_tie());
+        assert_eq!(log.winner(), None);
+        assert!(log.rating(Side::P1).is_none());
+        assert!(log.rating(Side::P2).is_some());
+    }
+
+    #[test]
+    fn missing_timestamp_and_format() {
+        let json = sample_json()
+            .replace(
+                r#""timestamp":"Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)","#,
+                "",
+            )
+            .replace(r#","format":"gen8randombattle""#, "");
+        let log = BattleLog::parse(&json).unwrap();
+        assert!(log.timestamp.is_none());
+        assert!(log.format.is_none());
+        // the format is taken from the room ID instead
+        assert_eq!(log.format_id(), "gen8randombattle");
+        assert_eq!(log.format_name(), "[Gen 8] Random Battle");
+
+        let json = json.replace(r#""roomid":"battle-gen8randombattle-1","#, "");
+        assert_eq!(BattleLog::parse(&json).unwrap().format_id(), "unknown");
+    }
+
+    #[test]
+    fn errors() {
+        let kind_and_message = |json: &str| {
+            let error = BattleLog::parse(json).unwrap_err();
+            (error.kind(), error.to_string())
+        };
+
+        assert_eq!(kind_and_message("this isn't JSON").0, "JSONParsingError");
+
+        let json = sample_json().replace(r#""p1":"Annika""#, r#""p1":null"#);
+        let (kind, message) = kind_and_message(&json);
+        assert_eq!(kind, "InvalidLog");
+        assert!(
+            message.starts_with("invalid battle log: `p1`: invalid type: null"),
+            "{}",
+            message
+        );
+
+        let json = sample_json().replace(r#""elo":1400.4859871929"#, r#""elo":"high""#);
+        let (_, message) = kind_and_message(&json);
+        assert!(
+            message.starts_with("invalid battle log: `p1rating.elo`: "),
+            "{}",
+            message
+        );
+
+        let json = sample_json().replace(r#""p2":"Rust Haters","#, "");
+        let (_, message) = kind_and_message(&json);
+        assert!(
+            message.starts_with("invalid battle log: missing field `p2`"),
+            "{}",
+            message
+        );
+    }
+}
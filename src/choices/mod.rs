@@ -0,0 +1,59 @@
+// Code for exporting the choices players made in battles.
+use serde_derive::Serialize;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{battle_log::BattleLog, directory::LogParser, input_log::Choice, BattleToolsError};
+
+/// Prints every command in each battle's `inputLog` as a line of JSON
+pub struct ChoiceExporter;
+
+/// A line of the output: a choice, along with the battle it's from
+#[derive(Serialize)]
+struct ChoiceRecord<'a> {
+    /// The log's file name, without `.log.json`
+    battle: &'a str,
+    #[serde(flatten)]
+    choice: Choice<'a>,
+}
+
+impl LogParser<()> for ChoiceExporter {
+    fn new_accumulator(&self) {}
+
+    fn handle_log_file(
+        &self,
+        raw_json: String,
+        path: &Path,
+        _: &mut (),
+    ) -> Result<(), BattleToolsError> {
+        let log = BattleLog::parse(&raw_json)?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                BattleToolsError::PathConversion(format!("Can't get file name of {:?}", path))
+            })?;
+        let battle = file_name.strip_suffix(".log.json").unwrap_or(file_name);
+
+        // Each battle's choices are written all at once, so they aren't interleaved with other battles'
+        let mut output = vec![];
+        for line in &log.input_log {
+            let record = ChoiceRecord {
+                battle,
+                choice: Choice::parse(line)?,
+            };
+            serde_json::to_writer(&mut output, &record)?;
+            output.push(b'\n');
+        }
+        io::stdout().lock().write_all(&output)?;
+        Ok(())
+    }
+
+    fn merge_results(&self, _: (), _: ()) {}
+
+    fn handle_results(&mut self, _: ()) -> Result<(), BattleToolsError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+// Code for writing CSV output.
+
+/// Quotes a CSV field if it contains anything that would otherwise be misread
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(csv_field("logs/2021-01"), "logs/2021-01");
+        assert_eq!(csv_field(r#"a,b "c""#), r#""a,b ""c""""#);
+    }
+}
@@ -0,0 +1,235 @@
+// Code for the dates found in PS's log directory names and battle timestamps.
+use std::{fmt, str::FromStr};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A calendar date, as used in PS's `YYYY-MM-DD` log directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// A moment in time, parsed from a log's `timestamp` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogTime {
+    /// The local date
+    pub date: LogDate,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// The local time zone's offset from UTC, in minutes
+    pub utc_offset: i16,
+}
+
+/// The date that a log directory's name refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryDate {
+    /// A `YYYY-MM-DD` directory
+    Day(LogDate),
+    /// A `YYYY-MM` directory: (year, month)
+    Month(u16, u8),
+}
+
+impl LogDate {
+    /// Parses the date from a log's `timestamp` property,
+    /// e.g. "Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)"
+    pub fn from_timestamp(timestamp: &str) -> Option<Self> {
+        let mut parts = timestamp.split_whitespace().skip(1);
+        let month_name = parts.next()?;
+        let month = MONTHS.iter().position(|m| *m == month_name)? as u8 + 1;
+        let day = parts.next()?.parse().ok()?;
+        let year = parts.next()?.parse().ok()?;
+        Some(Self { year, month, day })
+    }
+}
+
+impl LogTime {
+    /// Parses a log's `timestamp` property,
+    /// e.g. "Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)"
+    pub fn from_timestamp(timestamp: &str) -> Option<Self> {
+        let date = LogDate::from_timestamp(timestamp)?;
+        let mut parts = timestamp.split_whitespace().skip(4);
+
+        let mut time = parts.next()?.split(':').map(|part| part.parse().ok());
+        let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+        let zone = parts.next()?.strip_prefix("GMT")?;
+        let sign = match zone.get(..1)? {
+            "+" => 1,
+            "-" => -1,
+            _ => return None,
+        };
+        let hours: i16 = zone.get(1..3)?.parse().ok()?;
+        let minutes: i16 = zone.get(3..5)?.parse().ok()?;
+
+        Some(Self {
+            date,
+            hour,
+            minute,
+            second,
+            utc_offset: sign * (hours * 60 + minutes),
+        })
+    }
+
+    /// Seconds since the Unix epoch
+    pub fn unix_time(&self) -> i64 {
+        // from http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let (month, day) = (self.date.month as i64, self.date.day as i64);
+        let year = self.date.year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+            - self.utc_offset as i64 * 60
+    }
+}
+
+impl DirectoryDate {
+    /// Parses a directory name like `2021-08` or `2021-08-01`
+    pub fn from_dir_name(name: &str) -> Option<Self> {
+        let parts = name.split('-').collect::<Vec<_>>();
+        let is_numeric = |s: &str, len| s.len() == len && s.bytes().all(|b| b.is_ascii_digit());
+        match parts[..] {
+            [year, month] if is_numeric(year, 4) && is_numeric(month, 2) => Some(
+                DirectoryDate::Month(year.parse().ok()?, month.parse().ok()?),
+            ),
+            [year, month, day]
+                if is_numeric(year, 4) && is_numeric(month, 2) && is_numeric(day, 2) =>
+            {
+                Some(DirectoryDate::Day(LogDate {
+                    year: year.parse().ok()?,
+                    month: month.parse().ok()?,
+                    day: day.parse().ok()?,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for LogDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match DirectoryDate::from_dir_name(s) {
+            Some(DirectoryDate::Day(date))
+                if (1..=12).contains(&date.month) && (1..=31).contains(&date.day) =>
+            {
+                Ok(date)
+            }
+            _ => Err(format!("'{}' is not a date in YYYY-MM-DD format", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Formats the time in ISO 8601 format, like `2020-11-21T17:05:04-05:00`
+impl fmt::Display for LogTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.utc_offset < 0 { '-' } else { '+' };
+        let offset = self.utc_offset.unsigned_abs();
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+            self.date,
+            self.hour,
+            self.minute,
+            self.second,
+            sign,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates() {
+        let date = LogDate {
+            year: 2021,
+            month: 8,
+            day: 1,
+        };
+        assert_eq!("2021-08-01".parse::<LogDate>(), Ok(date));
+        assert_eq!(date.to_string(), "2021-08-01");
+        assert!("2021-8-1".parse::<LogDate>().is_err());
+        assert!("2021-13-01".parse::<LogDate>().is_err());
+        assert!("gen8ou".parse::<LogDate>().is_err());
+
+        assert_eq!(
+            DirectoryDate::from_dir_name("2021-08-01"),
+            Some(DirectoryDate::Day(date))
+        );
+        assert_eq!(
+            DirectoryDate::from_dir_name("2021-08"),
+            Some(DirectoryDate::Month(2021, 8))
+        );
+        assert_eq!(DirectoryDate::from_dir_name("gen8randombattle"), None);
+        assert_eq!(
+            DirectoryDate::from_dir_name("battle-gen8ou-1.log.json"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(
+            LogDate::from_timestamp("Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)"),
+            Some(LogDate {
+                year: 2020,
+                month: 11,
+                day: 21
+            })
+        );
+        assert_eq!(
+            LogDate::from_timestamp("Wed Nov 01 1970 00:00:01 GMT-0400 (Eastern Daylight Time)"),
+            Some(LogDate {
+                year: 1970,
+                month: 11,
+                day: 1
+            })
+        );
+        assert_eq!(
+            LogDate::from_timestamp("Sat Nov 21 2020 17"),
+            Some(LogDate {
+                year: 2020,
+                month: 11,
+                day: 21
+            })
+        );
+        assert_eq!(LogDate::from_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn parse_times() {
+        let time =
+            LogTime::from_timestamp("Sat Nov 21 2020 17:05:04 GMT-0500 (Eastern Standard Time)")
+                .unwrap();
+        assert_eq!((time.hour, time.minute, time.second), (17, 5, 4));
+        assert_eq!(time.utc_offset, -300);
+        assert_eq!(time.unix_time(), 1605996304);
+        assert_eq!(time.to_string(), "2020-11-21T17:05:04-05:00");
+
+        let epoch = LogTime::from_timestamp("Thu Jan 01 1970 05:30:00 GMT+0530").unwrap();
+        assert_eq!(epoch.unix_time(), 0);
+        assert_eq!(epoch.to_string(), "1970-01-01T05:30:00+05:30");
+
+        assert_eq!(LogTime::from_timestamp("Sat Nov 21 2020 17"), None);
+        assert_eq!(LogTime::from_timestamp("Sat Nov 21 2020 17:05:04"), None);
+    }
+}
@@ -0,0 +1,439 @@
+// Code for deciding which directories and log files get parsed
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::{
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::Manifest;
+use crate::{
+    battle_log::BattleHeader,
+    date::{DirectoryDate, LogDate},
+};
+
+/// Decides which directories and log files are parsed
+#[derive(Debug, Default)]
+pub struct DirectoryFilter {
+    /// If there are any include patterns, only logs matching one of them (or inside a directory matching one) are parsed
+    pub include: Vec<PathPattern>,
+    /// Directories and files matching any of these patterns are ignored
+    pub exclude: Vec<PathPattern>,
+    /// Logs from before this date are ignored
+    pub since: Option<LogDate>,
+    /// Logs from after this date are ignored
+    pub until: Option<LogDate>,
+    /// How many logs were skipped because they didn't match any include pattern
+    pub not_included: AtomicUsize,
+    /// How many logs were skipped because they had no timestamp to check against the date range
+    pub undated: AtomicUsize,
+    /// If there's a manifest, files it says were already parsed are skipped
+    pub manifest: Option<Manifest>,
+}
+
+/// A pattern matched against paths relative to the directory being parsed.
+///
+/// Patterns are globs by default; globs without a `/` are matched against just the name of each directory
+/// or file, and globs with a `/` are matched against the whole relative path. Patterns starting with `re:`
+/// are regular expressions, which are searched for in the whole relative path.
+#[derive(Debug)]
+pub struct PathPattern {
+    source: String,
+    matcher: PatternMatcher,
+    /// How many directories or files this pattern has matched
+    matches: AtomicUsize,
+}
+
+#[derive(Debug)]
+enum PatternMatcher {
+    Name(GlobMatcher),
+    Path(GlobMatcher),
+    Regex(Regex),
+}
+
+impl PathPattern {
+    /// Returns whether the relative path matches this pattern, and counts the match if so
+    fn check(&self, relative_path: &Path) -> bool {
+        let is_match = match &self.matcher {
+            PatternMatcher::Name(glob) => relative_path
+                .file_name()
+                .is_some_and(|name| glob.is_match(name)),
+            PatternMatcher::Path(glob) => glob.is_match(relative_path),
+            PatternMatcher::Regex(regex) => regex.is_match(&relative_path.to_string_lossy()),
+        };
+        if is_match {
+            self.matches.fetch_add(1, Ordering::Relaxed);
+        }
+        is_match
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.load(Ordering::Relaxed)
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let matcher = if let Some(regex) = s.strip_prefix("re:") {
+            PatternMatcher::Regex(Regex::new(regex).map_err(|e| e.to_string())?)
+        } else if s.contains('/') {
+            let glob = GlobBuilder::new(s)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| e.to_string())?;
+            PatternMatcher::Path(glob.compile_matcher())
+        } else {
+            PatternMatcher::Name(Glob::new(s).map_err(|e| e.to_string())?.compile_matcher())
+        };
+        Ok(Self {
+            source: s.to_string(),
+            matcher,
+            matches: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Whether all the logs in a directory are known to be within the date range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateCoverage {
+    /// Every log is in range (or there's no date range)
+    Full,
+    /// Each log's date has to be checked individually
+    Partial,
+}
+
+/// What the filter knows about a directory that's being parsed
+#[derive(Debug, Clone)]
+pub struct FilterState {
+    /// The directory's path, relative to the path passed to `handle_directories`
+    relative_path: PathBuf,
+    date_coverage: DateCoverage,
+    /// Whether the directory matched (or is inside a directory that matched) an include pattern
+    included: bool,
+}
+
+impl DirectoryFilter {
+    fn contains_date(&self, date: LogDate) -> bool {
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    /// Uses PS's `YYYY-MM` and `YYYY-MM-DD` directory names to work out which logs in a directory are in the date range.
+    ///
+    /// Returns `None` if the directory is entirely outside of the date range.
+    fn directory_coverage(&self, name: &OsStr, parent: DateCoverage) -> Option<DateCoverage> {
+        if parent == DateCoverage::Full {
+            return Some(DateCoverage::Full);
+        }
+
+        match name.to_str().and_then(DirectoryDate::from_dir_name) {
+            Some(DirectoryDate::Day(date)) => {
+                if self.contains_date(date) {
+                    Some(DateCoverage::Full)
+                } else {
+                    None
+                }
+            }
+            Some(DirectoryDate::Month(year, month)) => {
+                let first = LogDate {
+                    year,
+                    month,
+                    day: 1,
+                };
+                let last = LogDate {
+                    year,
+                    month,
+                    day: 31,
+                };
+                if self.since.is_some_and(|since| last < since)
+                    || self.until.is_some_and(|until| first > until)
+                {
+                    None
+                } else if self.contains_date(first) && self.contains_date(last) {
+                    Some(DateCoverage::Full)
+                } else {
+                    Some(DateCoverage::Partial)
+                }
+            }
+            None => Some(parent),
+        }
+    }
+
+    /// Returns whether the relative path matches an exclude pattern
+    fn is_excluded(&self, relative_path: &Path) -> bool {
+        // `any` would stop at the first match, but every matching pattern should be counted
+        self.exclude
+            .iter()
+            .filter(|pattern| pattern.check(relative_path))
+            .count()
+            > 0
+    }
+
+    fn is_included(&self, relative_path: &Path) -> bool {
+        self.include
+            .iter()
+            .filter(|pattern| pattern.check(relative_path))
+            .count()
+            > 0
+    }
+
+    /// Works out the state of a directory passed to `handle_directories`.
+    ///
+    /// The path may already include date directories (like `logs/2021-08/gen8ou`), so its components are
+    /// checked against the date range. Returns `None` if it's entirely outside of the date range.
+    pub fn root_state(&self, root: &Path) -> Option<FilterState> {
+        let initial_coverage = if self.since.is_none() && self.until.is_none() {
+            DateCoverage::Full
+        } else {
+            DateCoverage::Partial
+        };
+        let date_coverage = root
+            .components()
+            .try_fold(initial_coverage, |coverage, component| {
+                self.directory_coverage(component.as_os_str(), coverage)
+            })?;
+
+        Some(FilterState {
+            relative_path: PathBuf::new(),
+            date_coverage,
+            included: self.include.is_empty(),
+        })
+    }
+
+    /// Checks a subdirectory (or archive) of a directory with the given state.
+    ///
+    /// Returns `None` if the subdirectory should be skipped entirely.
+    pub fn enter_directory(&self, parent: &FilterState, name: &OsStr) -> Option<FilterState> {
+        let relative_path = parent.relative_path.join(name);
+        if self.is_excluded(&relative_path) {
+            return None;
+        }
+
+        Some(FilterState {
+            date_coverage: self.directory_coverage(name, parent.date_coverage)?,
+            included: parent.included || self.is_included(&relative_path),
+            relative_path,
+        })
+    }
+
+    /// Checks whether a file in a directory with the given state should be read
+    pub fn includes_file(&self, parent: &FilterState, name: &OsStr) -> bool {
+        let relative_path = parent.relative_path.join(name);
+        if self.is_excluded(&relative_path) {
+            return false;
+        }
+        if !parent.included && !self.is_included(&relative_path) {
+            self.not_included.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Checks a file inside an archive with the given state.
+    ///
+    /// Returns the state of the directory the file is in, or `None` if the file should be skipped.
+    pub fn enter_archive_member(
+        &self,
+        archive: &FilterState,
+        member: &Path,
+    ) -> Option<FilterState> {
+        let mut state = archive.clone();
+        for component in member.parent()?.components() {
+            state = self.enter_directory(&state, component.as_os_str())?;
+        }
+        if self.includes_file(&state, member.file_name()?) {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Checks a file given in a list of files, treating each directory in its path like a subdirectory.
+    ///
+    /// Returns the state of the directory the file is in, or `None` if the file should be skipped.
+    pub fn listed_file_state(&self, file: &Path) -> Option<FilterState> {
+        self.enter_archive_member(&self.root_state(Path::new(""))?, file)
+    }
+
+    /// Checks a log's `timestamp` property when its directories don't say whether it's in the date range.
+    ///
+    /// Logs that can't be parsed are let through, so the parser can report what's wrong with them.
+    pub fn includes_log(&self, raw_json: &str, parent: &FilterState) -> bool {
+        if parent.date_coverage == DateCoverage::Full {
+            return true;
+        }
+        let header = match BattleHeader::parse(raw_json) {
+            Ok(header) => header,
+            Err(_) => return true,
+        };
+        match header
+            .timestamp
+            .as_deref()
+            .and_then(LogDate::from_timestamp)
+        {
+            Some(date) => self.contains_date(date),
+            None => {
+                self.undated.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Describes how many directories and files each include and exclude pattern matched,
+    /// and how many logs had no date to check against the date range
+    pub fn report(&self) -> Vec<String> {
+        let mut report = vec![];
+        for pattern in &self.include {
+            report.push(format!(
+                "--include '{}' matched {} entries",
+                pattern,
+                pattern.match_count()
+            ));
+        }
+        if !self.include.is_empty() {
+            report.push(format!(
+                "{} files didn't match any --include pattern",
+                self.not_included.load(Ordering::Relaxed)
+            ));
+        }
+        for pattern in &self.exclude {
+            report.push(format!(
+                "--exclude '{}' pruned {} entries",
+                pattern,
+                pattern.match_count()
+            ));
+        }
+        let undated = self.undated.load(Ordering::Relaxed);
+        if undated > 0 {
+            report.push(format!(
+                "{} logs were skipped because they had no timestamp to check against --since and --until",
+                undated
+            ));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        pattern
+            .parse::<PathPattern>()
+            .unwrap()
+            .check(Path::new(path))
+    }
+
+    #[test]
+    fn pattern_matching() {
+        // globs without a slash match names at any depth
+        assert!(matches("*-test", "2021-08/gen9ou-test"));
+        assert!(!matches("*-test", "2021-08/test"));
+        assert!(!matches("*-test", "2021-08/gen9ou-test/2021-08-01"));
+        assert!(matches("gen9ou", "2021-08/gen9ou"));
+        assert!(matches(
+            "*.log.json",
+            "2021-08/gen9ou/2021-08-01/battle-gen9ou-1.log.json"
+        ));
+
+        // globs with a slash match the whole relative path
+        assert!(matches("2021-08/gen9*", "2021-08/gen9ou"));
+        assert!(!matches("2021-08/gen9*", "2021-08/gen9ou/2021-08-01"));
+        assert!(matches("**/2021-08-01", "2021-08/gen9ou/2021-08-01"));
+
+        // regexes search the whole relative path
+        assert!(matches(
+            "re:gen9(ou|randombattle)$",
+            "2021-08/gen9randombattle"
+        ));
+        assert!(!matches("re:gen9(ou|randombattle)$", "2021-08/gen9ubers"));
+        assert!(matches("re:6", "day1/16.json"));
+
+        assert!("re:(".parse::<PathPattern>().is_err());
+        assert!("[".parse::<PathPattern>().is_err());
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = DirectoryFilter {
+            include: vec![
+                "gen9ou".parse().unwrap(),
+                "gen9randombattle".parse().unwrap(),
+            ],
+            exclude: vec!["*-test".parse().unwrap()],
+            ..Default::default()
+        };
+        let root = filter.root_state(Path::new("logs")).unwrap();
+        let month = filter
+            .enter_directory(&root, OsStr::new("2021-08"))
+            .unwrap();
+
+        let gen9ou = filter
+            .enter_directory(&month, OsStr::new("gen9ou"))
+            .unwrap();
+        assert!(filter.includes_file(&gen9ou, OsStr::new("battle-gen9ou-1.log.json")));
+
+        let gen9ubers = filter
+            .enter_directory(&month, OsStr::new("gen9ubers"))
+            .unwrap();
+        assert!(!filter.includes_file(&gen9ubers, OsStr::new("battle-gen9ubers-1.log.json")));
+
+        assert!(filter
+            .enter_directory(&month, OsStr::new("gen9ou-test"))
+            .is_none());
+        assert!(filter
+            .enter_archive_member(
+                &month,
+                Path::new("gen9randombattle/2021-08-01/battle-gen9randombattle-2.log.json")
+            )
+            .is_some());
+
+        assert_eq!(
+            filter.report(),
+            vec![
+                "--include 'gen9ou' matched 1 entries",
+                "--include 'gen9randombattle' matched 1 entries",
+                "1 files didn't match any --include pattern",
+                "--exclude '*-test' pruned 1 entries",
+            ]
+        );
+    }
+
+    #[test]
+    fn log_dates() {
+        let filter = DirectoryFilter {
+            since: Some(LogDate {
+                year: 2023,
+                month: 10,
+                day: 1,
+            }),
+            ..Default::default()
+        };
+        // a directory that isn't named after a date doesn't say which of its logs are in range
+        let root = filter.root_state(Path::new("logs")).unwrap();
+        let in_range = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let too_early = std::fs::read_to_string("src/test-logs/gen8doublesou.log.json").unwrap();
+        assert!(filter.includes_log(&in_range, &root));
+        assert!(!filter.includes_log(&too_early, &root));
+
+        let undated = in_range.replace("\"timestamp\"", "\"time\"");
+        assert!(!filter.includes_log(&undated, &root));
+        // logs that can't be parsed are left for the parser to report
+        assert!(filter.includes_log("{", &root));
+        assert_eq!(
+            filter.report(),
+            vec!["1 logs were skipped because they had no timestamp to check against --since and --until"]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// Code for remembering which files have already been parsed, so later runs can skip them
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::UNIX_EPOCH,
+};
+
+use crate::BattleToolsError;
+
+/// Identifies a version of a file, to tell whether it's changed since it was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Seconds since the Unix epoch for files and tar members; the packed DOS date and time for zip members
+    pub modified: u64,
+}
+
+impl FileStamp {
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs()),
+        }
+    }
+}
+
+/// The files parsed in previous runs, along with the parser's state after parsing them.
+///
+/// Files are identified by their canonical path, so the same logs are recognized however their directory is
+/// spelled on the command line, and skipped if their stamp hasn't changed since they were recorded. Only files
+/// that were parsed successfully are recorded, so ones that failed are tried again on the next run. A file that
+/// was modified can't be parsed again, since its old results can't be removed from the saved state; these are
+/// skipped too, and `check_changed` fails the run before any results are merged.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    previous: HashMap<String, FileStamp>,
+    parsed: Mutex<Vec<(String, FileStamp)>>,
+    state: Option<serde_json::Value>,
+    /// How many files were skipped because they were unchanged
+    skipped: AtomicUsize,
+    /// Recorded files that changed since they were parsed
+    changed: Mutex<Vec<String>>,
+}
+
+/// What's actually written to the manifest file
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    files: BTreeMap<String, FileStamp>,
+    state: serde_json::Value,
+}
+
+impl Manifest {
+    /// Loads a manifest, or starts a new one if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, BattleToolsError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file: ManifestFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self {
+            previous: file.files.into_iter().collect(),
+            state: Some(file.state),
+            ..Default::default()
+        })
+    }
+
+    /// The parser state saved by the previous run, if there was one
+    pub fn take_state(&mut self) -> Option<serde_json::Value> {
+        self.state.take()
+    }
+
+    /// Returns whether a file was already parsed, so it shouldn't be parsed again.
+    /// Unchanged files are counted as skipped, and changed ones are remembered for `check_changed`.
+    pub fn is_recorded(&self, path: &Path, stamp: FileStamp) -> bool {
+        let key = key(path);
+        match self.previous.get(&key) {
+            Some(previous) if *previous == stamp => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Some(_) => {
+                self.changed.lock().unwrap().push(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records that a file was parsed successfully
+    pub fn record(&self, path: &Path, stamp: FileStamp) {
+        self.parsed.lock().unwrap().push((key(path), stamp));
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// How many files were already in the manifest but had changed, so they weren't parsed again
+    pub fn changed(&self) -> usize {
+        self.changed.lock().unwrap().len()
+    }
+
+    /// Fails if any recorded file changed since it was parsed, since merging its new results would count it twice
+    pub fn check_changed(&self) -> Result<(), BattleToolsError> {
+        if self.changed() == 0 {
+            return Ok(());
+        }
+        Err(BattleToolsError::InvalidManifest(format!(
+            "{} files in it (such as {}) changed since they were parsed, and their old results can't be \
+             removed; delete the manifest to start over",
+            self.changed(),
+            self.changed.lock().unwrap()[0]
+        )))
+    }
+
+    /// Writes the manifest (every file parsed in this run or a previous one) along with the parser's new state
+    pub fn save(self, path: &Path, state: serde_json::Value) -> Result<(), BattleToolsError> {
+        let mut files: BTreeMap<String, FileStamp> = self.previous.into_iter().collect();
+        files.extend(self.parsed.into_inner().unwrap());
+        let json = serde_json::to_string(&ManifestFile { files, state })?;
+
+        // Write to a temporary file first, so a crash can't leave behind a manifest that doesn't match its state
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        fs::write(&temporary_path, json)?;
+        fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+}
+
+/// The key a file is stored under: its canonical path, or for archive members (`archive!/member`),
+/// the archive's canonical path followed by the member's path
+fn key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let (on_disk, member) = match path.split_once("!/") {
+        Some((archive, member)) => (archive, Some(member)),
+        None => (&*path, None),
+    };
+    let canonical = fs::canonicalize(on_disk).unwrap_or_else(|_| PathBuf::from(on_disk));
+    match member {
+        Some(member) => format!("{}!/{}", canonical.display(), member),
+        None => canonical.to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        fs::create_dir_all("test-scratch").unwrap();
+        let path = Path::new("test-scratch/manifest-unit-test.json");
+        let _ = fs::remove_file(path);
+
+        let mut manifest = Manifest::load(path).unwrap();
+        assert!(manifest.take_state().is_none());
+        let stamp = FileStamp {
+            size: 100,
+            modified: 1_600_000_000,
+        };
+        manifest.record(Path::new("logs/1.log.json"), stamp);
+        manifest
+            .save(path, serde_json::json!({"battles": 1}))
+            .unwrap();
+
+        let mut manifest = Manifest::load(path).unwrap();
+        assert_eq!(
+            manifest.take_state(),
+            Some(serde_json::json!({"battles": 1}))
+        );
+        assert!(manifest.is_recorded(Path::new("logs/1.log.json"), stamp));
+        assert!(manifest.check_changed().is_ok());
+        assert!(!manifest.is_recorded(Path::new("logs/2.log.json"), stamp));
+        // a changed file isn't parsed again, and fails the run
+        assert!(manifest.is_recorded(
+            Path::new("logs/1.log.json"),
+            FileStamp { size: 101, ..stamp }
+        ));
+        assert_eq!(manifest.skipped(), 1);
+        assert_eq!(manifest.changed(), 1);
+        assert!(matches!(
+            manifest.check_changed(),
+            Err(BattleToolsError::InvalidManifest(_))
+        ));
+    }
+
+    #[test]
+    fn path_spellings() {
+        let dir = Path::new("test-scratch/manifest-spellings");
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("1.log.json"), "{}").unwrap();
+        fs::write(dir.join("logs.zip"), "").unwrap();
+        let stamp = FileStamp {
+            size: 2,
+            modified: 1_600_000_000,
+        };
+
+        let manifest = Manifest::default();
+        manifest.record(&dir.join("1.log.json"), stamp);
+        manifest.record(
+            Path::new("test-scratch/manifest-spellings/logs.zip!/2.log.json"),
+            stamp,
+        );
+        let manifest = Manifest {
+            previous: manifest.parsed.into_inner().unwrap().into_iter().collect(),
+            ..Default::default()
+        };
+
+        let absolute = fs::canonicalize(dir).unwrap();
+        for spelling in [
+            PathBuf::from("./test-scratch/manifest-spellings/1.log.json"),
+            PathBuf::from("test-scratch/manifest-spellings/../manifest-spellings/1.log.json"),
+            absolute.join("1.log.json"),
+            // archive members are keyed on the archive's path, since they aren't on disk themselves
+            PathBuf::from("./test-scratch/manifest-spellings/logs.zip!/2.log.json"),
+            absolute.join("logs.zip!/2.log.json"),
+        ] {
+            assert!(manifest.is_recorded(&spelling, stamp), "{:?}", spelling);
+        }
+    }
+}
@@ -0,0 +1,813 @@
+// Code for handling directories
+mod filter;
+mod manifest;
+mod progress;
+mod report;
+
+use rayon::prelude::*;
+use std::{
+    fs::{self, File},
+    io::Read,
+    marker::{Send, Sync},
+    path::{Path, PathBuf},
+};
+
+use crate::BattleToolsError;
+use filter::FilterState;
+pub use filter::{DirectoryFilter, PathPattern};
+use manifest::FileStamp;
+pub use manifest::Manifest;
+pub use progress::{Progress, ProgressFormat};
+pub use report::ErrorReport;
+
+/// How many archive members are read into memory before being parsed in parallel
+const ARCHIVE_BATCH_SIZE: usize = 512;
+
+/// Anything that wants to parse logs should implement this
+///
+/// Results are combined like Rayon's `fold`/`reduce`: each worker thread adds the logs it parses to its
+/// own accumulator (created with `new_accumulator`), and accumulators are then combined with `merge_results`.
+/// This means memory usage depends on the size of an accumulator, not on the number of logs parsed.
+pub trait LogParser<R> {
+    /// Creates an empty accumulator
+    fn new_accumulator(&self) -> R;
+    /// Parses an individual log file's JSON, adding its results to the accumulator
+    fn handle_log_file(
+        &self,
+        raw_json: String,
+        file_path: &Path,
+        accumulator: &mut R,
+    ) -> Result<(), BattleToolsError>;
+    /// Combines two accumulators into one
+    fn merge_results(&self, a: R, b: R) -> R;
+    /// Parses the results from an entire directory.
+    /// Guaranteed to be only called once per invocation of ParallelDirectoryParser::handle_directory;
+    /// if subdirectories are found, their parse results will be merged together and passed to handle_results.
+    fn handle_results(&mut self, results: R) -> Result<(), BattleToolsError>;
+}
+
+/// A `LogParser` whose results can be saved in a `Manifest`, so that later runs can continue from them
+pub trait ResumableParser {
+    fn save_state(&self) -> Result<serde_json::Value, BattleToolsError>;
+    /// Replaces the parser's state with one returned by `save_state`
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), BattleToolsError>;
+}
+
+/// Iterates over directories and executes code on each log file in parallel.
+pub trait ParallelDirectoryParser<R> {
+    /// `filter` decides which directories and files are parsed, and `progress` reports on how parsing is going.
+    /// Files and directories that can't be parsed are skipped and returned in the `ErrorReport`.
+    ///
+    /// `dirs` may also contain `.tar.gz`, `.tar.zst`, or `.zip` archives; each file within them is
+    /// parsed with a path of the form `archive.tar.gz!/path/within/archive.log.json`.
+    fn handle_directories(
+        &mut self,
+        dirs: Vec<PathBuf>,
+        filter: &DirectoryFilter,
+        progress: &Progress,
+    ) -> Result<ErrorReport, BattleToolsError>;
+
+    /// Like `handle_directories`, but parses exactly the given log files (which can't be archives).
+    /// Include and exclude patterns are matched against each path as it was given.
+    fn handle_files(
+        &mut self,
+        files: Vec<PathBuf>,
+        filter: &DirectoryFilter,
+        progress: &Progress,
+    ) -> Result<ErrorReport, BattleToolsError>;
+}
+
+/// Compressed archives that can be read in place of a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveKind::TarZst)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Builds the path passed to `LogParser::handle_log_file` for a file inside an archive
+fn archive_member_path(archive: &Path, member: &Path) -> PathBuf {
+    PathBuf::from(format!("{}!/{}", archive.display(), member.display()))
+}
+
+/// Reads a whole archive member, failing like `fs::read_to_string` does on plain files if it isn't UTF-8.
+/// The member is always read to the end, so a bad one doesn't leave the archive stream partway through it.
+fn read_member<Rd: Read>(member: &mut Rd) -> std::io::Result<String> {
+    let mut contents = vec![];
+    member.read_to_end(&mut contents)?;
+    String::from_utf8(contents).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+/// Everything needed while walking directories
+struct Walker<'a, T> {
+    parser: &'a T,
+    filter: &'a DirectoryFilter,
+    errors: &'a ErrorReport,
+    progress: &'a Progress,
+}
+
+impl<T> Walker<'_, T> {
+    fn record_error(&self, path: &Path, error: &BattleToolsError) {
+        self.progress
+            .message(format_args!("Error parsing {}: {}", path.display(), error));
+        self.errors.record(path, error);
+    }
+
+    /// Whether a file is in the manifest, so it shouldn't be parsed again
+    fn is_recorded(&self, path: &Path, stamp: FileStamp) -> bool {
+        self.filter
+            .manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.is_recorded(path, stamp))
+    }
+
+    /// Parses a single log file, adding its results to `accumulator`.
+    /// If it's parsed successfully and we're keeping a manifest, its `stamp` is recorded.
+    fn parse_file<R>(
+        &self,
+        accumulator: &mut R,
+        raw_json: String,
+        path: &Path,
+        stamp: Option<FileStamp>,
+    ) where
+        T: LogParser<R>,
+    {
+        self.progress.finish_file(raw_json.len());
+        match self.parser.handle_log_file(raw_json, path, accumulator) {
+            Ok(()) => {
+                if let (Some(manifest), Some(stamp)) = (&self.filter.manifest, stamp) {
+                    manifest.record(path, stamp);
+                }
+            }
+            Err(e) => self.record_error(path, &e),
+        }
+    }
+
+    /// Parses a directory or archive, returning the merged results of every log within
+    fn parse_path<R>(&self, path: &Path, state: &FilterState) -> R
+    where
+        T: LogParser<R> + Sync,
+        R: Send,
+    {
+        let results = match ArchiveKind::from_path(path) {
+            Some(kind) if path.is_file() => self.parse_archive(path, kind, state),
+            _ => self.parse_directory(path, state),
+        };
+        results.unwrap_or_else(|e| {
+            self.record_error(path, &e);
+            self.parser.new_accumulator()
+        })
+    }
+
+    /// Reads and parses a log file that's passed the include and exclude patterns
+    fn read_file<R>(&self, accumulator: &mut R, path: &Path, state: &FilterState)
+    where
+        T: LogParser<R>,
+    {
+        let stamp = match self.filter.manifest.as_ref().map(|_| fs::metadata(path)) {
+            None => None,
+            Some(Ok(metadata)) => Some(FileStamp::from_metadata(&metadata)),
+            Some(Err(e)) => {
+                self.progress.finish_file(0);
+                self.record_error(path, &e.into());
+                return;
+            }
+        };
+        if stamp.is_some_and(|stamp| self.is_recorded(path, stamp)) {
+            self.progress.forget();
+            return;
+        }
+
+        match fs::read_to_string(path) {
+            Ok(raw_json) => {
+                if self.filter.includes_log(&raw_json, state) {
+                    self.parse_file(accumulator, raw_json, path, stamp);
+                } else {
+                    self.progress.finish_file(raw_json.len());
+                }
+            }
+            Err(e) => {
+                self.progress.finish_file(0);
+                self.record_error(path, &e.into());
+            }
+        }
+    }
+
+    /// Parses a list of log files, returning their merged results
+    fn parse_listed_files<R>(&self, files: &[PathBuf]) -> R
+    where
+        T: LogParser<R> + Sync,
+        R: Send,
+    {
+        self.progress.discover(files.len());
+        files
+            .par_iter()
+            .fold(
+                || self.parser.new_accumulator(),
+                |mut accumulator, path| {
+                    match self.filter.listed_file_state(path) {
+                        Some(state) => self.read_file(&mut accumulator, path, &state),
+                        None => self.progress.forget(),
+                    }
+                    accumulator
+                },
+            )
+            .reduce(
+                || self.parser.new_accumulator(),
+                |a, b| self.parser.merge_results(a, b),
+            )
+    }
+
+    fn parse_directory<R>(&self, dir: &Path, state: &FilterState) -> Result<R, BattleToolsError>
+    where
+        T: LogParser<R> + Sync,
+        R: Send,
+    {
+        self.progress
+            .message(format_args!("Parsing {}...", dir.display()));
+        let entries = fs::read_dir(dir)?.collect::<Vec<_>>();
+        self.progress.discover(
+            entries
+                .iter()
+                .filter_map(|entry| entry.as_ref().ok())
+                .filter(|entry| {
+                    entry.file_type().is_ok_and(|t| !t.is_dir())
+                        && ArchiveKind::from_path(&entry.path()).is_none()
+                })
+                .count(),
+        );
+        let results = entries
+            .into_par_iter()
+            .fold(
+                || self.parser.new_accumulator(),
+                |mut accumulator, file| {
+                    let entry = match file {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            self.record_error(dir, &e.into());
+                            return accumulator;
+                        }
+                    };
+                    let path = entry.path();
+                    let is_dir = match entry.file_type() {
+                        Ok(file_type) => file_type.is_dir(),
+                        Err(e) => {
+                            self.record_error(&path, &e.into());
+                            return accumulator;
+                        }
+                    };
+                    if is_dir || ArchiveKind::from_path(&path).is_some() {
+                        // Subdirectories are parsed recursively from inside this fold, so Rayon can steal
+                        // their files (and their own subdirectories) just like files in this directory.
+                        // Excluded subdirectories and ones entirely outside the date range are never read.
+                        let subdirectory_state =
+                            match self.filter.enter_directory(state, &entry.file_name()) {
+                                Some(s) => s,
+                                None => return accumulator,
+                            };
+                        let subdirectory_results = self.parse_path(&path, &subdirectory_state);
+                        return self.parser.merge_results(accumulator, subdirectory_results);
+                    }
+                    if !self.filter.includes_file(state, &entry.file_name()) {
+                        self.progress.forget();
+                        return accumulator;
+                    }
+                    self.read_file(&mut accumulator, &path, state);
+                    accumulator
+                },
+            )
+            .reduce(
+                || self.parser.new_accumulator(),
+                |a, b| self.parser.merge_results(a, b),
+            );
+        Ok(results)
+    }
+
+    fn parse_archive<R>(
+        &self,
+        archive: &Path,
+        kind: ArchiveKind,
+        state: &FilterState,
+    ) -> Result<R, BattleToolsError>
+    where
+        T: LogParser<R> + Sync,
+        R: Send,
+    {
+        self.progress
+            .message(format_args!("Parsing {}...", archive.display()));
+        let mut results = self.parser.new_accumulator();
+        self.read_archive(archive, kind, state, |batch| {
+            let batch_results = batch
+                .into_par_iter()
+                .fold(
+                    || self.parser.new_accumulator(),
+                    |mut accumulator, (path, raw_json, stamp)| {
+                        self.parse_file(&mut accumulator, raw_json, &path, Some(stamp));
+                        accumulator
+                    },
+                )
+                .reduce(
+                    || self.parser.new_accumulator(),
+                    |a, b| self.parser.merge_results(a, b),
+                );
+            let previous = std::mem::replace(&mut results, self.parser.new_accumulator());
+            results = self.parser.merge_results(previous, batch_results);
+        })?;
+        Ok(results)
+    }
+
+    /// Reads every file in an archive, calling `handle_batch` on groups of (member path, contents, stamp).
+    ///
+    /// Archives can only be read sequentially, so members are read in batches
+    /// (to be parsed in parallel) rather than all at once to keep memory usage bounded.
+    fn read_archive<F>(
+        &self,
+        archive: &Path,
+        kind: ArchiveKind,
+        state: &FilterState,
+        mut handle_batch: F,
+    ) -> Result<(), BattleToolsError>
+    where
+        F: FnMut(Vec<(PathBuf, String, FileStamp)>),
+    {
+        let mut batch = Vec::with_capacity(ARCHIVE_BATCH_SIZE);
+        let mut push_member = |path: PathBuf,
+                               read_result: std::io::Result<String>,
+                               member_state: &FilterState,
+                               stamp| {
+            self.progress.discover(1);
+            let contents = match read_result {
+                Ok(contents) => contents,
+                Err(e) => {
+                    self.progress.finish_file(0);
+                    self.record_error(&path, &e.into());
+                    return;
+                }
+            };
+            if !self.filter.includes_log(&contents, member_state) {
+                self.progress.finish_file(contents.len());
+                return;
+            }
+            batch.push((path, contents, stamp));
+            if batch.len() >= ARCHIVE_BATCH_SIZE {
+                handle_batch(std::mem::take(&mut batch));
+            }
+        };
+
+        match kind {
+            ArchiveKind::TarGz | ArchiveKind::TarZst => {
+                let file = File::open(archive)?;
+                let reader: Box<dyn Read> = if kind == ArchiveKind::TarGz {
+                    Box::new(flate2::read::GzDecoder::new(file))
+                } else {
+                    Box::new(zstd::Decoder::new(file)?)
+                };
+
+                let mut tar = tar::Archive::new(reader);
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let member = entry.path()?.into_owned();
+                    let member_state = match self.filter.enter_archive_member(state, &member) {
+                        Some(member_state) => member_state,
+                        None => continue,
+                    };
+                    let path = archive_member_path(archive, &member);
+                    let stamp = FileStamp {
+                        size: entry.size(),
+                        modified: entry.header().mtime().unwrap_or(0),
+                    };
+                    if self.is_recorded(&path, stamp) {
+                        continue;
+                    }
+
+                    let read_result = read_member(&mut entry);
+                    push_member(path, read_result, &member_state, stamp);
+                }
+            }
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(File::open(archive)?)?;
+                for i in 0..zip.len() {
+                    let mut entry = zip.by_index(i)?;
+                    if !entry.is_file() {
+                        continue;
+                    }
+                    let member = match entry.enclosed_name() {
+                        Some(name) => name,
+                        None => {
+                            self.record_error(
+                                &archive_member_path(
+                                    archive,
+                                    Path::new(&*String::from_utf8_lossy(entry.name_raw())),
+                                ),
+                                &BattleToolsError::PathConversion(String::from(
+                                    "Path points outside of the archive",
+                                )),
+                            );
+                            continue;
+                        }
+                    };
+                    let member_state = match self.filter.enter_archive_member(state, &member) {
+                        Some(member_state) => member_state,
+                        None => continue,
+                    };
+                    let path = archive_member_path(archive, &member);
+                    let stamp = FileStamp {
+                        size: entry.size(),
+                        modified: entry.last_modified().map_or(0, |time| {
+                            ((time.datepart() as u64) << 16) | time.timepart() as u64
+                        }),
+                    };
+                    if self.is_recorded(&path, stamp) {
+                        continue;
+                    }
+
+                    let read_result = read_member(&mut entry);
+                    push_member(path, read_result, &member_state, stamp);
+                }
+            }
+        }
+
+        if !batch.is_empty() {
+            handle_batch(batch);
+        }
+        Ok(())
+    }
+}
+
+impl<T, R> ParallelDirectoryParser<R> for T
+where
+    T: LogParser<R> + Sync + Send,
+    R: Send,
+{
+    fn handle_directories(
+        &mut self,
+        dirs: Vec<PathBuf>,
+        filter: &DirectoryFilter,
+        progress: &Progress,
+    ) -> Result<ErrorReport, BattleToolsError> {
+        // We don't know if we'll get a directory with lots of subdirectories or one with lots of JSON files,
+        // so we always use parallel iteration.
+
+        // Directories are walked recursively inside Rayon's thread pool: listing a directory and parsing its
+        // files are both just work items, so idle threads can steal either one. This matters for PS log trees,
+        // which have lots of small day directories. Every directory's results are merged on the way back up,
+        // so handle_results is still only called once.
+        let errors = ErrorReport::default();
+        let walker = Walker {
+            parser: &*self,
+            filter,
+            errors: &errors,
+            progress,
+        };
+        let results = progress.report_while(&errors, || {
+            dirs.par_iter()
+                .filter_map(|dir| {
+                    let state = filter.root_state(dir)?;
+                    Some(walker.parse_path(dir, &state))
+                })
+                .reduce(|| self.new_accumulator(), |a, b| self.merge_results(a, b))
+        });
+        if let Some(manifest) = &filter.manifest {
+            manifest.check_changed()?;
+        }
+        self.handle_results(results)?;
+        Ok(errors)
+    }
+
+    fn handle_files(
+        &mut self,
+        files: Vec<PathBuf>,
+        filter: &DirectoryFilter,
+        progress: &Progress,
+    ) -> Result<ErrorReport, BattleToolsError> {
+        let errors = ErrorReport::default();
+        let walker = Walker {
+            parser: &*self,
+            filter,
+            errors: &errors,
+            progress,
+        };
+        let results = progress.report_while(&errors, || walker.parse_listed_files(&files));
+        if let Some(manifest) = &filter.manifest {
+            manifest.check_changed()?;
+        }
+        self.handle_results(results)?;
+        Ok(errors)
+    }
+}
+
+/// Reads a list of file paths (as given to `--files-from`), separated by NUL characters if there are any,
+/// or by newlines otherwise. Empty entries are ignored.
+pub fn read_file_list<Rd: Read>(mut reader: Rd) -> Result<Vec<PathBuf>, BattleToolsError> {
+    let mut list = String::new();
+    reader.read_to_string(&mut list)?;
+    let entries: Vec<&str> = if list.contains('\0') {
+        list.split('\0').collect()
+    } else {
+        list.lines().collect()
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::io::Write;
+
+    const MEMBERS: [&str; 3] = [
+        "2021-08/gen8randombattle/2021-08-01/battle-gen8randombattle-1.log.json",
+        "2021-08/gen8randombattle/2021-08-02/battle-gen8randombattle-2.log.json",
+        "2021-08/gen8ou/2021-08-02/battle-gen8ou-3.log.json",
+    ];
+
+    /// Records the path of every log it is given
+    struct PathCollector {
+        paths: Vec<PathBuf>,
+    }
+
+    impl LogParser<Vec<PathBuf>> for PathCollector {
+        fn new_accumulator(&self) -> Vec<PathBuf> {
+            vec![]
+        }
+
+        fn handle_log_file(
+            &self,
+            _: String,
+            path: &Path,
+            accumulator: &mut Vec<PathBuf>,
+        ) -> Result<(), BattleToolsError> {
+            accumulator.push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn merge_results(&self, mut a: Vec<PathBuf>, b: Vec<PathBuf>) -> Vec<PathBuf> {
+            a.extend(b);
+            a
+        }
+
+        fn handle_results(&mut self, results: Vec<PathBuf>) -> Result<(), BattleToolsError> {
+            self.paths.extend(results);
+            Ok(())
+        }
+    }
+
+    fn write_tar<W: Write>(writer: W) -> W {
+        let contents = fs::read("src/benchmark-data.json").unwrap();
+        let mut builder = tar::Builder::new(writer);
+        for member in MEMBERS {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, member, contents.as_slice())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn build_archives(dir: &Path) -> Vec<PathBuf> {
+        fs::create_dir_all(dir).unwrap();
+
+        let tar_gz = dir.join("logs.tar.gz");
+        write_tar(flate2::write::GzEncoder::new(
+            File::create(&tar_gz).unwrap(),
+            flate2::Compression::fast(),
+        ))
+        .finish()
+        .unwrap();
+
+        let tar_zst = dir.join("logs.tar.zst");
+        write_tar(zstd::Encoder::new(File::create(&tar_zst).unwrap(), 0).unwrap())
+            .finish()
+            .unwrap();
+
+        let zip_path = dir.join("logs.zip");
+        let contents = fs::read("src/benchmark-data.json").unwrap();
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        for member in MEMBERS {
+            zip.start_file(member, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(&contents).unwrap();
+        }
+        zip.finish().unwrap();
+
+        vec![tar_gz, tar_zst, zip_path]
+    }
+
+    #[test]
+    fn test_archives() {
+        for archive in build_archives(Path::new("test-scratch/archives")) {
+            let mut collector = PathCollector { paths: vec![] };
+            collector
+                .handle_directories(
+                    vec![archive.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
+                .unwrap();
+
+            let mut expected = MEMBERS
+                .iter()
+                .map(|member| PathBuf::from(format!("{}!/{}", archive.display(), member)))
+                .collect::<Vec<_>>();
+            expected.sort();
+            collector.paths.sort();
+            assert_eq!(collector.paths, expected);
+
+            // the date directory is still the parent of each log
+            for path in &collector.paths {
+                let date = path.parent().unwrap().file_name().unwrap();
+                assert!(date.to_string_lossy().starts_with("2021-08-0"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_archive_invalid_utf8() {
+        let dir = Path::new("test-scratch/archives-invalid-utf8");
+        fs::create_dir_all(dir).unwrap();
+        let contents = fs::read("src/benchmark-data.json").unwrap();
+        let members: [(&str, &[u8]); 3] = [
+            ("a.log.json", &contents),
+            ("b.log.json", b"{\"p1\": \"\xff\xfe\"}"),
+            ("c.log.json", &contents),
+        ];
+
+        let tar_gz = dir.join("logs.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&tar_gz).unwrap(),
+            flate2::Compression::fast(),
+        ));
+        for (name, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let zip_path = dir.join("logs.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        for (name, data) in members {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+
+        // the bad member fails on its own, like a plain file would, and the rest are still parsed
+        for archive in [tar_gz, zip_path] {
+            let mut collector = PathCollector { paths: vec![] };
+            let errors = collector
+                .handle_directories(
+                    vec![archive.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
+                .unwrap();
+            collector.paths.sort();
+            assert_eq!(
+                collector.paths,
+                vec![
+                    archive_member_path(&archive, Path::new("a.log.json")),
+                    archive_member_path(&archive, Path::new("c.log.json")),
+                ]
+            );
+            assert_eq!(errors.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_archive_exclusion() {
+        for archive in build_archives(Path::new("test-scratch/archives-exclusion")) {
+            let mut collector = PathCollector { paths: vec![] };
+            collector
+                .handle_directories(
+                    vec![archive],
+                    &DirectoryFilter {
+                        exclude: vec!["gen8ou".parse().unwrap()],
+                        ..Default::default()
+                    },
+                    &Progress::default(),
+                )
+                .unwrap();
+            assert_eq!(collector.paths.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_file_lists() {
+        assert_eq!(
+            read_file_list("a.log.json\nlogs/b c.log.json\r\n\n".as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("a.log.json"),
+                PathBuf::from("logs/b c.log.json")
+            ]
+        );
+        assert_eq!(
+            read_file_list("a\nb.log.json\0c.log.json\0".as_bytes()).unwrap(),
+            vec![PathBuf::from("a\nb.log.json"), PathBuf::from("c.log.json")]
+        );
+
+        let root = PathBuf::from("test-scratch/file-list");
+        tests::build_multi_day_test_dir(&root, 2, 2).unwrap();
+        let day1 = root.join("2021-01/gen8randombattle/2021-01-01");
+        let files = vec![
+            day1.join("battle-gen8randombattle-0.log.json"),
+            day1.join("battle-gen8randombattle-1.log.json"),
+            root.join("2021-01/gen8randombattle/2021-01-02/battle-gen8randombattle-2.log.json"),
+            root.join("nonexistent.log.json"),
+        ];
+
+        let mut collector = PathCollector { paths: vec![] };
+        let errors = collector
+            .handle_files(
+                files.clone(),
+                &DirectoryFilter::default(),
+                &Progress::default(),
+            )
+            .unwrap();
+        assert_eq!(collector.paths.len(), 3);
+        assert_eq!(errors.len(), 1);
+
+        let mut collector = PathCollector { paths: vec![] };
+        let filter = DirectoryFilter {
+            since: Some("2021-01-02".parse().unwrap()),
+            exclude: vec!["*-1.log.json".parse().unwrap()],
+            ..Default::default()
+        };
+        collector
+            .handle_files(files, &filter, &Progress::default())
+            .unwrap();
+        assert_eq!(collector.paths.len(), 1);
+    }
+
+    #[test]
+    fn test_date_range() {
+        // 2021-01-01 through 2021-01-28, then 2021-02-01 through 2021-02-12, with 2 logs per day
+        let root = PathBuf::from("test-scratch/date-range");
+        tests::build_multi_day_test_dir(&root, 40, 2).unwrap();
+
+        let count_logs = |dirs: Vec<PathBuf>, since: Option<&str>, until: Option<&str>| {
+            let mut collector = PathCollector { paths: vec![] };
+            let filter = DirectoryFilter {
+                since: since.map(|s| s.parse().unwrap()),
+                until: until.map(|s| s.parse().unwrap()),
+                ..Default::default()
+            };
+            collector
+                .handle_directories(dirs, &filter, &Progress::default())
+                .unwrap();
+            collector.paths.len()
+        };
+
+        assert_eq!(count_logs(vec![root.clone()], None, None), 80);
+        assert_eq!(
+            count_logs(vec![root.clone()], Some("2021-01-27"), Some("2021-02-02")),
+            8
+        );
+        assert_eq!(count_logs(vec![root.clone()], None, Some("2021-01-31")), 56);
+        assert_eq!(count_logs(vec![root.clone()], Some("2021-02-12"), None), 2);
+        assert_eq!(
+            count_logs(vec![root.join("2021-02")], None, Some("2021-01-31")),
+            0
+        );
+        assert_eq!(
+            count_logs(
+                vec![root.join("2021-01/gen8randombattle/2021-01-05")],
+                Some("2021-01-05"),
+                Some("2021-01-05")
+            ),
+            2
+        );
+    }
+}
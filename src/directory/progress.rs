@@ -0,0 +1,300 @@
+// Code for reporting progress while parsing directories
+use serde_derive::Serialize;
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::ErrorReport;
+
+/// How often the progress bar is redrawn on a terminal
+const BAR_INTERVAL: Duration = Duration::from_millis(250);
+/// How often a progress line is printed when stderr isn't a terminal
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+const BAR_WIDTH: usize = 30;
+
+/// How progress is shown while parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// A progress bar if stderr is a terminal, and plain lines otherwise
+    Auto,
+    /// A single line, redrawn in place
+    Bar,
+    /// A line of text every few seconds
+    Plain,
+    /// A line of JSON every few seconds
+    Json,
+    /// No progress reporting
+    None,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ProgressFormat::Auto),
+            "bar" => Ok(ProgressFormat::Bar),
+            "plain" => Ok(ProgressFormat::Plain),
+            "json" => Ok(ProgressFormat::Json),
+            "none" => Ok(ProgressFormat::None),
+            _ => Err(format!(
+                "unknown progress format '{}' (expected auto, bar, plain, json, or none)",
+                s
+            )),
+        }
+    }
+}
+
+/// Counts the files parsed by `ParallelDirectoryParser::handle_directories` and reports on them.
+///
+/// Files are "discovered" when their directory is listed (or when they're read from an archive),
+/// and each discovered file is then either finished or forgotten (if it's filtered out).
+#[derive(Debug)]
+pub struct Progress {
+    format: ProgressFormat,
+    quiet: bool,
+    files_discovered: AtomicUsize,
+    files_done: AtomicUsize,
+    bytes_done: AtomicU64,
+}
+
+/// The numbers shown in a progress report
+#[derive(Debug, Serialize)]
+struct Snapshot {
+    files_done: usize,
+    files_discovered: usize,
+    bytes_done: u64,
+    errors: usize,
+    elapsed_secs: f64,
+    files_per_sec: f64,
+    mb_per_sec: f64,
+    /// Only an estimate, since more files may still be discovered
+    eta_secs: Option<u64>,
+    finished: bool,
+}
+
+impl Progress {
+    /// With `quiet`, nothing but results (and fatal errors) is printed, whatever the `format`.
+    pub fn new(format: ProgressFormat, quiet: bool) -> Self {
+        let format = match format {
+            _ if quiet => ProgressFormat::None,
+            ProgressFormat::Auto if io::stderr().is_terminal() => ProgressFormat::Bar,
+            ProgressFormat::Auto => ProgressFormat::Plain,
+            format => format,
+        };
+        Self {
+            format,
+            quiet,
+            files_discovered: AtomicUsize::new(0),
+            files_done: AtomicUsize::new(0),
+            bytes_done: AtomicU64::new(0),
+        }
+    }
+
+    pub fn discover(&self, files: usize) {
+        self.files_discovered.fetch_add(files, Ordering::Relaxed);
+    }
+
+    /// Un-discovers a file that won't be parsed after all
+    pub fn forget(&self) {
+        self.files_discovered.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn finish_file(&self, bytes: usize) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.bytes_done.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Prints a message to stderr (unless we're being quiet), without garbling the progress bar
+    pub fn message<D: Display>(&self, message: D) {
+        if self.quiet {
+            return;
+        }
+        let mut stderr = io::stderr().lock();
+        if self.format == ProgressFormat::Bar {
+            // clear the bar; it's redrawn below the message on the next tick
+            let _ = write!(stderr, "\r\x1b[K");
+        }
+        let _ = writeln!(stderr, "{}", message);
+    }
+
+    /// Runs `work`, reporting progress from another thread until it's done
+    pub fn report_while<T, F>(&self, errors: &ErrorReport, work: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let interval = match self.format {
+            ProgressFormat::None | ProgressFormat::Auto => return work(),
+            ProgressFormat::Bar => BAR_INTERVAL,
+            ProgressFormat::Plain | ProgressFormat::Json => LINE_INTERVAL,
+        };
+
+        let start = Instant::now();
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) =
+                    done_receiver.recv_timeout(interval)
+                {
+                    self.print(&self.snapshot(start.elapsed(), errors, false));
+                }
+                self.print(&self.snapshot(start.elapsed(), errors, true));
+            });
+
+            let result = work();
+            drop(done_sender);
+            result
+        })
+    }
+
+    fn snapshot(&self, elapsed: Duration, errors: &ErrorReport, finished: bool) -> Snapshot {
+        let files_done = self.files_done.load(Ordering::Relaxed);
+        let files_discovered = self.files_discovered.load(Ordering::Relaxed);
+        let bytes_done = self.bytes_done.load(Ordering::Relaxed);
+        let elapsed_secs = elapsed.as_secs_f64();
+
+        let files_per_sec = if elapsed_secs > 0.0 {
+            files_done as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+        let mb_per_sec = if elapsed_secs > 0.0 {
+            bytes_done as f64 / 1_000_000.0 / elapsed_secs
+        } else {
+            0.0
+        };
+        let eta_secs = if finished {
+            Some(0)
+        } else if files_per_sec > 0.0 {
+            let remaining = files_discovered.saturating_sub(files_done);
+            Some((remaining as f64 / files_per_sec).ceil() as u64)
+        } else {
+            None
+        };
+
+        Snapshot {
+            files_done,
+            files_discovered,
+            bytes_done,
+            errors: errors.len(),
+            elapsed_secs,
+            files_per_sec,
+            mb_per_sec,
+            eta_secs,
+            finished,
+        }
+    }
+
+    fn print(&self, snapshot: &Snapshot) {
+        let mut stderr = io::stderr().lock();
+        let _ = match self.format {
+            ProgressFormat::Bar => {
+                let filled = (BAR_WIDTH * snapshot.files_done)
+                    .checked_div(snapshot.files_discovered)
+                    .unwrap_or(0)
+                    .min(BAR_WIDTH);
+                write!(
+                    stderr,
+                    "\r\x1b[K[{}{}] {}{}",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    snapshot,
+                    if snapshot.finished { "\n" } else { "" }
+                )
+            }
+            ProgressFormat::Plain => writeln!(stderr, "{}", snapshot),
+            ProgressFormat::Json => match serde_json::to_string(snapshot) {
+                Ok(json) => writeln!(stderr, "{}", json),
+                Err(_) => Ok(()),
+            },
+            ProgressFormat::Auto | ProgressFormat::None => Ok(()),
+        };
+        let _ = stderr.flush();
+    }
+}
+
+impl Default for Progress {
+    /// Doesn't report progress, but still prints messages
+    fn default() -> Self {
+        Self::new(ProgressFormat::None, false)
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} files | {:.1} files/s | {:.1} MB/s | {} errors | ",
+            self.files_done,
+            self.files_discovered,
+            self.files_per_sec,
+            self.mb_per_sec,
+            self.errors
+        )?;
+        match self.eta_secs {
+            _ if self.finished => {
+                write!(f, "done in {}", format_duration(self.elapsed_secs as u64))
+            }
+            Some(eta) => write!(f, "ETA {}", format_duration(eta)),
+            None => write!(f, "ETA unknown"),
+        }
+    }
+}
+
+/// Formats a number of seconds as H:MM:SS
+fn format_duration(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn snapshots() {
+        let progress = Progress::default();
+        progress.discover(10);
+        progress.forget();
+        for _ in 0..3 {
+            progress.finish_file(1_000_000);
+        }
+
+        let errors = ErrorReport::default();
+        let snapshot = progress.snapshot(Duration::from_secs(2), &errors, false);
+        assert_eq!(snapshot.files_discovered, 9);
+        assert_eq!(snapshot.files_done, 3);
+        assert_eq!(snapshot.bytes_done, 3_000_000);
+        // 6 files left at ~1.5 files/sec
+        assert_eq!(snapshot.eta_secs, Some(4));
+        assert_eq!(
+            snapshot.to_string(),
+            "3/9 files | 1.5 files/s | 1.5 MB/s | 0 errors | ETA 0:00:04"
+        );
+
+        let finished = progress.snapshot(Duration::from_secs(2), &errors, true);
+        assert!(
+            finished.to_string().ends_with("done in 0:00:02"),
+            "{}",
+            finished
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!("json".parse(), Ok(ProgressFormat::Json));
+        assert!("fancy".parse::<ProgressFormat>().is_err());
+        assert_eq!(
+            Progress::new(ProgressFormat::Json, true).format,
+            ProgressFormat::None
+        );
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+}
@@ -0,0 +1,129 @@
+// Code for collecting the errors encountered while parsing directories
+use prettytable::*;
+use serde_derive::Serialize;
+use std::{path::Path, sync::Mutex};
+
+use crate::BattleToolsError;
+
+/// A file or directory that couldn't be parsed
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub path: String,
+    /// The `BattleToolsError` variant
+    pub kind: &'static str,
+    pub message: String,
+    #[serde(skip)]
+    pub exit_code: i32,
+}
+
+/// Every failure encountered by `ParallelDirectoryParser::handle_directories`
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    failures: Mutex<Vec<Failure>>,
+}
+
+impl ErrorReport {
+    pub fn record(&self, path: &Path, error: &BattleToolsError) {
+        let failure = Failure {
+            path: path.display().to_string(),
+            kind: error.kind(),
+            message: error.to_string(),
+            exit_code: error.exit_code(),
+        };
+        self.failures.lock().unwrap().push(failure);
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The code to exit with in `--strict` mode: if there were several kinds of failures, the highest exit code is used
+    pub fn exit_code(&self) -> i32 {
+        self.failures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|failure| failure.exit_code)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Counts the failures of each kind, as a human-readable table
+    pub fn to_summary_table(&self) -> String {
+        let failures = self.failures.lock().unwrap();
+        // (kind, count, example path)
+        let mut kinds: Vec<(&str, usize, &str)> = vec![];
+        for failure in failures.iter() {
+            match kinds.iter_mut().find(|(kind, _, _)| *kind == failure.kind) {
+                Some((_, count, _)) => *count += 1,
+                None => kinds.push((failure.kind, 1, &failure.path)),
+            }
+        }
+        kinds.sort_by_key(|&(_, count, _)| std::cmp::Reverse(count));
+
+        let mut table = table!(["Error", "Count", "Example"]);
+        for (kind, count, example) in kinds {
+            table.add_row(row![kind, count, example]);
+        }
+        table.to_string()
+    }
+
+    /// Serializes each failure as a line of JSON
+    pub fn to_jsonl(&self) -> serde_json::Result<String> {
+        let mut jsonl = String::new();
+        for failure in self.failures.lock().unwrap().iter() {
+            jsonl.push_str(&serde_json::to_string(failure)?);
+            jsonl.push('\n');
+        }
+        Ok(jsonl)
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::error::exit_code;
+
+    #[test]
+    fn report() {
+        let report = ErrorReport::default();
+        assert!(report.is_empty());
+
+        report.record(
+            Path::new("logs/a.log.json"),
+            &BattleToolsError::InvalidLog(String::from("Bad JSON for p1")),
+        );
+        report.record(
+            Path::new("logs/b.log.json"),
+            &BattleToolsError::InvalidLog(String::from("Bad JSON for p2")),
+        );
+        report.record(
+            Path::new("logs/c.log.json"),
+            &BattleToolsError::IncompleteAnonymization(String::from("battle-gen8ou-1")),
+        );
+        assert_eq!(report.len(), 3);
+        assert_eq!(report.exit_code(), exit_code::INCOMPLETE_ANONYMIZATION);
+
+        let jsonl = report.to_jsonl().unwrap();
+        let first_line = jsonl.lines().next().unwrap();
+        assert_eq!(gjson::get(first_line, "path").str(), "logs/a.log.json");
+        assert_eq!(gjson::get(first_line, "kind").str(), "InvalidLog");
+        assert_eq!(jsonl.lines().count(), 3);
+
+        assert_eq!(
+            report.to_summary_table(),
+            "+-------------------------+-------+-----------------+
+| Error                   | Count | Example         |
++-------------------------+-------+-----------------+
+| InvalidLog              | 2     | logs/a.log.json |
++-------------------------+-------+-----------------+
+| IncompleteAnonymization | 1     | logs/c.log.json |
++-------------------------+-------+-----------------+
+"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// Code for psbattletools's errors and exit codes.
+use std::{error::Error, fmt};
+
+/// The exit codes `psbattletools` uses for each category of error
+pub mod exit_code {
+    /// Something unexpected went wrong inside `psbattletools`
+    pub const INTERNAL: i32 = 1;
+    /// The command-line arguments were invalid
+    pub const USAGE: i32 = 2;
+    /// A battle log, archive, state file, or manifest was malformed
+    pub const INVALID_INPUT: i32 = 3;
+    /// A file or directory couldn't be read or written
+    pub const IO: i32 = 4;
+    /// An anonymized battle log still contained personal information
+    pub const INCOMPLETE_ANONYMIZATION: i32 = 5;
+}
+
+#[derive(Debug)]
+pub enum BattleToolsError {
+    IOError(std::io::Error),
+    JSONParsingError(serde_json::Error),
+    RegexError(regex::Error),
+    ThreadPoolError(rayon::ThreadPoolBuildError),
+    InvalidLog(String),
+    PathConversion(String),
+    IncompleteAnonymization(String),
+    SerializationError(serde_json::Error),
+    ArchiveError(zip::result::ZipError),
+    InvalidManifest(String),
+    /// Another error, along with a description of what was being done when it happened
+    Context(String, Box<BattleToolsError>),
+}
+
+impl BattleToolsError {
+    /// The name of the error's variant (or, for `Context`, of the underlying error's variant)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BattleToolsError::IOError(_) => "IOError",
+            BattleToolsError::JSONParsingError(_) => "JSONParsingError",
+            BattleToolsError::RegexError(_) => "RegexError",
+            BattleToolsError::ThreadPoolError(_) => "ThreadPoolError",
+            BattleToolsError::InvalidLog(_) => "InvalidLog",
+            BattleToolsError::PathConversion(_) => "PathConversion",
+            BattleToolsError::IncompleteAnonymization(_) => "IncompleteAnonymization",
+            BattleToolsError::SerializationError(_) => "SerializationError",
+            BattleToolsError::ArchiveError(_) => "ArchiveError",
+            BattleToolsError::InvalidManifest(_) => "InvalidManifest",
+            BattleToolsError::Context(_, source) => source.kind(),
+        }
+    }
+
+    /// The code `psbattletools` should exit with because of this error (see `exit_code`)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BattleToolsError::IOError(_) => exit_code::IO,
+            BattleToolsError::JSONParsingError(_)
+            | BattleToolsError::InvalidLog(_)
+            | BattleToolsError::PathConversion(_)
+            | BattleToolsError::InvalidManifest(_) => exit_code::INVALID_INPUT,
+            BattleToolsError::RegexError(_) | BattleToolsError::ThreadPoolError(_) => {
+                exit_code::INTERNAL
+            }
+            BattleToolsError::IncompleteAnonymization(_) => exit_code::INCOMPLETE_ANONYMIZATION,
+            BattleToolsError::SerializationError(e) => match e.classify() {
+                serde_json::error::Category::Io => exit_code::IO,
+                _ => exit_code::INVALID_INPUT,
+            },
+            BattleToolsError::ArchiveError(zip::result::ZipError::Io(_)) => exit_code::IO,
+            BattleToolsError::ArchiveError(_) => exit_code::INVALID_INPUT,
+            BattleToolsError::Context(_, source) => source.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for BattleToolsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BattleToolsError::IOError(e) => write!(f, "I/O error: {}", e),
+            BattleToolsError::JSONParsingError(e) => write!(f, "invalid JSON: {}", e),
+            BattleToolsError::RegexError(e) => write!(f, "invalid regular expression: {}", e),
+            BattleToolsError::ThreadPoolError(e) => write!(f, "couldn't start threads: {}", e),
+            BattleToolsError::InvalidLog(message) => write!(f, "invalid battle log: {}", message),
+            BattleToolsError::PathConversion(message) => write!(f, "invalid path: {}", message),
+            BattleToolsError::IncompleteAnonymization(room) => write!(
+                f,
+                "personal information remained after anonymizing battle {}",
+                room
+            ),
+            BattleToolsError::SerializationError(e) => write!(f, "invalid JSON: {}", e),
+            BattleToolsError::ArchiveError(e) => write!(f, "invalid archive: {}", e),
+            BattleToolsError::InvalidManifest(message) => {
+                write!(f, "invalid manifest: {}", message)
+            }
+            BattleToolsError::Context(context, source) => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl Error for BattleToolsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BattleToolsError::IOError(e) => Some(e),
+            BattleToolsError::JSONParsingError(e) => Some(e),
+            BattleToolsError::RegexError(e) => Some(e),
+            BattleToolsError::ThreadPoolError(e) => Some(e),
+            BattleToolsError::SerializationError(e) => Some(e),
+            BattleToolsError::ArchiveError(e) => Some(e),
+            BattleToolsError::Context(_, source) => Some(source.as_ref()),
+            BattleToolsError::InvalidLog(_)
+            | BattleToolsError::PathConversion(_)
+            | BattleToolsError::IncompleteAnonymization(_)
+            | BattleToolsError::InvalidManifest(_) => None,
+        }
+    }
+}
+
+/// Adds a description of what was being done when an error happened
+pub trait ErrorContext<T> {
+    fn with_context<F, S>(self, describe: F) -> Result<T, BattleToolsError>
+    where
+        F: FnOnce() -> S,
+        S: Into<String>;
+}
+
+impl<T, E> ErrorContext<T> for Result<T, E>
+where
+    E: Into<BattleToolsError>,
+{
+    fn with_context<F, S>(self, describe: F) -> Result<T, BattleToolsError>
+    where
+        F: FnOnce() -> S,
+        S: Into<String>,
+    {
+        self.map_err(|e| BattleToolsError::Context(describe().into(), Box::new(e.into())))
+    }
+}
+
+impl From<std::io::Error> for BattleToolsError {
+    fn from(error: std::io::Error) -> Self {
+        BattleToolsError::IOError(error)
+    }
+}
+impl From<String> for BattleToolsError {
+    fn from(error: String) -> Self {
+        BattleToolsError::InvalidLog(error)
+    }
+}
+impl From<regex::Error> for BattleToolsError {
+    fn from(error: regex::Error) -> Self {
+        BattleToolsError::RegexError(error)
+    }
+}
+impl From<rayon::ThreadPoolBuildError> for BattleToolsError {
+    fn from(error: rayon::ThreadPoolBuildError) -> Self {
+        BattleToolsError::ThreadPoolError(error)
+    }
+}
+impl From<serde_json::Error> for BattleToolsError {
+    fn from(error: serde_json::Error) -> Self {
+        BattleToolsError::SerializationError(error)
+    }
+}
+impl From<zip::result::ZipError> for BattleToolsError {
+    fn from(error: zip::result::ZipError) -> Self {
+        BattleToolsError::ArchiveError(error)
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn context() {
+        let result: Result<(), std::io::Error> = Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "No such file or directory",
+        ));
+        let error = result
+            .with_context(|| "reading state.json")
+            .with_context(|| String::from("loading anonymizer state"))
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "loading anonymizer state: reading state.json: I/O error: No such file or directory"
+        );
+        assert_eq!(error.kind(), "IOError");
+        assert_eq!(error.exit_code(), exit_code::IO);
+
+        // source() walks down to the underlying io::Error
+        let mut source = error.source();
+        let mut depth = 0;
+        while let Some(s) = source {
+            source = s.source();
+            depth += 1;
+        }
+        assert_eq!(depth, 3);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let invalid = BattleToolsError::InvalidLog(String::from("`p1` is not a string"));
+        assert_eq!(invalid.exit_code(), exit_code::INVALID_INPUT);
+        assert_eq!(
+            invalid.to_string(),
+            "invalid battle log: `p1` is not a string"
+        );
+
+        let bad_state = serde_json::from_str::<u32>("{").unwrap_err();
+        assert_eq!(
+            BattleToolsError::from(bad_state).exit_code(),
+            exit_code::INVALID_INPUT
+        );
+
+        assert_eq!(
+            BattleToolsError::IncompleteAnonymization(String::from("battle-gen8ou-1")).exit_code(),
+            exit_code::INCOMPLETE_ANONYMIZATION
+        );
+    }
+}
@@ -0,0 +1,536 @@
+// Code for parsing the commands in a battle log's `inputLog` array, which are what the simulator was sent:
+// the battle's settings, the players, and each choice the players made.
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::{
+    battle_log::{borrowed_option, PokemonSet, Side, StatTable},
+    BattleToolsError,
+};
+
+/// One command in an `inputLog`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Choice<'a> {
+    /// `>start {"formatid":...}`
+    Start(StartOptions<'a>),
+    /// `>player p1 {"name":...}`
+    Player {
+        side: Side,
+        #[serde(flatten)]
+        options: PlayerOptions<'a>,
+    },
+    /// `>p1 move 1, switch 3`: a player's choice for every Pokémon they control
+    Decision {
+        side: Side,
+        actions: Vec<Action<'a>>,
+    },
+    /// `>forcewin p1`
+    ForceWin { side: Side },
+    /// `>forcelose p1`, which is how forfeits are recorded
+    ForceLose { side: Side },
+    /// `>forcetie`
+    ForceTie,
+    /// `>chat MESSAGE`
+    Chat { message: &'a str },
+    /// Any other command, like `>version` or `>eval`
+    Other { command: &'a str, args: &'a str },
+}
+
+/// The settings a battle was started with
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StartOptions<'a> {
+    #[serde(borrow, rename = "formatid")]
+    pub format_id: Cow<'a, str>,
+    /// The PRNG seed, which is an array of numbers in older logs and a string in newer ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<serde_json::Value>,
+    /// Why the battle is rated (like `Rated battle`), or `false` if it isn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rated: Option<serde_json::Value>,
+}
+
+/// A player's name and team
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerOptions<'a> {
+    pub name: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    /// `None` when the team was generated by the simulator (as in Random Battles)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<Vec<PokemonSet<'a>>>,
+}
+
+/// `PlayerOptions` as it's written in the input log, with the team still packed
+#[derive(Deserialize)]
+struct RawPlayerOptions<'a> {
+    #[serde(borrow, default)]
+    name: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "borrowed_option")]
+    avatar: Option<Cow<'a, str>>,
+    #[serde(default)]
+    rating: Option<f64>,
+    #[serde(borrow, default, deserialize_with = "borrowed_option")]
+    team: Option<Cow<'a, str>>,
+}
+
+/// A choice for one Pokémon (or for team preview)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action<'a> {
+    Move {
+        #[serde(rename = "move")]
+        selection: Selection<'a>,
+        /// The target's position, in doubles and triples (negative for the player's own side)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<i8>,
+        /// `mega`, `zmove`, `ultra`, `dynamax` (or `max`), or `terastallize`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        gimmick: Option<&'a str>,
+    },
+    Switch {
+        pokemon: Selection<'a>,
+    },
+    /// The order of the player's team, from team preview, by (1-based) team slot
+    Team {
+        order: Vec<u8>,
+    },
+    /// Let the simulator choose; this is also what's recorded when a player runs out of time
+    Default,
+    Undo,
+    Pass,
+    Shift,
+    Other {
+        command: &'a str,
+        args: &'a str,
+    },
+}
+
+/// A move or Pokémon, chosen either by its (1-based) slot or by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Selection<'a> {
+    Slot(u8),
+    Name(&'a str),
+}
+
+impl<'a> Selection<'a> {
+    fn parse(s: &'a str) -> Self {
+        match s.parse() {
+            Ok(slot) => Selection::Slot(slot),
+            Err(_) => Selection::Name(s),
+        }
+    }
+}
+
+const GIMMICKS: [&str; 6] = ["mega", "zmove", "ultra", "dynamax", "max", "terastallize"];
+
+impl<'a> Action<'a> {
+    fn parse(s: &'a str) -> Self {
+        let (command, args) = s.split_once(' ').unwrap_or((s, ""));
+        let args = args.trim();
+        match command {
+            "move" => {
+                let mut words: Vec<&str> = args.split(' ').collect();
+                let gimmick = match words.last() {
+                    Some(word) if words.len() > 1 && GIMMICKS.contains(word) => words.pop(),
+                    _ => None,
+                };
+                let target = match words.last().map(|word| word.parse()) {
+                    Some(Ok(target)) if words.len() > 1 => {
+                        words.pop();
+                        Some(target)
+                    }
+                    _ => None,
+                };
+                // the rest is the move's slot or its name, which may contain spaces
+                let end = words.iter().map(|word| word.len() + 1).sum::<usize>();
+                Action::Move {
+                    selection: Selection::parse(&args[..end.saturating_sub(1)]),
+                    target,
+                    gimmick,
+                }
+            }
+            "switch" => Action::Switch {
+                pokemon: Selection::parse(args),
+            },
+            "team" => match team_order(args) {
+                Some(order) => Action::Team { order },
+                None => Action::Other { command, args },
+            },
+            "default" => Action::Default,
+            "undo" => Action::Undo,
+            "pass" => Action::Pass,
+            "shift" => Action::Shift,
+            _ => Action::Other { command, args },
+        }
+    }
+}
+
+/// Parses a team order, which is either a list of digits (`123456`) or comma-separated (`1, 2, 3`)
+fn team_order(s: &str) -> Option<Vec<u8>> {
+    if s.contains(',') {
+        s.split(',')
+            .map(|slot| slot.trim().parse::<u8>().ok())
+            .collect()
+    } else {
+        s.chars()
+            .map(|digit| digit.to_digit(10).map(|slot| slot as u8))
+            .collect()
+    }
+}
+
+impl<'a> Choice<'a> {
+    /// The side the choice was made for, if it's specific to one
+    pub fn side(&self) -> Option<Side> {
+        match self {
+            Choice::Player { side, .. }
+            | Choice::Decision { side, .. }
+            | Choice::ForceWin { side }
+            | Choice::ForceLose { side } => Some(*side),
+            _ => None,
+        }
+    }
+
+    /// Parses a line of an input log, like `>p1 move 1`.
+    ///
+    /// Fails only if a `>start` or `>player` line's JSON is invalid.
+    pub fn parse(line: &'a str) -> Result<Self, BattleToolsError> {
+        let line = line.strip_prefix('>').unwrap_or(line);
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let invalid = |e: serde_json::Error| {
+            BattleToolsError::InvalidLog(format!("invalid `>{}` in inputLog: {}", command, e))
+        };
+
+        Ok(match (command, Side::from_id(command)) {
+            ("start", _) => Choice::Start(serde_json::from_str(args).map_err(invalid)?),
+            ("player", _) => {
+                let (side, json) = args.split_once(' ').unwrap_or((args, ""));
+                match Side::from_id(side) {
+                    Some(side) => Choice::Player {
+                        side,
+                        options: PlayerOptions::parse(json).map_err(invalid)?,
+                    },
+                    None => Choice::Other { command, args },
+                }
+            }
+            (_, Some(side)) => Choice::Decision {
+                side,
+                // team orders may contain commas too
+                actions: if args.starts_with("team ") {
+                    vec![Action::parse(args)]
+                } else {
+                    args.split(',')
+                        .map(|action| Action::parse(action.trim()))
+                        .collect()
+                },
+            },
+            ("forcewin", _) | ("forcelose", _) => match Side::from_id(args) {
+                Some(side) if command == "forcewin" => Choice::ForceWin { side },
+                Some(side) => Choice::ForceLose { side },
+                None => Choice::Other { command, args },
+            },
+            ("forcetie", _) => Choice::ForceTie,
+            ("chat", _) => Choice::Chat { message: args },
+            _ => Choice::Other { command, args },
+        })
+    }
+}
+
+impl<'a> PlayerOptions<'a> {
+    fn parse(json: &'a str) -> Result<Self, serde_json::Error> {
+        let raw: RawPlayerOptions = serde_json::from_str(json)?;
+        let team = match raw.team {
+            Some(Cow::Borrowed(packed)) => unpack_team(packed),
+            // (only if the team had to be unescaped)
+            Some(Cow::Owned(packed)) => unpack_team(&packed)
+                .map(|team| team.into_iter().map(PokemonSet::into_owned).collect()),
+            None => None,
+        };
+        Ok(Self {
+            name: raw.name,
+            avatar: raw.avatar,
+            rating: raw.rating,
+            team,
+        })
+    }
+}
+
+/// Unpacks a team in PS's packed format; returns `None` for an empty or invalid team.
+///
+/// See https://github.com/smogon/pokemon-showdown/blob/master/sim/TEAMS.md#packed-format
+pub fn unpack_team(packed: &str) -> Option<Vec<PokemonSet<'_>>> {
+    if packed.is_empty() {
+        return None;
+    }
+    packed.split(']').map(unpack_set).collect()
+}
+
+fn unpack_set(packed: &str) -> Option<PokemonSet<'_>> {
+    let fields: Vec<&str> = packed.split('|').collect();
+    if fields.len() < 10 {
+        return None;
+    }
+    fn optional(field: &str) -> Option<Cow<'_, str>> {
+        (!field.is_empty()).then_some(Cow::Borrowed(field))
+    }
+    let stats = |field: &str, default: u32| -> Option<Option<StatTable>> {
+        if field.is_empty() {
+            return Some(None);
+        }
+        let values = field
+            .split(',')
+            .map(|value| match value {
+                "" => Some(default),
+                value => value.parse().ok(),
+            })
+            .collect::<Option<Vec<u32>>>()?;
+        match values[..] {
+            [hp, atk, def, spa, spd, spe] => Some(Some(StatTable {
+                hp,
+                atk,
+                def,
+                spa,
+                spd,
+                spe,
+            })),
+            _ => None,
+        }
+    };
+    // happiness, Poké Ball, Hidden Power type, Gigantamax, Dynamax level, and Tera type
+    let misc: Vec<&str> = fields
+        .get(11)
+        .map_or(vec![], |misc| misc.split(',').collect());
+
+    Some(PokemonSet {
+        name: Cow::Borrowed(fields[0]),
+        // the species is left out when it's the same as the nickname
+        species: Cow::Borrowed(if fields[1].is_empty() {
+            fields[0]
+        } else {
+            fields[1]
+        }),
+        item: optional(fields[2]),
+        ability: optional(fields[3]),
+        moves: fields[4]
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(Cow::Borrowed)
+            .collect(),
+        nature: optional(fields[5]),
+        evs: stats(fields[6], 0)?,
+        gender: optional(fields[7]),
+        ivs: stats(fields[8], 31)?,
+        shiny: Some(fields[9] == "S"),
+        level: match fields.get(10) {
+            Some(level) if !level.is_empty() => Some(level.parse().ok()?),
+            _ => None,
+        },
+        gigantamax: Some(misc.get(3) == Some(&"G")),
+        tera_type: misc.get(5).copied().and_then(optional),
+    })
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn actions(line: &str) -> Vec<Action<'_>> {
+        match Choice::parse(line).unwrap() {
+            Choice::Decision { actions, .. } => actions,
+            choice => panic!("{:?} isn't a decision", choice),
+        }
+    }
+
+    #[test]
+    fn decisions() {
+        assert_eq!(
+            Choice::parse(">p1 move 1").unwrap(),
+            Choice::Decision {
+                side: Side::P1,
+                actions: vec![Action::Move {
+                    selection: Selection::Slot(1),
+                    target: None,
+                    gimmick: None
+                }]
+            }
+        );
+        assert_eq!(
+            actions(">p2 switch 3"),
+            vec![Action::Switch {
+                pokemon: Selection::Slot(3)
+            }]
+        );
+        assert_eq!(
+            actions(">p1 move 2 terastallize"),
+            vec![Action::Move {
+                selection: Selection::Slot(2),
+                target: None,
+                gimmick: Some("terastallize")
+            }]
+        );
+        assert_eq!(
+            actions(">p2 move 1 1, move 2 -1"),
+            vec![
+                Action::Move {
+                    selection: Selection::Slot(1),
+                    target: Some(1),
+                    gimmick: None
+                },
+                Action::Move {
+                    selection: Selection::Slot(2),
+                    target: Some(-1),
+                    gimmick: None
+                }
+            ]
+        );
+        assert_eq!(
+            actions(">p1 move Thunder Wave 2 dynamax, switch Iron Valiant"),
+            vec![
+                Action::Move {
+                    selection: Selection::Name("Thunder Wave"),
+                    target: Some(2),
+                    gimmick: Some("dynamax")
+                },
+                Action::Switch {
+                    pokemon: Selection::Name("Iron Valiant")
+                }
+            ]
+        );
+        assert_eq!(actions(">p1 default"), vec![Action::Default]);
+        assert_eq!(
+            actions(">p2 pass, shift"),
+            vec![Action::Pass, Action::Shift]
+        );
+        assert_eq!(
+            actions(">p1 team 312456"),
+            vec![Action::Team {
+                order: vec![3, 1, 2, 4, 5, 6]
+            }]
+        );
+        assert_eq!(
+            actions(">p1 team 10, 2, 3"),
+            vec![Action::Team {
+                order: vec![10, 2, 3]
+            }]
+        );
+        assert_eq!(
+            actions(">p1 dance wildly"),
+            vec![Action::Other {
+                command: "dance",
+                args: "wildly"
+            }]
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            Choice::parse(
+                r#">start {"formatid":"gen8doublesou","seed":[1,2,3,4],"rated":"Rated battle"}"#
+            )
+            .unwrap(),
+            Choice::Start(StartOptions {
+                format_id: Cow::Borrowed("gen8doublesou"),
+                seed: Some(serde_json::json!([1, 2, 3, 4])),
+                rated: Some(serde_json::json!("Rated battle")),
+            })
+        );
+        assert_eq!(
+            Choice::parse(">forcelose p2").unwrap(),
+            Choice::ForceLose { side: Side::P2 }
+        );
+        assert_eq!(
+            Choice::parse(">forcewin p1").unwrap(),
+            Choice::ForceWin { side: Side::P1 }
+        );
+        assert_eq!(Choice::parse(">forcetie").unwrap(), Choice::ForceTie);
+        assert_eq!(
+            Choice::parse(">chat gl hf").unwrap(),
+            Choice::Chat { message: "gl hf" }
+        );
+        assert_eq!(
+            Choice::parse(">version abc123").unwrap(),
+            Choice::Other {
+                command: "version",
+                args: "abc123"
+            }
+        );
+        assert!(Choice::parse(">player p1 {").is_err());
+        assert!(Choice::parse(">start").is_err());
+    }
+
+    #[test]
+    fn players() {
+        let line = r#">player p2 {"name":"Rust Haters","avatar":"red","team":"Koko|TapuKoko|lifeorb|electricsurge|dazzlinggleam,thunderbolt|Timid|,,4,252,,252||,0,,,,|S|50|,,,G,,Electric]Incineroar||sitrusberry|H|fakeout|Careful|252,4,,,252,||||","rating":1349}"#;
+        let Choice::Player { side, options } = Choice::parse(line).unwrap() else {
+            panic!("not a player");
+        };
+        assert_eq!(side, Side::P2);
+        assert_eq!(options.name, "Rust Haters");
+        assert_eq!(options.avatar.as_deref(), Some("red"));
+        assert_eq!(options.rating, Some(1349.0));
+
+        let team = options.team.unwrap();
+        assert_eq!(team.len(), 2);
+        let koko = &team[0];
+        assert_eq!(koko.name, "Koko");
+        assert_eq!(koko.species, "TapuKoko");
+        assert_eq!(koko.item.as_deref(), Some("lifeorb"));
+        assert_eq!(koko.moves, vec!["dazzlinggleam", "thunderbolt"]);
+        assert_eq!(koko.nature.as_deref(), Some("Timid"));
+        assert_eq!(
+            koko.evs,
+            Some(StatTable {
+                hp: 0,
+                atk: 0,
+                def: 4,
+                spa: 252,
+                spd: 0,
+                spe: 252
+            })
+        );
+        assert_eq!(koko.ivs.map(|ivs| (ivs.hp, ivs.atk)), Some((31, 0)));
+        assert_eq!(koko.gender, None);
+        assert_eq!(koko.shiny, Some(true));
+        assert_eq!(koko.level, Some(50));
+        assert_eq!(koko.gigantamax, Some(true));
+        assert_eq!(koko.tera_type.as_deref(), Some("Electric"));
+
+        let incineroar = &team[1];
+        assert_eq!(incineroar.species, "Incineroar");
+        assert_eq!(incineroar.ability.as_deref(), Some("H"));
+        assert_eq!(incineroar.ivs, None);
+        assert_eq!(incineroar.level, None);
+        assert_eq!(incineroar.tera_type, None);
+
+        // teams generated by the simulator are left out
+        let Choice::Player { options, .. } =
+            Choice::parse(r#">player p1 {"name":"Annika","avatar":"cynthia","team":"","rating":1512,"seed":"sodium,1"}"#).unwrap()
+        else {
+            panic!("not a player");
+        };
+        assert_eq!(options.team, None);
+        assert!(unpack_team("Koko|TapuKoko").is_none());
+    }
+
+    #[test]
+    fn serialization() {
+        let json = |line| serde_json::to_string(&Choice::parse(line).unwrap()).unwrap();
+        assert_eq!(
+            json(">p1 move 1 2 terastallize, switch 3"),
+            r#"{"type":"decision","side":"p1","actions":[{"action":"move","move":1,"target":2,"gimmick":"terastallize"},{"action":"switch","pokemon":3}]}"#
+        );
+        assert_eq!(
+            json(">p2 default"),
+            r#"{"type":"decision","side":"p2","actions":[{"action":"default"}]}"#
+        );
+        assert_eq!(
+            json(r#">player p1 {"name":"Annika","team":""}"#),
+            r#"{"type":"player","side":"p1","name":"Annika"}"#
+        );
+        assert_eq!(json(">forcelose p1"), r#"{"type":"forcelose","side":"p1"}"#);
+    }
+}
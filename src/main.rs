@@ -1,18 +1,47 @@
-#![feature(iter_intersperse, test)]
+#![feature(iter_intersperse)]
+#![cfg_attr(test, feature(test))]
 // Command-line program to manage PS battle logs.
 
 mod anonymize;
+mod battle_log;
+mod choices;
+mod csv;
+mod date;
 mod directory;
+mod error;
 mod id;
+mod input_log;
+mod protocol;
+mod rating_history;
+mod replay;
+mod replay_html;
 mod search;
 mod statistics;
+mod transcript;
 
 use anonymize::AnonymizingDirectoryParser;
-use directory::ParallelDirectoryParser;
-use search::BattleSearcher;
+use battle_log::BattleLog;
+use choices::ChoiceExporter;
+use date::LogDate;
+use directory::{
+    DirectoryFilter, ErrorReport, Manifest, ParallelDirectoryParser, PathPattern, Progress,
+    ProgressFormat, ResumableParser,
+};
+pub use error::BattleToolsError;
+use error::{exit_code, ErrorContext};
+use id::to_id;
+use rating_history::{HistoryFormat, RatingHistory};
+pub use replay::Replay;
+use search::{BattleSearcher, GrepScope, LogGrepper, OutputFormat, SortKey, TeamFilter};
 use statistics::{StatisticsDirectoryParser, StatsOutput};
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
 use structopt::StructOpt;
+use transcript::{ColorMode, Transcript};
 
 #[derive(StructOpt)]
 enum Subcommand {
@@ -20,7 +49,6 @@ enum Subcommand {
     Statistics {
         #[structopt(
             help = "A list of directories to calculate statistics for",
-            required(true),
             parse(from_os_str)
         )]
         directories: Vec<PathBuf>,
@@ -41,35 +69,208 @@ enum Subcommand {
             help = "Battles in which either player is below this ELO rating will be ignored"
         )]
         minimum_elo: Option<u64>,
+        #[structopt(
+            long = "manifest",
+            parse(from_os_str),
+            help = "A file recording which battle logs have been processed; unchanged logs in it are skipped, and their statistics are carried over"
+        )]
+        manifest_path: Option<PathBuf>,
     },
     #[structopt(name = "search", alias = "s")]
     Search {
-        #[structopt(help = "Search for battles played by this user", required(true))]
-        username: String,
+        #[structopt(
+            help = "Search for battles played by this user (or by any of several comma-separated users)",
+            required(true)
+        )]
+        usernames: String,
         #[structopt(
             help = "A list of directories to search for matching battle logs in",
-            required(true),
             parse(from_os_str)
         )]
         directories: Vec<PathBuf>,
         #[structopt(
             long = "wins-only",
             short = "w",
-            help = "Search only for battles that were won by the username you're searching for"
+            help = "Search only for battles that were won by the username you're searching for",
+            conflicts_with_all = &["losses-only", "ties-only"]
         )]
         wins_only: bool,
+        #[structopt(
+            long = "losses-only",
+            help = "Search only for battles that were lost by the username you're searching for",
+            conflicts_with = "ties-only"
+        )]
+        losses_only: bool,
+        #[structopt(
+            long = "ties-only",
+            help = "Search only for battles that ended in a tie"
+        )]
+        ties_only: bool,
         #[structopt(
             long = "forfeits-only",
             short = "f",
             help = "Search only for battles that ended by forfeit"
         )]
         forfeits_only: bool,
+        #[structopt(
+            long = "include-renames",
+            help = "Also match users under any other name they had during the battle"
+        )]
+        include_renames: bool,
+        #[structopt(
+            long = "end-type",
+            number_of_values = 1,
+            help = "Search only for battles with this end type (such as normal or forfeit); may be given multiple times"
+        )]
+        end_types: Vec<String>,
+        #[structopt(
+            long = "min-turns",
+            help = "Search only for battles that lasted at least this many turns"
+        )]
+        min_turns: Option<u32>,
+        #[structopt(
+            long = "max-turns",
+            help = "Search only for battles that lasted at most this many turns"
+        )]
+        max_turns: Option<u32>,
+        #[structopt(
+            long = "min-rating",
+            help = "Search only for battles where the searched user's Elo was at least this before the battle"
+        )]
+        min_rating: Option<f64>,
+        #[structopt(
+            long = "max-rating",
+            help = "Search only for battles where the searched user's Elo was at most this before the battle"
+        )]
+        max_rating: Option<f64>,
+        #[structopt(long = "rated-only", help = "Search only for rated battles")]
+        rated_only: bool,
+        #[structopt(
+            long = "all",
+            help = "Search only for battles that all of the given users played in",
+            conflicts_with = "any"
+        )]
+        all: bool,
+        #[structopt(
+            long = "any",
+            help = "Search for battles that any of the given users played in (the default)"
+        )]
+        any: bool,
+        #[structopt(
+            long = "opponent",
+            number_of_values = 1,
+            help = "Search only for battles against this user; may be given multiple times"
+        )]
+        opponents: Vec<String>,
+        #[structopt(
+            long = "species",
+            number_of_values = 1,
+            help = "Search only for battles where a team has a Pokémon of this species; may be given multiple times"
+        )]
+        species: Vec<String>,
+        #[structopt(
+            long = "move",
+            number_of_values = 1,
+            help = "Search only for battles where a team has a Pokémon with this move; if given multiple times, the Pokémon must have all of the moves"
+        )]
+        moves: Vec<String>,
+        #[structopt(
+            long = "item",
+            number_of_values = 1,
+            help = "Search only for battles where a team has a Pokémon holding this item; may be given multiple times"
+        )]
+        items: Vec<String>,
+        #[structopt(
+            long = "ability",
+            number_of_values = 1,
+            help = "Search only for battles where a team has a Pokémon with this ability; may be given multiple times"
+        )]
+        abilities: Vec<String>,
+        #[structopt(
+            long = "tera-type",
+            number_of_values = 1,
+            help = "Search only for battles where a team has a Pokémon with this Tera Type; may be given multiple times"
+        )]
+        tera_types: Vec<String>,
+        #[structopt(
+            long = "user-team-only",
+            help = "Only check the searched users' teams for --species, --move, --item, --ability, and --tera-type"
+        )]
+        user_team_only: bool,
+        #[structopt(
+            long = "output-format",
+            default_value = "human",
+            help = "How to print matching battles: human, json, csv, paths, or roomids"
+        )]
+        output_format: OutputFormat,
+        #[structopt(
+            long = "sort",
+            default_value = "date",
+            help = "The order to print battles in: date (oldest first), rating (highest first), or turns (longest first)"
+        )]
+        sort: SortKey,
+        #[structopt(long = "limit", help = "Print at most this many battles")]
+        limit: Option<usize>,
+        #[structopt(
+            long = "unordered",
+            help = "Print battles as soon as they're found, in no particular order, instead of sorting them",
+            conflicts_with = "sort"
+        )]
+        unordered: bool,
+        #[structopt(
+            long = "summary",
+            help = "Print a summary of the searched users' matching battles (as tables, or with --output-format json) instead of listing them",
+            conflicts_with_all = &["limit", "unordered"]
+        )]
+        summary: bool,
+    },
+    #[structopt(name = "grep")]
+    Grep {
+        #[structopt(help = "A regular expression to search battle log lines for")]
+        pattern: String,
+        #[structopt(
+            help = "A list of directories to search for matching battle logs in",
+            parse(from_os_str)
+        )]
+        directories: Vec<PathBuf>,
+        #[structopt(
+            long = "chat-only",
+            help = "Only search chat messages",
+            conflicts_with = "input-log"
+        )]
+        chat_only: bool,
+        #[structopt(
+            long = "input-log",
+            help = "Search the players' choices in each battle's input log instead of the battle log"
+        )]
+        input_log: bool,
+    },
+    #[structopt(name = "rating-history")]
+    RatingHistory {
+        #[structopt(help = "The user whose ratings to list")]
+        username: String,
+        #[structopt(
+            help = "A list of directories to search for the user's battles in",
+            parse(from_os_str)
+        )]
+        directories: Vec<PathBuf>,
+        #[structopt(
+            long = "output-format",
+            default_value = "csv",
+            help = "How to print the history: csv or json"
+        )]
+        output_format: HistoryFormat,
+        #[structopt(
+            long = "svg",
+            help = "A path to write a line chart of the user's Elo to, as an SVG image",
+            parse(from_os_str)
+        )]
+        svg_path: Option<PathBuf>,
     },
     #[structopt(name = "anonymize")]
     Anonymize {
         #[structopt(
             help = "A list of directories containing battle logs to anonymize",
-            required(true),
             parse(from_os_str)
         )]
         directories: Vec<PathBuf>,
@@ -98,7 +299,63 @@ enum Subcommand {
             help = "Load the state of the anonymizer from this file"
         )]
         load_state_from: Option<PathBuf>,
+        #[structopt(
+            long = "manifest",
+            parse(from_os_str),
+            conflicts_with = "load-state-from",
+            help = "A file recording which battle logs have been anonymized; unchanged logs in it are skipped, and the anonymizer continues from its saved state"
+        )]
+        manifest_path: Option<PathBuf>,
+    },
+    #[structopt(name = "choices")]
+    Choices {
+        #[structopt(
+            help = "A list of directories containing battle logs to export choices from",
+            parse(from_os_str)
+        )]
+        directories: Vec<PathBuf>,
     },
+    #[structopt(name = "replay-html")]
+    ReplayHtml {
+        #[structopt(help = "The battle log to export", parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(
+            long = "output",
+            short = "o",
+            help = "The file to write the replay page to",
+            required(true),
+            parse(from_os_str)
+        )]
+        output_path: PathBuf,
+    },
+    #[structopt(name = "show")]
+    Show {
+        #[structopt(help = "The battle log to show", parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(long = "no-chat", help = "Leave chat messages out of the transcript")]
+        no_chat: bool,
+        #[structopt(
+            long = "color",
+            default_value = "auto",
+            help = "When to color the transcript: 'always', 'never', or 'auto' (only if standard output is a terminal)"
+        )]
+        color: ColorMode,
+    },
+}
+
+impl Subcommand {
+    /// The directories to parse, for subcommands that parse directories of logs
+    fn directories(&self) -> Option<&[PathBuf]> {
+        match self {
+            Subcommand::Statistics { directories, .. }
+            | Subcommand::Search { directories, .. }
+            | Subcommand::Grep { directories, .. }
+            | Subcommand::RatingHistory { directories, .. }
+            | Subcommand::Anonymize { directories, .. }
+            | Subcommand::Choices { directories } => Some(directories),
+            Subcommand::ReplayHtml { .. } | Subcommand::Show { .. } => None,
+        }
+    }
 }
 
 #[derive(StructOpt)]
@@ -109,65 +366,114 @@ enum Subcommand {
 struct Options {
     #[structopt(subcommand)]
     command: Subcommand,
+    #[structopt(
+        long = "include",
+        number_of_values = 1,
+        global = true,
+        help = "Only battle logs matching this glob (or regex, if prefixed with 're:') will be used; may be repeated"
+    )]
+    include: Vec<PathPattern>,
     #[structopt(
         long = "exclude",
-        help = "Filenames and directories including this string will be ignored"
+        number_of_values = 1,
+        global = true,
+        help = "Files and directories matching this glob (or regex, if prefixed with 're:') will be ignored; may be repeated"
+    )]
+    exclude: Vec<PathPattern>,
+    #[structopt(
+        long = "since",
+        global = true,
+        help = "Battles from before this date (YYYY-MM-DD) will be ignored"
     )]
-    exclude: Option<String>,
+    since: Option<LogDate>,
+    #[structopt(
+        long = "until",
+        global = true,
+        help = "Battles from after this date (YYYY-MM-DD) will be ignored"
+    )]
+    until: Option<LogDate>,
     #[structopt(
         long = "threads",
         short = "j",
         help = "The maximum number of threads to use for concurrent processing"
     )]
     threads: Option<usize>,
+    #[structopt(
+        long = "error-report",
+        global = true,
+        parse(from_os_str),
+        help = "A path to a file to which every error will be written in JSON Lines format"
+    )]
+    error_report_path: Option<PathBuf>,
+    #[structopt(
+        long = "strict",
+        global = true,
+        help = "Exit with a non-zero status if any battle log or directory couldn't be parsed"
+    )]
+    strict: bool,
+    #[structopt(
+        long = "files-from",
+        global = true,
+        parse(from_os_str),
+        help = "Instead of directories, use exactly the battle logs listed in this file ('-' for standard input), one per line or separated by NUL characters"
+    )]
+    files_from: Option<PathBuf>,
+    #[structopt(
+        long = "progress",
+        global = true,
+        default_value = "auto",
+        help = "How to report progress: 'bar', 'plain', 'json', or 'none' ('auto' uses a bar if stderr is a terminal, and plain lines otherwise)"
+    )]
+    progress: ProgressFormat,
+    #[structopt(
+        long = "quiet",
+        short = "q",
+        global = true,
+        help = "Don't print anything but results: no progress, per-directory messages, or error summaries"
+    )]
+    quiet: bool,
 }
 
-#[derive(Debug)]
-pub enum BattleToolsError {
-    IOError(std::io::Error),
-    JSONParsingError(json::Error),
-    RegexError(regex::Error),
-    ThreadPoolError(rayon::ThreadPoolBuildError),
-    InvalidLog(String),
-    PathConversion(String),
-    IncompleteAnonymization(String),
-    SerializationError(serde_json::Error),
-}
-
-impl From<std::io::Error> for BattleToolsError {
-    fn from(error: std::io::Error) -> Self {
-        BattleToolsError::IOError(error)
-    }
-}
-impl From<String> for BattleToolsError {
-    fn from(error: String) -> Self {
-        BattleToolsError::InvalidLog(error)
-    }
-}
-impl From<json::Error> for BattleToolsError {
-    fn from(error: json::Error) -> Self {
-        BattleToolsError::JSONParsingError(error)
-    }
-}
-impl From<regex::Error> for BattleToolsError {
-    fn from(error: regex::Error) -> Self {
-        BattleToolsError::RegexError(error)
-    }
-}
-impl From<rayon::ThreadPoolBuildError> for BattleToolsError {
-    fn from(error: rayon::ThreadPoolBuildError) -> Self {
-        BattleToolsError::ThreadPoolError(error)
+fn main() {
+    let options = match Options::from_args_safe() {
+        Ok(options) => options,
+        Err(e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            process::exit(exit_code::USAGE);
+        }
+        // --help or --version
+        Err(e) => e.exit(),
+    };
+    // `--files-from` is global, so clap can't check this for us
+    match (
+        options
+            .command
+            .directories()
+            .map(|directories| directories.is_empty()),
+        &options.files_from,
+    ) {
+        (Some(true), None) => {
+            eprintln!("error: either a list of directories or --files-from must be provided");
+            process::exit(exit_code::USAGE);
+        }
+        (Some(false), Some(_)) => {
+            eprintln!("error: directories can't be provided along with --files-from");
+            process::exit(exit_code::USAGE);
+        }
+        (None, Some(_)) => {
+            eprintln!("error: --files-from can't be used with this subcommand");
+            process::exit(exit_code::USAGE);
+        }
+        _ => {}
     }
-}
-impl From<serde_json::Error> for BattleToolsError {
-    fn from(error: serde_json::Error) -> Self {
-        BattleToolsError::SerializationError(error)
+
+    if let Err(e) = run(options) {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
     }
 }
 
-fn main() -> Result<(), BattleToolsError> {
-    let options = Options::from_args();
-
+fn run(options: Options) -> Result<(), BattleToolsError> {
     if let Some(threads) = options.threads {
         // see https://stackoverflow.com/questions/59205184/how-can-i-change-the-number-of-threads-rayon-uses
         rayon::ThreadPoolBuilder::new()
@@ -175,23 +481,51 @@ fn main() -> Result<(), BattleToolsError> {
             .build_global()?;
     }
 
-    match options.command {
+    let mut filter = DirectoryFilter {
+        include: options.include,
+        exclude: options.exclude,
+        since: options.since,
+        until: options.until,
+        ..Default::default()
+    };
+    let progress = Progress::new(options.progress, options.quiet);
+    let files = match options.files_from {
+        Some(path) if path == Path::new("-") => Some(
+            directory::read_file_list(std::io::stdin().lock())
+                .with_context(|| "reading the list of files from standard input")?,
+        ),
+        Some(path) => Some(
+            fs::File::open(&path)
+                .map_err(BattleToolsError::from)
+                .and_then(directory::read_file_list)
+                .with_context(|| format!("reading the list of files from {}", path.display()))?,
+        ),
+        None => None,
+    };
+
+    let error_report = match options.command {
         Subcommand::Statistics {
             directories,
             csv_path,
             human_readable_path,
             minimum_elo,
+            manifest_path,
         } => {
             let mut parser = StatisticsDirectoryParser::new(minimum_elo);
-            parser.handle_directories(directories, options.exclude)?;
+            load_manifest(manifest_path.as_deref(), &mut parser, &mut filter)?;
+            let error_report = parse_logs(&mut parser, directories, files, &filter, &progress)?;
+            save_manifest(manifest_path.as_deref(), &parser, &mut filter, &progress)?;
 
             let mut produced_output = false;
             if let Some(csv_path) = csv_path {
-                fs::write(csv_path, parser.to_csv())?;
+                fs::write(&csv_path, parser.to_csv())
+                    .with_context(|| format!("writing CSV to {}", csv_path.display()))?;
                 produced_output = true;
             }
             if let Some(human_readable_path) = human_readable_path {
-                fs::write(human_readable_path, parser.to_human_readable())?;
+                fs::write(&human_readable_path, parser.to_human_readable()).with_context(|| {
+                    format!("writing table to {}", human_readable_path.display())
+                })?;
                 produced_output = true;
             }
 
@@ -199,15 +533,71 @@ fn main() -> Result<(), BattleToolsError> {
             if !produced_output {
                 println!("{}", parser.to_human_readable());
             }
+            error_report
         }
         Subcommand::Search {
-            username,
+            usernames,
             directories,
             wins_only,
+            losses_only,
+            ties_only,
             forfeits_only,
+            include_renames,
+            end_types,
+            min_turns,
+            max_turns,
+            min_rating,
+            max_rating,
+            rated_only,
+            all,
+            any,
+            opponents,
+            species,
+            moves,
+            items,
+            abilities,
+            tera_types,
+            user_team_only,
+            output_format,
+            sort,
+            limit,
+            unordered,
+            summary,
         } => {
-            let mut parser = BattleSearcher::new(&username, wins_only, forfeits_only);
-            parser.handle_directories(directories, options.exclude)?;
+            if summary && !matches!(output_format, OutputFormat::Human | OutputFormat::Json) {
+                eprintln!(
+                    "error: --summary can only be printed in the human or json output formats"
+                );
+                process::exit(exit_code::USAGE);
+            }
+            let usernames = usernames.split(',').collect::<Vec<_>>();
+            let mut parser = BattleSearcher::new(&usernames, wins_only, forfeits_only);
+            parser.require_all = all && !any;
+            let ids = |names: Vec<String>| names.iter().map(|name| to_id(name)).collect();
+            parser.opponent_ids = ids(opponents);
+            parser.team_filter = TeamFilter {
+                species: ids(species),
+                moves: ids(moves),
+                items: ids(items),
+                abilities: ids(abilities),
+                tera_types: ids(tera_types),
+            };
+            parser.user_team_only = user_team_only;
+            parser.include_renames = include_renames;
+            parser.losses_only = losses_only;
+            parser.ties_only = ties_only;
+            parser.end_types = end_types;
+            parser.min_turns = min_turns;
+            parser.max_turns = max_turns;
+            parser.min_rating = min_rating;
+            parser.max_rating = max_rating;
+            parser.rated_only = rated_only;
+            parser.output_format = output_format;
+            parser.sort = sort;
+            parser.limit = limit;
+            parser.unordered = unordered;
+            parser.summary = summary;
+            parse_logs(&mut parser, directories, files, &filter, &progress)?
         }
         Subcommand::Anonymize {
             directories,
@@ -216,26 +606,163 @@ fn main() -> Result<(), BattleToolsError> {
             no_log,
             save_state_to,
             load_state_from,
+            manifest_path,
         } => {
             // create dir if needed
-            fs::create_dir_all(&output_dir)?;
+            fs::create_dir_all(&output_dir)
+                .with_context(|| format!("creating output directory {}", output_dir.display()))?;
 
             let mut anonymizer = if let Some(load_path) = load_state_from {
-                let json = fs::read_to_string(load_path)?;
-                let anonymizer = anonymize::Anonymizer::with_json(json, is_safe, no_log);
+                let describe = || format!("loading anonymizer state from {}", load_path.display());
+                let json = fs::read_to_string(&load_path).with_context(describe)?;
+                let anonymizer = anonymize::Anonymizer::with_json(json, is_safe, no_log)
+                    .with_context(describe)?;
                 AnonymizingDirectoryParser::with_anonymizer(anonymizer, output_dir)
             } else {
                 AnonymizingDirectoryParser::new(is_safe, no_log, output_dir)
             };
 
-            anonymizer.handle_directories(directories, options.exclude)?;
+            load_manifest(manifest_path.as_deref(), &mut anonymizer, &mut filter)?;
+            let error_report = parse_logs(&mut anonymizer, directories, files, &filter, &progress)?;
+            save_manifest(
+                manifest_path.as_deref(),
+                &anonymizer,
+                &mut filter,
+                &progress,
+            )?;
 
             if let Some(save_state_path) = save_state_to {
                 let json = anonymizer.get_state_json()?;
-                fs::write(save_state_path, json)?;
+                fs::write(&save_state_path, json).with_context(|| {
+                    format!("saving anonymizer state to {}", save_state_path.display())
+                })?;
             }
+            error_report
+        }
+        Subcommand::Grep {
+            pattern,
+            directories,
+            chat_only,
+            input_log,
+        } => {
+            let scope = if input_log {
+                GrepScope::InputLog
+            } else if chat_only {
+                GrepScope::Chat
+            } else {
+                GrepScope::Log
+            };
+            let mut parser = LogGrepper::new(&pattern, scope)
+                .with_context(|| format!("compiling the pattern {:?}", pattern))?;
+            parse_logs(&mut parser, directories, files, &filter, &progress)?
+        }
+        Subcommand::RatingHistory {
+            username,
+            directories,
+            output_format,
+            svg_path,
+        } => {
+            let mut parser = RatingHistory::new(&username, output_format, svg_path);
+            parse_logs(&mut parser, directories, files, &filter, &progress)?
+        }
+        Subcommand::Choices { directories } => {
+            parse_logs(&mut ChoiceExporter, directories, files, &filter, &progress)?
+        }
+        Subcommand::ReplayHtml { file, output_path } => {
+            let json =
+                fs::read_to_string(&file).with_context(|| format!("reading {}", file.display()))?;
+            let log =
+                BattleLog::parse(&json).with_context(|| format!("parsing {}", file.display()))?;
+
+            fs::write(&output_path, replay_html::replay_html(&log))
+                .with_context(|| format!("writing replay to {}", output_path.display()))?;
+            ErrorReport::default()
         }
+        Subcommand::Show {
+            file,
+            no_chat,
+            color,
+        } => {
+            let json =
+                fs::read_to_string(&file).with_context(|| format!("reading {}", file.display()))?;
+            let log =
+                BattleLog::parse(&json).with_context(|| format!("parsing {}", file.display()))?;
+
+            let mut stdout = BufWriter::new(io::stdout().lock());
+            Transcript::new(!no_chat, color.use_color()).write(&mut stdout, &log)?;
+            stdout.flush()?;
+            ErrorReport::default()
+        }
+    };
+
+    for line in filter.report() {
+        progress.message(line);
+    }
+
+    if let Some(error_report_path) = options.error_report_path {
+        fs::write(&error_report_path, error_report.to_jsonl()?)
+            .with_context(|| format!("writing error report to {}", error_report_path.display()))?;
+    }
+    if !error_report.is_empty() {
+        progress.message(format_args!(
+            "{} errors occurred:\n{}",
+            error_report.len(),
+            error_report.to_summary_table()
+        ));
+        if options.strict {
+            process::exit(error_report.exit_code());
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses either the listed files (if `--files-from` was given) or the directories
+fn parse_logs<P: ParallelDirectoryParser<R>, R>(
+    parser: &mut P,
+    directories: Vec<PathBuf>,
+    files: Option<Vec<PathBuf>>,
+    filter: &DirectoryFilter,
+    progress: &Progress,
+) -> Result<ErrorReport, BattleToolsError> {
+    match files {
+        Some(files) => parser.handle_files(files, filter, progress),
+        None => parser.handle_directories(directories, filter, progress),
+    }
+}
+
+/// Loads the manifest at `path` (if there is one), restoring the parser's state from it
+fn load_manifest<P: ResumableParser>(
+    path: Option<&Path>,
+    parser: &mut P,
+    filter: &mut DirectoryFilter,
+) -> Result<(), BattleToolsError> {
+    if let Some(path) = path {
+        let describe = || format!("loading manifest from {}", path.display());
+        let mut manifest = Manifest::load(path).with_context(describe)?;
+        if let Some(state) = manifest.take_state() {
+            parser.load_state(state).with_context(describe)?;
+        }
+        filter.manifest = Some(manifest);
     }
+    Ok(())
+}
 
+/// Saves the manifest loaded by `load_manifest`, along with the parser's new state
+fn save_manifest<P: ResumableParser>(
+    path: Option<&Path>,
+    parser: &P,
+    filter: &mut DirectoryFilter,
+    progress: &Progress,
+) -> Result<(), BattleToolsError> {
+    if let (Some(path), Some(manifest)) = (path, filter.manifest.take()) {
+        progress.message(format_args!(
+            "{} unchanged files in the manifest were skipped",
+            manifest.skipped()
+        ));
+        manifest
+            .save(path, parser.save_state()?)
+            .with_context(|| format!("saving manifest to {}", path.display()))?;
+    }
     Ok(())
 }
@@ -0,0 +1,1218 @@
+// Code for parsing the battle protocol lines in a battle log's `log` array.
+// See https://github.com/smogon/pokemon-showdown/blob/master/sim/SIM-PROTOCOL.md for the protocol itself.
+use serde_derive::Serialize;
+use std::{borrow::Cow, fmt};
+
+use crate::battle_log::Side;
+
+/// A Pokémon in a battle, like `p1a: Rotom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PokemonId<'a> {
+    pub side: Side,
+    /// The active slot (`a`, `b`, or `c`), or `None` for a Pokémon that isn't necessarily active
+    pub position: Option<char>,
+    /// The Pokémon's nickname
+    pub name: &'a str,
+}
+
+impl<'a> PokemonId<'a> {
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let (prefix, name) = s.split_once(": ")?;
+        let side = Side::from_id(prefix.get(..2)?)?;
+        let mut rest = prefix[2..].chars();
+        let position = rest.next();
+        if rest.next().is_some() || position.is_some_and(|c| !c.is_ascii_lowercase()) {
+            return None;
+        }
+        Some(Self {
+            side,
+            position,
+            name,
+        })
+    }
+}
+
+impl fmt::Display for PokemonId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.side.id())?;
+        if let Some(position) = self.position {
+            write!(f, "{}", position)?;
+        }
+        write!(f, ": {}", self.name)
+    }
+}
+
+/// A side, along with its player's name, like `p1: Annika`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideRef<'a> {
+    pub side: Side,
+    pub name: &'a str,
+}
+
+impl<'a> SideRef<'a> {
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let (side, name) = s.split_once(": ")?;
+        Some(Self {
+            side: Side::from_id(side)?,
+            name,
+        })
+    }
+}
+
+impl fmt::Display for SideRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.side.id(), self.name)
+    }
+}
+
+/// A Pokémon's species and other visible details, like `Rotom-Fan, L84, M, shiny, tera:Electric`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Details<'a> {
+    pub species: &'a str,
+    /// `None` means level 100
+    pub level: Option<u8>,
+    /// `M` or `F`; `None` for genderless Pokémon
+    pub gender: Option<char>,
+    pub shiny: bool,
+    pub tera_type: Option<&'a str>,
+    /// The details as written, so they're written back out unchanged, including any parts that aren't modeled
+    #[serde(skip)]
+    pub text: &'a str,
+}
+
+impl<'a> Details<'a> {
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let mut parts = s.split(", ");
+        let mut details = Self {
+            species: parts.next()?,
+            level: None,
+            gender: None,
+            shiny: false,
+            tera_type: None,
+            text: s,
+        };
+        // Parts that aren't recognized (perhaps added to the protocol later) are skipped,
+        // rather than losing the whole line
+        for part in parts {
+            match part {
+                "M" => details.gender = Some('M'),
+                "F" => details.gender = Some('F'),
+                "shiny" => details.shiny = true,
+                _ => {
+                    if let Some(level) = part.strip_prefix('L').and_then(|level| level.parse().ok())
+                    {
+                        details.level = Some(level);
+                    } else if let Some(tera_type) = part.strip_prefix("tera:") {
+                        details.tera_type = Some(tera_type);
+                    }
+                }
+            }
+        }
+        Some(details)
+    }
+}
+
+impl fmt::Display for Details<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// A Pokémon's HP and status, like `45/100 par` or `0 fnt`.
+///
+/// Spectators (and so battle logs) usually see HP out of 100, but it can be out of the Pokémon's actual max HP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hp<'a> {
+    pub current: u32,
+    /// `None` when the Pokémon has fainted (`0 fnt`)
+    pub max: Option<u32>,
+    /// A status condition (`brn`, `par`, `slp`, `frz`, `psn`, `tox`, or `fnt`)
+    pub status: Option<&'a str>,
+}
+
+impl<'a> Hp<'a> {
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let (hp, status) = match s.split_once(' ') {
+            Some((hp, status)) => (hp, Some(status)),
+            None => (s, None),
+        };
+        let (current, max) = match hp.split_once('/') {
+            Some((current, max)) => (current.parse().ok()?, Some(max.parse().ok()?)),
+            None => (hp.parse().ok()?, None),
+        };
+        Some(Self {
+            current,
+            max,
+            status,
+        })
+    }
+}
+
+impl fmt::Display for Hp<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.current)?;
+        if let Some(max) = self.max {
+            write!(f, "/{}", max)?;
+        }
+        if let Some(status) = self.status {
+            write!(f, " {}", status)?;
+        }
+        Ok(())
+    }
+}
+
+/// A tag at the end of a battle action line, like `[from] item: Leftovers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tag<'a> {
+    /// What caused the action (`[from] EFFECT`)
+    From(&'a str),
+    /// The Pokémon that caused the action (`[of] POKEMON`)
+    Of(PokemonId<'a>),
+    /// A tag without a value, like `[silent]` or `[still]`
+    Flag(&'a str),
+    Other {
+        name: &'a str,
+        value: &'a str,
+    },
+}
+
+impl<'a> Tag<'a> {
+    /// Parses a tag; the space after the name is optional (PS writes `[from]lockedmove`, for example)
+    fn parse(s: &'a str) -> Option<Self> {
+        let (name, value) = s.strip_prefix('[')?.split_once(']')?;
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        Some(match (name, value) {
+            (name, "") => Tag::Flag(name),
+            ("from", value) => Tag::From(value),
+            ("of", value) => match PokemonId::parse(value) {
+                Some(pokemon) => Tag::Of(pokemon),
+                None => Tag::Other { name, value },
+            },
+            (name, value) => Tag::Other { name, value },
+        })
+    }
+}
+
+impl fmt::Display for Tag<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tag::From(effect) => write!(f, "[from] {}", effect),
+            Tag::Of(pokemon) => write!(f, "[of] {}", pokemon),
+            Tag::Flag(name) => write!(f, "[{}]", name),
+            Tag::Other { name, value } => write!(f, "[{}] {}", name, value),
+        }
+    }
+}
+
+/// How a Pokémon came into battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchKind {
+    /// `|switch|`: the player switched
+    Switch,
+    /// `|drag|`: the Pokémon was forced in (by Roar, for example)
+    Drag,
+    /// `|replace|`: an Illusion was broken, revealing the real Pokémon
+    Replace,
+}
+
+impl SwitchKind {
+    fn name(self) -> &'static str {
+        match self {
+            SwitchKind::Switch => "switch",
+            SwitchKind::Drag => "drag",
+            SwitchKind::Replace => "replace",
+        }
+    }
+}
+
+/// One line of the battle protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// `|`
+    Blank,
+    /// A line that doesn't start with `|`, which the client shows as-is
+    Text(&'a str),
+
+    // Battle initialization
+    Player {
+        side: Side,
+        /// `None` (or an empty name) when the player has left
+        name: Option<&'a str>,
+        avatar: Option<&'a str>,
+        rating: Option<&'a str>,
+    },
+    TeamSize {
+        side: Side,
+        size: u8,
+    },
+    GameType(&'a str),
+    Gen(u8),
+    Tier(&'a str),
+    Rated(Option<&'a str>),
+    Rule(&'a str),
+    Start,
+
+    // Battle progress
+    Turn(u32),
+    Upkeep,
+    Win(&'a str),
+    Tie,
+    /// `|t:|`, a Unix timestamp
+    Timestamp(&'a str),
+
+    // Room messages
+    /// `quiet` is true for `|J|`, which isn't shown in the chat
+    Join {
+        user: &'a str,
+        quiet: bool,
+    },
+    Leave {
+        user: &'a str,
+        quiet: bool,
+    },
+    Rename {
+        user: &'a str,
+        old_id: &'a str,
+        quiet: bool,
+    },
+    Chat {
+        /// Only for `|c:|` lines
+        timestamp: Option<&'a str>,
+        user: &'a str,
+        message: &'a str,
+    },
+    Inactive(&'a str),
+    InactiveOff(&'a str),
+    Raw(&'a str),
+    Message(&'a str),
+
+    // Major actions
+    Switch {
+        kind: SwitchKind,
+        pokemon: PokemonId<'a>,
+        details: Details<'a>,
+        hp: Option<Hp<'a>>,
+    },
+    DetailsChange {
+        pokemon: PokemonId<'a>,
+        details: Details<'a>,
+        hp: Option<Hp<'a>>,
+    },
+    Move {
+        pokemon: PokemonId<'a>,
+        name: &'a str,
+        target: Option<PokemonId<'a>>,
+    },
+    Cant {
+        pokemon: PokemonId<'a>,
+        reason: &'a str,
+        move_name: Option<&'a str>,
+    },
+    Faint(PokemonId<'a>),
+
+    // Minor actions
+    Damage {
+        pokemon: PokemonId<'a>,
+        hp: Hp<'a>,
+    },
+    Heal {
+        pokemon: PokemonId<'a>,
+        hp: Hp<'a>,
+    },
+    SetHp {
+        pokemon: PokemonId<'a>,
+        hp: Hp<'a>,
+    },
+    Status {
+        pokemon: PokemonId<'a>,
+        status: &'a str,
+    },
+    CureStatus {
+        pokemon: PokemonId<'a>,
+        status: &'a str,
+    },
+    Boost {
+        pokemon: PokemonId<'a>,
+        stat: &'a str,
+        amount: u8,
+    },
+    Unboost {
+        pokemon: PokemonId<'a>,
+        stat: &'a str,
+        amount: u8,
+    },
+    SetBoost {
+        pokemon: PokemonId<'a>,
+        stat: &'a str,
+        amount: i8,
+    },
+    ClearBoost(PokemonId<'a>),
+    ClearNegativeBoost(PokemonId<'a>),
+    ClearAllBoost,
+    /// `none` when the weather ends
+    Weather(&'a str),
+    FieldStart(&'a str),
+    FieldEnd(&'a str),
+    SideStart {
+        side: SideRef<'a>,
+        condition: &'a str,
+    },
+    SideEnd {
+        side: SideRef<'a>,
+        condition: &'a str,
+    },
+    /// `|-start|`: a volatile status (like Substitute or confusion) started
+    VolatileStart {
+        pokemon: PokemonId<'a>,
+        effect: &'a str,
+        info: Vec<&'a str>,
+    },
+    VolatileEnd {
+        pokemon: PokemonId<'a>,
+        effect: &'a str,
+        info: Vec<&'a str>,
+    },
+    Item {
+        pokemon: PokemonId<'a>,
+        item: &'a str,
+    },
+    EndItem {
+        pokemon: PokemonId<'a>,
+        item: &'a str,
+    },
+    Ability {
+        pokemon: PokemonId<'a>,
+        ability: &'a str,
+        info: Vec<&'a str>,
+    },
+    Transform {
+        pokemon: PokemonId<'a>,
+        target: PokemonId<'a>,
+    },
+    FormeChange {
+        pokemon: PokemonId<'a>,
+        species: &'a str,
+        hp: Option<Hp<'a>>,
+    },
+    Terastallize {
+        pokemon: PokemonId<'a>,
+        tera_type: &'a str,
+    },
+    Activate {
+        pokemon: PokemonId<'a>,
+        effect: &'a str,
+        info: Vec<&'a str>,
+    },
+    Crit(PokemonId<'a>),
+    SuperEffective(PokemonId<'a>),
+    Resisted(PokemonId<'a>),
+    Immune(PokemonId<'a>),
+    Miss {
+        source: PokemonId<'a>,
+        target: Option<PokemonId<'a>>,
+    },
+    Fail {
+        pokemon: PokemonId<'a>,
+        action: Option<&'a str>,
+    },
+
+    /// A line that isn't modeled above (or doesn't have the expected arguments)
+    Unknown {
+        kind: &'a str,
+        args: Vec<&'a str>,
+    },
+}
+
+/// Kinds of line whose last argument is free text, which may contain `|`
+const TEXT_KINDS: [(&str, usize); 7] = [
+    ("c", 2),
+    ("c:", 3),
+    ("raw", 1),
+    ("html", 1),
+    ("-message", 1),
+    ("inactive", 1),
+    ("inactiveoff", 1),
+];
+
+/// Whether a kind of line is a battle action, which may end with tags
+fn has_tags(kind: &str) -> bool {
+    kind.starts_with('-')
+        || matches!(
+            kind,
+            "move" | "switch" | "drag" | "replace" | "detailschange" | "cant" | "faint"
+        )
+}
+
+impl<'a> Event<'a> {
+    /// Parses an event from its kind and (untagged) arguments; returns `None` if they don't fit any variant
+    fn from_args(kind: &'a str, args: &[&'a str]) -> Option<Self> {
+        let pokemon = |s: &'a str| PokemonId::parse(s);
+        // An empty argument (like the target of `|move|p1a: Rotom|Tackle||[notarget]`) is the same as a missing one
+        let optional_pokemon = |s: Option<&&'a str>| match s {
+            Some(&"") | None => Some(None),
+            Some(s) => PokemonId::parse(s).map(Some),
+        };
+        let hp = |s: Option<&&'a str>| match s {
+            Some(s) => Hp::parse(s).map(Some),
+            None => Some(None),
+        };
+
+        Some(match (kind, args) {
+            ("player", [side, rest @ ..]) if rest.len() <= 3 => Event::Player {
+                side: Side::from_id(side)?,
+                name: rest.first().copied(),
+                avatar: rest.get(1).copied(),
+                rating: rest.get(2).copied(),
+            },
+            ("teamsize", [side, size]) => Event::TeamSize {
+                side: Side::from_id(side)?,
+                size: size.parse().ok()?,
+            },
+            ("gametype", [game_type]) => Event::GameType(game_type),
+            ("gen", [generation]) => Event::Gen(generation.parse().ok()?),
+            ("tier", [tier]) => Event::Tier(tier),
+            ("rated", []) => Event::Rated(None),
+            ("rated", [message]) => Event::Rated(Some(message)),
+            ("rule", [rule]) => Event::Rule(rule),
+            ("start", []) => Event::Start,
+            ("turn", [turn]) => Event::Turn(turn.parse().ok()?),
+            ("upkeep", []) => Event::Upkeep,
+            ("win", [user]) => Event::Win(user),
+            ("tie", []) => Event::Tie,
+            ("t:", [timestamp]) => Event::Timestamp(timestamp),
+            ("j" | "J", [user]) => Event::Join {
+                user,
+                quiet: kind == "J",
+            },
+            ("l" | "L", [user]) => Event::Leave {
+                user,
+                quiet: kind == "L",
+            },
+            ("n" | "N", [user, old_id]) => Event::Rename {
+                user,
+                old_id,
+                quiet: kind == "N",
+            },
+            ("c", [user, message]) => Event::Chat {
+                timestamp: None,
+                user,
+                message,
+            },
+            ("c:", [timestamp, user, message]) => Event::Chat {
+                timestamp: Some(timestamp),
+                user,
+                message,
+            },
+            ("inactive", [message]) => Event::Inactive(message),
+            ("inactiveoff", [message]) => Event::InactiveOff(message),
+            ("raw", [html]) => Event::Raw(html),
+            ("-message", [message]) => Event::Message(message),
+
+            ("switch" | "drag" | "replace", [id, details, rest @ ..]) if rest.len() <= 1 => {
+                Event::Switch {
+                    kind: match kind {
+                        "switch" => SwitchKind::Switch,
+                        "drag" => SwitchKind::Drag,
+                        _ => SwitchKind::Replace,
+                    },
+                    pokemon: pokemon(id)?,
+                    details: Details::parse(details)?,
+                    hp: hp(rest.first())?,
+                }
+            }
+            ("detailschange", [id, details, rest @ ..]) if rest.len() <= 1 => {
+                Event::DetailsChange {
+                    pokemon: pokemon(id)?,
+                    details: Details::parse(details)?,
+                    hp: hp(rest.first())?,
+                }
+            }
+            ("move", [id, name, rest @ ..]) if rest.len() <= 1 => Event::Move {
+                pokemon: pokemon(id)?,
+                name,
+                target: optional_pokemon(rest.first())?,
+            },
+            ("cant", [id, reason, rest @ ..]) if rest.len() <= 1 => Event::Cant {
+                pokemon: pokemon(id)?,
+                reason,
+                move_name: rest.first().copied(),
+            },
+            ("faint", [id]) => Event::Faint(pokemon(id)?),
+
+            ("-damage", [id, health]) => Event::Damage {
+                pokemon: pokemon(id)?,
+                hp: Hp::parse(health)?,
+            },
+            ("-heal", [id, health]) => Event::Heal {
+                pokemon: pokemon(id)?,
+                hp: Hp::parse(health)?,
+            },
+            ("-sethp", [id, health]) => Event::SetHp {
+                pokemon: pokemon(id)?,
+                hp: Hp::parse(health)?,
+            },
+            ("-status", [id, status]) => Event::Status {
+                pokemon: pokemon(id)?,
+                status,
+            },
+            ("-curestatus", [id, status]) => Event::CureStatus {
+                pokemon: pokemon(id)?,
+                status,
+            },
+            ("-boost", [id, stat, amount]) => Event::Boost {
+                pokemon: pokemon(id)?,
+                stat,
+                amount: amount.parse().ok()?,
+            },
+            ("-unboost", [id, stat, amount]) => Event::Unboost {
+                pokemon: pokemon(id)?,
+                stat,
+                amount: amount.parse().ok()?,
+            },
+            ("-setboost", [id, stat, amount]) => Event::SetBoost {
+                pokemon: pokemon(id)?,
+                stat,
+                amount: amount.parse().ok()?,
+            },
+            ("-clearboost", [id]) => Event::ClearBoost(pokemon(id)?),
+            ("-clearnegativeboost", [id]) => Event::ClearNegativeBoost(pokemon(id)?),
+            ("-clearallboost", []) => Event::ClearAllBoost,
+            ("-weather", [weather]) => Event::Weather(weather),
+            ("-fieldstart", [condition]) => Event::FieldStart(condition),
+            ("-fieldend", [condition]) => Event::FieldEnd(condition),
+            ("-sidestart", [side, condition]) => Event::SideStart {
+                side: SideRef::parse(side)?,
+                condition,
+            },
+            ("-sideend", [side, condition]) => Event::SideEnd {
+                side: SideRef::parse(side)?,
+                condition,
+            },
+            ("-start", [id, effect, info @ ..]) => Event::VolatileStart {
+                pokemon: pokemon(id)?,
+                effect,
+                info: info.to_vec(),
+            },
+            ("-end", [id, effect, info @ ..]) => Event::VolatileEnd {
+                pokemon: pokemon(id)?,
+                effect,
+                info: info.to_vec(),
+            },
+            ("-item", [id, item]) => Event::Item {
+                pokemon: pokemon(id)?,
+                item,
+            },
+            ("-enditem", [id, item]) => Event::EndItem {
+                pokemon: pokemon(id)?,
+                item,
+            },
+            ("-ability", [id, ability, info @ ..]) => Event::Ability {
+                pokemon: pokemon(id)?,
+                ability,
+                info: info.to_vec(),
+            },
+            ("-transform", [id, target]) => Event::Transform {
+                pokemon: pokemon(id)?,
+                target: pokemon(target)?,
+            },
+            ("-formechange", [id, species, rest @ ..]) if rest.len() <= 1 => Event::FormeChange {
+                pokemon: pokemon(id)?,
+                species,
+                hp: hp(rest.first())?,
+            },
+            ("-terastallize", [id, tera_type]) => Event::Terastallize {
+                pokemon: pokemon(id)?,
+                tera_type,
+            },
+            ("-activate", [id, effect, info @ ..]) => Event::Activate {
+                pokemon: pokemon(id)?,
+                effect,
+                info: info.to_vec(),
+            },
+            ("-crit", [id]) => Event::Crit(pokemon(id)?),
+            ("-supereffective", [id]) => Event::SuperEffective(pokemon(id)?),
+            ("-resisted", [id]) => Event::Resisted(pokemon(id)?),
+            ("-immune", [id]) => Event::Immune(pokemon(id)?),
+            ("-miss", [source, rest @ ..]) if rest.len() <= 1 => Event::Miss {
+                source: pokemon(source)?,
+                target: optional_pokemon(rest.first())?,
+            },
+            ("-fail", [id, rest @ ..]) if rest.len() <= 1 => Event::Fail {
+                pokemon: pokemon(id)?,
+                action: rest.first().copied(),
+            },
+            _ => return None,
+        })
+    }
+
+    /// The Pokémon mentioned in the event
+    fn pokemon_mut(&mut self) -> Vec<&mut PokemonId<'a>> {
+        match self {
+            Event::Switch { pokemon, .. }
+            | Event::DetailsChange { pokemon, .. }
+            | Event::Cant { pokemon, .. }
+            | Event::Faint(pokemon)
+            | Event::Damage { pokemon, .. }
+            | Event::Heal { pokemon, .. }
+            | Event::SetHp { pokemon, .. }
+            | Event::Status { pokemon, .. }
+            | Event::CureStatus { pokemon, .. }
+            | Event::Boost { pokemon, .. }
+            | Event::Unboost { pokemon, .. }
+            | Event::SetBoost { pokemon, .. }
+            | Event::ClearBoost(pokemon)
+            | Event::ClearNegativeBoost(pokemon)
+            | Event::VolatileStart { pokemon, .. }
+            | Event::VolatileEnd { pokemon, .. }
+            | Event::Item { pokemon, .. }
+            | Event::EndItem { pokemon, .. }
+            | Event::Ability { pokemon, .. }
+            | Event::FormeChange { pokemon, .. }
+            | Event::Terastallize { pokemon, .. }
+            | Event::Activate { pokemon, .. }
+            | Event::Crit(pokemon)
+            | Event::SuperEffective(pokemon)
+            | Event::Resisted(pokemon)
+            | Event::Immune(pokemon)
+            | Event::Fail { pokemon, .. } => vec![pokemon],
+            Event::Move {
+                pokemon: source,
+                target,
+                ..
+            }
+            | Event::Miss { source, target } => {
+                std::iter::once(source).chain(target.as_mut()).collect()
+            }
+            Event::Transform { pokemon, target } => vec![pokemon, target],
+            _ => vec![],
+        }
+    }
+}
+
+/// A parsed line of the battle protocol: an event, plus any tags at the end of it.
+///
+/// Displaying a line gives back the original text, except that tags are always written as `[name] value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line<'a> {
+    pub event: Event<'a>,
+    pub tags: Vec<Tag<'a>>,
+}
+
+impl<'a> Line<'a> {
+    /// Parses a line from a battle log's `log` array.
+    ///
+    /// Lines that aren't understood become `Event::Unknown` (or `Event::Text`), so this never fails.
+    pub fn parse(line: &'a str) -> Self {
+        let rest = match line.strip_prefix('|') {
+            Some("") => return Self::untagged(Event::Blank),
+            Some(rest) => rest,
+            None => return Self::untagged(Event::Text(line)),
+        };
+        let kind = rest.split('|').next().unwrap_or_default();
+        let args_text = rest.get(kind.len() + 1..);
+
+        if let Some((_, count)) = TEXT_KINDS.iter().find(|(text_kind, _)| *text_kind == kind) {
+            let args = args_text.map_or(vec![], |text| text.splitn(*count, '|').collect());
+            return Self::untagged(
+                Event::from_args(kind, &args).unwrap_or(Event::Unknown { kind, args }),
+            );
+        }
+
+        let mut args: Vec<&str> = args_text.map_or(vec![], |text| text.split('|').collect());
+        let mut tags = vec![];
+        if has_tags(kind) {
+            while let Some(tag) = args.last().and_then(|arg| Tag::parse(arg)) {
+                tags.push(tag);
+                args.pop();
+            }
+            tags.reverse();
+        }
+        Self {
+            event: Event::from_args(kind, &args).unwrap_or(Event::Unknown { kind, args }),
+            tags,
+        }
+    }
+
+    fn untagged(event: Event<'a>) -> Self {
+        Self {
+            event,
+            tags: vec![],
+        }
+    }
+
+    /// Calls `f` with the side and name of every Pokémon and side mentioned in the line, allowing the names to be
+    /// changed.
+    pub fn rename(&mut self, mut f: impl FnMut(Side, &mut &'a str)) {
+        for pokemon in self.event.pokemon_mut() {
+            f(pokemon.side, &mut pokemon.name);
+        }
+        if let Event::SideStart { side, .. } | Event::SideEnd { side, .. } = &mut self.event {
+            f(side.side, &mut side.name);
+        }
+        for tag in &mut self.tags {
+            if let Tag::Of(pokemon) = tag {
+                f(pokemon.side, &mut pokemon.name);
+            }
+        }
+    }
+}
+
+/// The lines of a battle's `log` that spectators saw.
+///
+/// Lines with a Pokémon's exact HP are written as `|split|p1` (or the side that saw them), then the line with the
+/// exact HP that only that player saw, and then the same line with the HP as a percentage; this skips the first two.
+pub struct PublicLines<'l, 'a> {
+    lines: std::slice::Iter<'l, Cow<'a, str>>,
+}
+
+impl<'l, 'a> PublicLines<'l, 'a> {
+    pub fn new(log: &'l [Cow<'a, str>]) -> Self {
+        Self { lines: log.iter() }
+    }
+}
+
+impl<'l, 'a> Iterator for PublicLines<'l, 'a> {
+    type Item = &'l Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        if line.starts_with("|split|") {
+            self.lines.next();
+            return self.lines.next();
+        }
+        Some(line)
+    }
+}
+
+/// Writes each argument, preceded by `|`
+fn write_args<T: fmt::Display>(f: &mut fmt::Formatter, args: &[T]) -> fmt::Result {
+    for arg in args {
+        write!(f, "|{}", arg)?;
+    }
+    Ok(())
+}
+
+fn write_optional<T: fmt::Display>(f: &mut fmt::Formatter, arg: &Option<T>) -> fmt::Result {
+    match arg {
+        Some(arg) => write!(f, "|{}", arg),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Blank => write!(f, "|"),
+            Event::Text(text) => write!(f, "{}", text),
+            Event::Player {
+                side,
+                name,
+                avatar,
+                rating,
+            } => {
+                write!(f, "|player|{}", side.id())?;
+                write_optional(f, name)?;
+                write_optional(f, avatar)?;
+                write_optional(f, rating)
+            }
+            Event::TeamSize { side, size } => write!(f, "|teamsize|{}|{}", side.id(), size),
+            Event::GameType(game_type) => write!(f, "|gametype|{}", game_type),
+            Event::Gen(generation) => write!(f, "|gen|{}", generation),
+            Event::Tier(tier) => write!(f, "|tier|{}", tier),
+            Event::Rated(message) => {
+                write!(f, "|rated")?;
+                write_optional(f, message)
+            }
+            Event::Rule(rule) => write!(f, "|rule|{}", rule),
+            Event::Start => write!(f, "|start"),
+            Event::Turn(turn) => write!(f, "|turn|{}", turn),
+            Event::Upkeep => write!(f, "|upkeep"),
+            Event::Win(user) => write!(f, "|win|{}", user),
+            Event::Tie => write!(f, "|tie"),
+            Event::Timestamp(timestamp) => write!(f, "|t:|{}", timestamp),
+            Event::Join { user, quiet } => {
+                write!(f, "|{}|{}", if *quiet { "J" } else { "j" }, user)
+            }
+            Event::Leave { user, quiet } => {
+                write!(f, "|{}|{}", if *quiet { "L" } else { "l" }, user)
+            }
+            Event::Rename {
+                user,
+                old_id,
+                quiet,
+            } => write!(f, "|{}|{}|{}", if *quiet { "N" } else { "n" }, user, old_id),
+            Event::Chat {
+                timestamp: None,
+                user,
+                message,
+            } => write!(f, "|c|{}|{}", user, message),
+            Event::Chat {
+                timestamp: Some(timestamp),
+                user,
+                message,
+            } => write!(f, "|c:|{}|{}|{}", timestamp, user, message),
+            Event::Inactive(message) => write!(f, "|inactive|{}", message),
+            Event::InactiveOff(message) => write!(f, "|inactiveoff|{}", message),
+            Event::Raw(html) => write!(f, "|raw|{}", html),
+            Event::Message(message) => write!(f, "|-message|{}", message),
+            Event::Switch {
+                kind,
+                pokemon,
+                details,
+                hp,
+            } => {
+                write!(f, "|{}|{}|{}", kind.name(), pokemon, details)?;
+                write_optional(f, hp)
+            }
+            Event::DetailsChange {
+                pokemon,
+                details,
+                hp,
+            } => {
+                write!(f, "|detailschange|{}|{}", pokemon, details)?;
+                write_optional(f, hp)
+            }
+            Event::Move {
+                pokemon,
+                name,
+                target,
+            } => {
+                write!(f, "|move|{}|{}", pokemon, name)?;
+                write_optional(f, target)
+            }
+            Event::Cant {
+                pokemon,
+                reason,
+                move_name,
+            } => {
+                write!(f, "|cant|{}|{}", pokemon, reason)?;
+                write_optional(f, move_name)
+            }
+            Event::Faint(pokemon) => write!(f, "|faint|{}", pokemon),
+            Event::Damage { pokemon, hp } => write!(f, "|-damage|{}|{}", pokemon, hp),
+            Event::Heal { pokemon, hp } => write!(f, "|-heal|{}|{}", pokemon, hp),
+            Event::SetHp { pokemon, hp } => write!(f, "|-sethp|{}|{}", pokemon, hp),
+            Event::Status { pokemon, status } => write!(f, "|-status|{}|{}", pokemon, status),
+            Event::CureStatus { pokemon, status } => {
+                write!(f, "|-curestatus|{}|{}", pokemon, status)
+            }
+            Event::Boost {
+                pokemon,
+                stat,
+                amount,
+            } => write!(f, "|-boost|{}|{}|{}", pokemon, stat, amount),
+            Event::Unboost {
+                pokemon,
+                stat,
+                amount,
+            } => write!(f, "|-unboost|{}|{}|{}", pokemon, stat, amount),
+            Event::SetBoost {
+                pokemon,
+                stat,
+                amount,
+            } => write!(f, "|-setboost|{}|{}|{}", pokemon, stat, amount),
+            Event::ClearBoost(pokemon) => write!(f, "|-clearboost|{}", pokemon),
+            Event::ClearNegativeBoost(pokemon) => write!(f, "|-clearnegativeboost|{}", pokemon),
+            Event::ClearAllBoost => write!(f, "|-clearallboost"),
+            Event::Weather(weather) => write!(f, "|-weather|{}", weather),
+            Event::FieldStart(condition) => write!(f, "|-fieldstart|{}", condition),
+            Event::FieldEnd(condition) => write!(f, "|-fieldend|{}", condition),
+            Event::SideStart { side, condition } => {
+                write!(f, "|-sidestart|{}|{}", side, condition)
+            }
+            Event::SideEnd { side, condition } => write!(f, "|-sideend|{}|{}", side, condition),
+            Event::VolatileStart {
+                pokemon,
+                effect,
+                info,
+            } => {
+                write!(f, "|-start|{}|{}", pokemon, effect)?;
+                write_args(f, info)
+            }
+            Event::VolatileEnd {
+                pokemon,
+                effect,
+                info,
+            } => {
+                write!(f, "|-end|{}|{}", pokemon, effect)?;
+                write_args(f, info)
+            }
+            Event::Item { pokemon, item } => write!(f, "|-item|{}|{}", pokemon, item),
+            Event::EndItem { pokemon, item } => write!(f, "|-enditem|{}|{}", pokemon, item),
+            Event::Ability {
+                pokemon,
+                ability,
+                info,
+            } => {
+                write!(f, "|-ability|{}|{}", pokemon, ability)?;
+                write_args(f, info)
+            }
+            Event::Transform { pokemon, target } => {
+                write!(f, "|-transform|{}|{}", pokemon, target)
+            }
+            Event::FormeChange {
+                pokemon,
+                species,
+                hp,
+            } => {
+                write!(f, "|-formechange|{}|{}", pokemon, species)?;
+                write_optional(f, hp)
+            }
+            Event::Terastallize { pokemon, tera_type } => {
+                write!(f, "|-terastallize|{}|{}", pokemon, tera_type)
+            }
+            Event::Activate {
+                pokemon,
+                effect,
+                info,
+            } => {
+                write!(f, "|-activate|{}|{}", pokemon, effect)?;
+                write_args(f, info)
+            }
+            Event::Crit(pokemon) => write!(f, "|-crit|{}", pokemon),
+            Event::SuperEffective(pokemon) => write!(f, "|-supereffective|{}", pokemon),
+            Event::Resisted(pokemon) => write!(f, "|-resisted|{}", pokemon),
+            Event::Immune(pokemon) => write!(f, "|-immune|{}", pokemon),
+            Event::Miss { source, target } => {
+                write!(f, "|-miss|{}", source)?;
+                write_optional(f, target)
+            }
+            Event::Fail { pokemon, action } => {
+                write!(f, "|-fail|{}", pokemon)?;
+                write_optional(f, action)
+            }
+            Event::Unknown { kind, args } => {
+                write!(f, "|{}", kind)?;
+                write_args(f, args)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.event)?;
+        // Arguments are positional, so a move's empty target is kept when tags follow it
+        if !self.tags.is_empty() && matches!(self.event, Event::Move { target: None, .. }) {
+            write!(f, "|")?;
+        }
+        write_args(f, &self.tags)
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn event(line: &str) -> Event<'_> {
+        Line::parse(line).event
+    }
+
+    fn id(side: Side, position: char, name: &str) -> PokemonId<'_> {
+        PokemonId {
+            side,
+            position: Some(position),
+            name,
+        }
+    }
+
+    #[test]
+    fn identifiers_and_details() {
+        assert_eq!(
+            PokemonId::parse("p2b: Mr. Mime: Galar?"),
+            Some(id(Side::P2, 'b', "Mr. Mime: Galar?"))
+        );
+        assert_eq!(
+            PokemonId::parse("p1: Rotom").map(|pokemon| pokemon.position),
+            Some(None)
+        );
+        assert_eq!(PokemonId::parse("p3a: Rotom"), None);
+        assert_eq!(PokemonId::parse("p1ab: Rotom"), None);
+        assert_eq!(PokemonId::parse("Rotom"), None);
+
+        assert_eq!(
+            Details::parse("Rotom-Fan, L84, M, shiny, tera:Electric"),
+            Some(Details {
+                species: "Rotom-Fan",
+                level: Some(84),
+                gender: Some('M'),
+                shiny: true,
+                tera_type: Some("Electric"),
+                text: "Rotom-Fan, L84, M, shiny, tera:Electric",
+            })
+        );
+        // parts that aren't recognized are skipped, but kept when the details are written out
+        let details = Details::parse("Ditto, L84, ???").unwrap();
+        assert_eq!((details.species, details.level), ("Ditto", Some(84)));
+        assert_eq!(details.to_string(), "Ditto, L84, ???");
+
+        assert_eq!(
+            Hp::parse("45/100 par"),
+            Some(Hp {
+                current: 45,
+                max: Some(100),
+                status: Some("par"),
+            })
+        );
+        assert_eq!(
+            Hp::parse("0 fnt"),
+            Some(Hp {
+                current: 0,
+                max: None,
+                status: Some("fnt"),
+            })
+        );
+        assert_eq!(Hp::parse("lots"), None);
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            event("|switch|p1a: Rotom|Rotom-Fan, L84|100/100"),
+            Event::Switch {
+                kind: SwitchKind::Switch,
+                pokemon: id(Side::P1, 'a', "Rotom"),
+                details: Details::parse("Rotom-Fan, L84").unwrap(),
+                hp: Hp::parse("100/100"),
+            }
+        );
+        assert_eq!(
+            event("|move|p1a: Rotom|Volt Switch|p2a: Drednaw"),
+            Event::Move {
+                pokemon: id(Side::P1, 'a', "Rotom"),
+                name: "Volt Switch",
+                target: Some(id(Side::P2, 'a', "Drednaw")),
+            }
+        );
+        // an empty target is the same as a missing one
+        let line = Line::parse("|move|p1a: Rotom|Tackle||[notarget]");
+        assert_eq!(
+            line.event,
+            Event::Move {
+                pokemon: id(Side::P1, 'a', "Rotom"),
+                name: "Tackle",
+                target: None,
+            }
+        );
+        assert_eq!(line.tags, vec![Tag::Flag("notarget")]);
+        assert_eq!(event("|turn|3"), Event::Turn(3));
+        assert_eq!(event("|win|Annika"), Event::Win("Annika"));
+        assert_eq!(event("|-weather|RainDance"), Event::Weather("RainDance"));
+        assert_eq!(
+            event("|tier|[Gen 8] Random Battle"),
+            Event::Tier("[Gen 8] Random Battle")
+        );
+        assert_eq!(
+            event("|-terastallize|p2a: Drednaw|Water"),
+            Event::Terastallize {
+                pokemon: id(Side::P2, 'a', "Drednaw"),
+                tera_type: "Water",
+            }
+        );
+        assert_eq!(
+            event("|c|☆Annika|a message | with a pipe"),
+            Event::Chat {
+                timestamp: None,
+                user: "☆Annika",
+                message: "a message | with a pipe",
+            }
+        );
+        assert_eq!(event("|"), Event::Blank);
+        assert_eq!(event("plain text"), Event::Text("plain text"));
+    }
+
+    #[test]
+    fn tags() {
+        let line = Line::parse(
+            "|-damage|p2a: Drednaw|55/100|[from] item: Rocky Helmet|[of] p1a: Rotom|[silent]",
+        );
+        assert_eq!(
+            line.event,
+            Event::Damage {
+                pokemon: id(Side::P2, 'a', "Drednaw"),
+                hp: Hp::parse("55/100").unwrap(),
+            }
+        );
+        assert_eq!(
+            line.tags,
+            vec![
+                Tag::From("item: Rocky Helmet"),
+                Tag::Of(id(Side::P1, 'a', "Rotom")),
+                Tag::Flag("silent"),
+            ]
+        );
+
+        let line = Line::parse("|move|p2a: Drednaw|Outrage|p1a: Rotom|[from]lockedmove");
+        assert_eq!(line.tags, vec![Tag::From("lockedmove")]);
+        assert!(matches!(line.event, Event::Move { .. }));
+
+        // only battle actions have tags
+        assert!(Line::parse("|rule|[Gen 9] something").tags.is_empty());
+    }
+
+    #[test]
+    fn unknown_lines() {
+        assert_eq!(
+            event("|-fieldactivate|move: Perish Song"),
+            Event::Unknown {
+                kind: "-fieldactivate",
+                args: vec!["move: Perish Song"],
+            }
+        );
+        // known kinds with unexpected arguments fall back too
+        assert_eq!(
+            event("|turn|three"),
+            Event::Unknown {
+                kind: "turn",
+                args: vec!["three"],
+            }
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for line in [
+            "|",
+            "|j|☆Annika",
+            "|player|p1|Annika|cynthia|1400",
+            "|player|p2|",
+            "|rated|",
+            "|start",
+            "|switch|p1a: Rotom|Rotom-Fan, L84, shiny|100/100",
+            "|switch|p1a: Rotom|Rotom-Fan, L84, something new|100/100",
+            "|drag|p2a: Drednaw|Drednaw, L84, F|80/100 brn",
+            "|move|p1a: Rotom|Will-O-Wisp|p2a: Drednaw|[miss]",
+            "|move|p1a: Rotom|Tackle||[notarget]",
+            "|move|p1a: Rotom|Splash",
+            "|-damage|p2a: Drednaw|0 fnt|[from] Stealth Rock",
+            "|-boost|p1a: Rotom|spa|2|[from] item: Weakness Policy",
+            "|-setboost|p1a: Rotom|atk|-6",
+            "|-sidestart|p2: Rust Haters|move: Stealth Rock",
+            "|-start|p2a: Drednaw|typechange|Water/Rock|[from] ability: Protean",
+            "|-activate|p1a: Rotom|move: Protect",
+            "|cant|p2a: Drednaw|par",
+            "|-miss|p1a: Rotom",
+            "|c:|1632906000|☆Annika|gg | wp",
+            "|-fieldactivate|move: Perish Song",
+            "|-anim|p1a: Rotom|Volt Switch|p2a: Drednaw",
+            "|t:|1632906000",
+        ] {
+            assert_eq!(Line::parse(line).to_string(), line);
+        }
+    }
+
+    #[test]
+    fn renaming() {
+        let mut line =
+            Line::parse("|-damage|p2a: Annika|55/100|[from] item: Rocky Helmet|[of] p1a: Annika");
+        line.rename(|side, name| {
+            if side == Side::P1 {
+                *name = "1";
+            }
+        });
+        assert_eq!(
+            line.to_string(),
+            "|-damage|p2a: Annika|55/100|[from] item: Rocky Helmet|[of] p1a: 1"
+        );
+    }
+}
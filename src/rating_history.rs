@@ -0,0 +1,387 @@
+// Code for following a user's ladder rating over time.
+use serde_derive::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    battle_log::{room_number, BattleLog},
+    csv::csv_field,
+    date::LogTime,
+    directory::LogParser,
+    id::{escape, to_id},
+    BattleToolsError,
+};
+
+/// How the rating history is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// CSV with a header row
+    Csv,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for HistoryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(HistoryFormat::Csv),
+            "json" => Ok(HistoryFormat::Json),
+            _ => Err(format!(
+                "unknown output format '{}' (expected csv or json)",
+                s
+            )),
+        }
+    }
+}
+
+/// The user's rating before one of their rated battles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RatingPoint {
+    /// In ISO 8601 format, or as written in the log if it couldn't be parsed
+    pub timestamp: String,
+    #[serde(skip)]
+    pub unix_time: Option<i64>,
+    pub roomid: Option<String>,
+    pub format: String,
+    pub elo: Option<f64>,
+    pub gxe: Option<f64>,
+    pub opponent: String,
+    /// `win`, `loss`, or `tie`
+    pub result: &'static str,
+}
+
+const CSV_HEADER: &str = "timestamp,roomid,format,elo,gxe,opponent,result";
+
+impl RatingPoint {
+    fn to_csv(&self) -> String {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        [
+            csv_field(&self.timestamp),
+            csv_field(self.roomid.as_deref().unwrap_or_default()),
+            csv_field(&self.format),
+            optional(self.elo),
+            optional(self.gxe),
+            csv_field(&self.opponent),
+            self.result.to_string(),
+        ]
+        .join(",")
+    }
+}
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 400.0;
+const CHART_MARGIN: f64 = 60.0;
+const CHART_COLORS: [&str; 6] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b",
+];
+
+/// Draws an SVG line chart of the Elo in each point, with a line for each format (since each has its own ladder).
+///
+/// Battles are spaced evenly along the x axis in the order they were played, so that
+/// rating changes stand out even if the user played many battles in a short time.
+pub fn rating_chart(points: &[RatingPoint]) -> String {
+    let rated = points
+        .iter()
+        .filter_map(|point| Some((point, point.elo?)))
+        .collect::<Vec<_>>();
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">
+<rect width="{w}" height="{h}" fill="white" />
+"#,
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT
+    );
+    if rated.is_empty() {
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">No rated battles</text>\n</svg>\n",
+            CHART_WIDTH / 2.0,
+            CHART_HEIGHT / 2.0
+        ));
+        return svg;
+    }
+
+    let min_elo = rated
+        .iter()
+        .map(|(_, elo)| *elo)
+        .fold(f64::INFINITY, f64::min);
+    let max_elo = rated
+        .iter()
+        .map(|(_, elo)| *elo)
+        .fold(f64::NEG_INFINITY, f64::max);
+    // keep a flat line off the edges of the chart
+    let (min_elo, max_elo) = if max_elo - min_elo < 1.0 {
+        (min_elo - 10.0, max_elo + 10.0)
+    } else {
+        (min_elo, max_elo)
+    };
+    let x = |index: usize| {
+        let step = (CHART_WIDTH - 2.0 * CHART_MARGIN) / (rated.len().max(2) - 1) as f64;
+        CHART_MARGIN + index as f64 * step
+    };
+    let y = |elo: f64| {
+        CHART_HEIGHT
+            - CHART_MARGIN
+            - (elo - min_elo) / (max_elo - min_elo) * (CHART_HEIGHT - 2.0 * CHART_MARGIN)
+    };
+
+    // axes, labelled with the Elo range and the first and last battles' timestamps
+    let (left, right) = (CHART_MARGIN, CHART_WIDTH - CHART_MARGIN);
+    let (top, bottom) = (CHART_MARGIN, CHART_HEIGHT - CHART_MARGIN);
+    svg.push_str(&format!(
+        r##"<polyline fill="none" stroke="#888" points="{left},{top} {left},{bottom} {right},{bottom}" />
+<text x="{label_x}" y="{top}" text-anchor="end">{max:.0}</text>
+<text x="{label_x}" y="{bottom}" text-anchor="end">{min:.0}</text>
+<text x="{left}" y="{time_y}">{first}</text>
+<text x="{right}" y="{time_y}" text-anchor="end">{last}</text>
+"##,
+        label_x = left - 5.0,
+        max = max_elo,
+        min = min_elo,
+        time_y = bottom + 20.0,
+        first = escape(&rated[0].0.timestamp),
+        last = escape(&rated[rated.len() - 1].0.timestamp),
+    ));
+
+    let mut formats: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, (point, _)) in rated.iter().enumerate() {
+        formats.entry(&point.format).or_default().push(index);
+    }
+    for (i, (format, indices)) in formats.into_iter().enumerate() {
+        let color = CHART_COLORS[i % CHART_COLORS.len()];
+        let coordinates = indices
+            .iter()
+            .map(|&index| format!("{:.1},{:.1}", x(index), y(rated[index].1)))
+            .collect::<Vec<_>>();
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\" />\n",
+            color,
+            coordinates.join(" ")
+        ));
+        for &index in &indices {
+            let (point, elo) = rated[index];
+            svg.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"><title>{}: {:.0} Elo before a {} against {}</title></circle>\n",
+                x(index),
+                y(elo),
+                color,
+                escape(&point.timestamp),
+                elo,
+                point.result,
+                escape(&point.opponent)
+            ));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>\n",
+            left,
+            20.0 + 15.0 * i as f64,
+            color,
+            escape(format)
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Prints a user's rating before each of their rated battles, in the order the battles were played
+pub struct RatingHistory {
+    pub user_id: String,
+    pub output_format: HistoryFormat,
+    /// If set, a chart of the history is also written to this path
+    pub svg_path: Option<PathBuf>,
+}
+
+impl RatingHistory {
+    pub fn new(username: &str, output_format: HistoryFormat, svg_path: Option<PathBuf>) -> Self {
+        Self {
+            user_id: to_id(username),
+            output_format,
+            svg_path,
+        }
+    }
+
+    /// Sorts points by time, and then by room number; points whose time is unknown come last
+    fn sort(points: &mut [RatingPoint]) {
+        points.sort_by_key(|point| {
+            (
+                point.unix_time.is_none(),
+                point.unix_time,
+                point.roomid.as_deref().and_then(room_number),
+            )
+        });
+    }
+
+    pub fn write_points<W: Write>(&self, out: &mut W, points: &[RatingPoint]) -> io::Result<()> {
+        if self.output_format == HistoryFormat::Csv {
+            writeln!(out, "{}", CSV_HEADER)?;
+        }
+        for point in points {
+            match self.output_format {
+                HistoryFormat::Csv => writeln!(out, "{}", point.to_csv())?,
+                HistoryFormat::Json => {
+                    serde_json::to_writer(&mut *out, point)?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+impl LogParser<Vec<RatingPoint>> for RatingHistory {
+    fn new_accumulator(&self) -> Vec<RatingPoint> {
+        vec![]
+    }
+
+    fn handle_log_file(
+        &self,
+        raw_json: String,
+        _: &Path,
+        points: &mut Vec<RatingPoint>,
+    ) -> Result<(), BattleToolsError> {
+        let log = BattleLog::parse(&raw_json)?;
+        let side = match log.side_of(&self.user_id) {
+            Some(side) => side,
+            None => return Ok(()),
+        };
+        // unrated battles don't have ratings
+        let rating = match log.rating(side) {
+            Some(rating) => rating,
+            None => return Ok(()),
+        };
+
+        let time = log.timestamp.as_deref().and_then(LogTime::from_timestamp);
+        let result = if log.is_tie() {
+            "tie"
+        } else if to_id(&log.winner) == self.user_id {
+            "win"
+        } else {
+            "loss"
+        };
+        points.push(RatingPoint {
+            timestamp: match time {
+                Some(time) => time.to_string(),
+                None => log.timestamp.as_deref().unwrap_or_default().to_string(),
+            },
+            unix_time: time.map(|time| time.unix_time()),
+            roomid: log.roomid.as_deref().map(String::from),
+            format: log.format_id().to_string(),
+            elo: rating.elo,
+            gxe: rating.gxe,
+            opponent: log.player(side.opponent()).to_string(),
+            result,
+        });
+        Ok(())
+    }
+
+    fn merge_results(&self, mut a: Vec<RatingPoint>, mut b: Vec<RatingPoint>) -> Vec<RatingPoint> {
+        a.append(&mut b);
+        a
+    }
+
+    fn handle_results(&mut self, mut points: Vec<RatingPoint>) -> Result<(), BattleToolsError> {
+        Self::sort(&mut points);
+        self.write_points(&mut BufWriter::new(io::stdout().lock()), &points)?;
+        if let Some(svg_path) = &self.svg_path {
+            fs::write(svg_path, rating_chart(&points))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn history(username: &str) -> Vec<RatingPoint> {
+        let parser = RatingHistory::new(username, HistoryFormat::Csv, None);
+        let mut points = vec![];
+        for format in ["gen9randombattle", "gen8doublesou"] {
+            let path = format!("src/test-logs/{}.log.json", format);
+            let json = std::fs::read_to_string(&path).unwrap();
+            parser
+                .handle_log_file(json, Path::new(&path), &mut points)
+                .unwrap();
+        }
+        RatingHistory::sort(&mut points);
+        points
+    }
+
+    #[test]
+    fn points() {
+        let points = history("Annika");
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            points[0],
+            RatingPoint {
+                timestamp: String::from("2021-01-31T19:02:30-05:00"),
+                unix_time: Some(1612137750),
+                roomid: Some(String::from("battle-gen8doublesou-1260000001")),
+                format: String::from("gen8doublesou"),
+                elo: Some(1303.0),
+                gxe: Some(55.2),
+                opponent: String::from("Rust Haters"),
+                result: "win",
+            }
+        );
+        assert_eq!(points[1].format, "gen9randombattle");
+        assert_eq!(points[1].result, "loss");
+
+        assert!(history("someone else").is_empty());
+    }
+
+    #[test]
+    fn output() {
+        let points = history("Rust Haters");
+        let mut parser = RatingHistory::new("Rust Haters", HistoryFormat::Csv, None);
+        let mut out = vec![];
+        parser.write_points(&mut out, &points).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp,roomid,format,elo,gxe,opponent,result\n\
+             2021-01-31T19:02:30-05:00,battle-gen8doublesou-1260000001,gen8doublesou,1349.9,57.9,Annika,loss\n\
+             2023-10-01T00:09:40-04:00,battle-gen9randombattle-1960000001,gen9randombattle,1487.05,51,Annika,win\n"
+        );
+
+        // timestamps that couldn't be parsed are written as they were, so they may need quoting
+        let unparsed = RatingPoint {
+            timestamp: String::from(r#"Sun, 31 Jan "2021""#),
+            ..points[0].clone()
+        };
+        assert!(unparsed
+            .to_csv()
+            .starts_with(r#""Sun, 31 Jan ""2021""",battle-gen8doublesou-1260000001,"#));
+
+        parser.output_format = HistoryFormat::Json;
+        let mut out = vec![];
+        parser.write_points(&mut out, &points[..1]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"timestamp":"2021-01-31T19:02:30-05:00","roomid":"battle-gen8doublesou-1260000001","format":"gen8doublesou","elo":1349.9,"gxe":57.9,"opponent":"Annika","result":"loss"}"#
+                .to_owned()
+                + "\n"
+        );
+    }
+
+    #[test]
+    fn chart() {
+        let svg = rating_chart(&history("Annika"));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        // one line per format
+        assert_eq!(svg.matches(r#"stroke-width="2""#).count(), 2);
+        assert!(svg.contains(">gen8doublesou</text>"));
+        assert!(svg.contains(
+            "<title>2021-01-31T19:02:30-05:00: 1303 Elo before a win against Rust Haters</title>"
+        ));
+
+        assert!(rating_chart(&[]).contains("No rated battles"));
+    }
+}
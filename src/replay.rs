@@ -0,0 +1,769 @@
+// Code for reconstructing the state of a battle, turn by turn, from its `log`.
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    battle_log::{BattleLog, Side},
+    protocol::{Details, Event, Hp, Line, PokemonId, PublicLines, SwitchKind, Tag},
+};
+
+/// A Pokémon, as far as spectators can tell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PokemonState<'a> {
+    /// The Pokémon's nickname
+    pub name: &'a str,
+    pub details: Details<'a>,
+    /// Out of `max_hp`, which is usually 100
+    pub hp: u32,
+    pub max_hp: u32,
+    /// A non-volatile status condition (`brn`, `par`, `slp`, `frz`, `psn`, or `tox`)
+    pub status: Option<&'a str>,
+    pub fainted: bool,
+    /// Stat stages; these are reset when the Pokémon switches out
+    pub boosts: BTreeMap<&'a str, i8>,
+    /// Volatile conditions (like Substitute or confusion); these end when the Pokémon switches out
+    pub volatiles: BTreeSet<&'a str>,
+    /// The moves the Pokémon has been seen to use, in the order they were first used
+    pub moves: Vec<&'a str>,
+    /// The item the Pokémon is known to be holding; `None` if it's unknown or the item was used up or removed
+    pub item: Option<&'a str>,
+    pub ability: Option<&'a str>,
+    /// The type the Pokémon terastallized into
+    pub terastallized: Option<&'a str>,
+}
+
+impl<'a> PokemonState<'a> {
+    fn new(name: &'a str, details: Details<'a>) -> Self {
+        Self {
+            name,
+            details,
+            hp: 100,
+            max_hp: 100,
+            status: None,
+            fainted: false,
+            boosts: BTreeMap::new(),
+            volatiles: BTreeSet::new(),
+            moves: vec![],
+            item: None,
+            ability: None,
+            terastallized: None,
+        }
+    }
+
+    pub fn hp_percent(&self) -> f64 {
+        if self.max_hp == 0 {
+            0.0
+        } else {
+            f64::from(self.hp) * 100.0 / f64::from(self.max_hp)
+        }
+    }
+
+    fn set_hp(&mut self, hp: &Hp<'a>) {
+        self.hp = hp.current;
+        if let Some(max) = hp.max {
+            self.max_hp = max;
+        }
+        // HP is always sent along with the current status
+        match hp.status {
+            Some("fnt") => self.fainted = true,
+            status => self.status = status,
+        }
+    }
+
+    fn boost(&mut self, stat: &'a str, amount: i8) {
+        let stage = self.boosts.entry(stat).or_insert(0);
+        *stage = (*stage + amount).clamp(-6, 6);
+        if *stage == 0 {
+            self.boosts.remove(stat);
+        }
+    }
+
+    /// Ends everything that doesn't persist when a Pokémon switches out
+    fn switch_out(&mut self) {
+        self.boosts.clear();
+        self.volatiles.clear();
+    }
+}
+
+/// One player's side of the battle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SideState<'a> {
+    pub player: &'a str,
+    /// Every Pokémon that has been seen, in the order they first appeared
+    pub pokemon: Vec<PokemonState<'a>>,
+    /// The index (in `pokemon`) of the Pokémon in each active slot
+    pub active: Vec<Option<usize>>,
+    /// Side conditions (like Stealth Rock or Tailwind), with how many layers of each have been set up
+    pub conditions: BTreeMap<&'a str, u8>,
+    /// What the Pokémon in each active slot was like before it switched in (`None` if it hadn't been seen),
+    /// so a switch-in that turns out to be an Illusion can be undone
+    #[serde(skip)]
+    before_switch_in: Vec<Option<PokemonState<'a>>>,
+}
+
+impl<'a> SideState<'a> {
+    fn new(player: &'a str) -> Self {
+        Self {
+            player,
+            pokemon: vec![],
+            active: vec![],
+            conditions: BTreeMap::new(),
+            before_switch_in: vec![],
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut PokemonState<'a>> {
+        self.pokemon.iter_mut().find(|pokemon| pokemon.name == name)
+    }
+
+    /// Puts a Pokémon into an active slot, returning its index in `pokemon`
+    fn switch_in(&mut self, slot: usize, name: &'a str, details: Details<'a>) -> usize {
+        if self.active.len() <= slot {
+            self.active.resize(slot + 1, None);
+            self.before_switch_in.resize(slot + 1, None);
+        }
+        if let Some(previous) = self.active[slot] {
+            self.pokemon[previous].switch_out();
+        }
+
+        let index = match self.pokemon.iter().position(|pokemon| pokemon.name == name) {
+            Some(index) => {
+                self.before_switch_in[slot] = Some(self.pokemon[index].clone());
+                self.pokemon[index].details = details;
+                index
+            }
+            None => {
+                self.before_switch_in[slot] = None;
+                self.pokemon.push(PokemonState::new(name, details));
+                self.pokemon.len() - 1
+            }
+        };
+        self.active[slot] = Some(index);
+        index
+    }
+
+    /// Undoes the switch-in of the Pokémon in a slot, which was really another Pokémon with Illusion.
+    ///
+    /// Returns what happened to it while it was active (which really happened to the other Pokémon),
+    /// with only the moves it was first seen to use then.
+    fn break_illusion(&mut self, slot: usize) -> Option<PokemonState<'a>> {
+        let index = self.active.get_mut(slot)?.take()?;
+        match self.before_switch_in[slot].take() {
+            Some(before) => {
+                let mut disguised = std::mem::replace(&mut self.pokemon[index], before);
+                let known_moves = self.pokemon[index].moves.len();
+                disguised.moves.drain(..known_moves);
+                Some(disguised)
+            }
+            // It hadn't been seen before, so it was never really in the battle
+            None => {
+                let disguised = self.pokemon.remove(index);
+                for active in self.active.iter_mut().flatten() {
+                    if *active > index {
+                        *active -= 1;
+                    }
+                }
+                Some(disguised)
+            }
+        }
+    }
+}
+
+/// The conditions that affect the whole field
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FieldState<'a> {
+    pub weather: Option<&'a str>,
+    pub terrain: Option<&'a str>,
+    /// Other field conditions, like Trick Room or Gravity
+    pub conditions: BTreeSet<&'a str>,
+}
+
+/// The state of a battle at some point
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BattleState<'a> {
+    /// The current turn; 0 before the first turn starts
+    pub turn: u32,
+    /// P1's side, then P2's
+    pub sides: [SideState<'a>; 2],
+    pub field: FieldState<'a>,
+    pub ended: bool,
+    /// The winner's name, once the battle has ended (and if it wasn't a tie)
+    pub winner: Option<&'a str>,
+}
+
+impl<'a> BattleState<'a> {
+    /// The state of a battle before its log starts
+    pub fn new(log: &'a BattleLog<'a>) -> Self {
+        Self {
+            turn: 0,
+            sides: [SideState::new(&log.p1), SideState::new(&log.p2)],
+            field: FieldState::default(),
+            ended: false,
+            winner: None,
+        }
+    }
+
+    /// Finds a Pokémon that has been seen in the battle
+    pub fn pokemon(&self, id: &PokemonId) -> Option<&PokemonState<'a>> {
+        self.sides[id.side as usize]
+            .pokemon
+            .iter()
+            .find(|pokemon| pokemon.name == id.name)
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut SideState<'a> {
+        &mut self.sides[side as usize]
+    }
+
+    fn pokemon_mut(&mut self, id: &PokemonId<'a>) -> Option<&mut PokemonState<'a>> {
+        self.side_mut(id.side).get_mut(id.name)
+    }
+
+    /// Updates the state for one line of the log
+    pub fn apply(&mut self, line: &Line<'a>) {
+        match &line.event {
+            Event::Player {
+                side,
+                name: Some(name),
+                ..
+            } if !name.is_empty() => self.side_mut(*side).player = name,
+            Event::Turn(turn) => self.turn = *turn,
+            Event::Win(winner) => {
+                self.ended = true;
+                self.winner = Some(winner);
+            }
+            Event::Tie => self.ended = true,
+
+            Event::Switch {
+                kind,
+                pokemon,
+                details,
+                hp,
+            } => {
+                let slot = slot_index(pokemon);
+                let side = self.side_mut(pokemon.side);
+                // An Illusion was broken: everything that happened to the Pokémon it was disguised as
+                // really happened to this one
+                let disguised = match kind {
+                    SwitchKind::Replace => side.break_illusion(slot),
+                    _ => None,
+                };
+                let index = side.switch_in(slot, pokemon.name, *details);
+                if let Some(disguised) = disguised {
+                    let revealed = &mut side.pokemon[index];
+                    revealed.hp = disguised.hp;
+                    revealed.max_hp = disguised.max_hp;
+                    revealed.status = disguised.status;
+                    revealed.boosts = disguised.boosts;
+                    revealed.volatiles = disguised.volatiles;
+                    for name in disguised.moves {
+                        if !revealed.moves.contains(&name) {
+                            revealed.moves.push(name);
+                        }
+                    }
+                }
+                if let Some(hp) = hp {
+                    side.pokemon[index].set_hp(hp);
+                }
+            }
+            Event::DetailsChange {
+                pokemon,
+                details,
+                hp,
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.details = *details;
+                    if let Some(hp) = hp {
+                        state.set_hp(hp);
+                    }
+                }
+            }
+            Event::FormeChange {
+                pokemon,
+                species,
+                hp,
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.details.species = species;
+                    if let Some(hp) = hp {
+                        state.set_hp(hp);
+                    }
+                }
+            }
+            Event::Move { pokemon, name, .. } => {
+                // Moves called by other moves (like Sleep Talk) aren't the user's own
+                let called = line
+                    .tags
+                    .iter()
+                    .any(|tag| matches!(tag, Tag::From(effect) if *effect != "lockedmove"));
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    if !called && !state.moves.contains(name) {
+                        state.moves.push(name);
+                    }
+                }
+            }
+            Event::Faint(pokemon) => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.fainted = true;
+                    state.hp = 0;
+                    state.status = None;
+                    state.switch_out();
+                }
+            }
+            Event::Damage { pokemon, hp }
+            | Event::Heal { pokemon, hp }
+            | Event::SetHp { pokemon, hp } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.set_hp(hp);
+                }
+            }
+            Event::Status { pokemon, status } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.status = Some(status);
+                }
+            }
+            Event::CureStatus { pokemon, .. } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.status = None;
+                }
+            }
+            Event::Boost {
+                pokemon,
+                stat,
+                amount,
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.boost(stat, *amount as i8);
+                }
+            }
+            Event::Unboost {
+                pokemon,
+                stat,
+                amount,
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.boost(stat, -(*amount as i8));
+                }
+            }
+            Event::SetBoost {
+                pokemon,
+                stat,
+                amount,
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.boosts.remove(stat);
+                    state.boost(stat, *amount);
+                }
+            }
+            Event::ClearBoost(pokemon) => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.boosts.clear();
+                }
+            }
+            Event::ClearNegativeBoost(pokemon) => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.boosts.retain(|_, stage| *stage > 0);
+                }
+            }
+            Event::ClearAllBoost => {
+                for side in &mut self.sides {
+                    for pokemon in &mut side.pokemon {
+                        pokemon.boosts.clear();
+                    }
+                }
+            }
+            Event::Weather(weather) => {
+                self.field.weather = if *weather == "none" {
+                    None
+                } else {
+                    Some(weather)
+                };
+            }
+            Event::FieldStart(condition) => {
+                let condition = effect_name(condition);
+                if condition.ends_with(" Terrain") {
+                    self.field.terrain = Some(condition);
+                } else {
+                    self.field.conditions.insert(condition);
+                }
+            }
+            Event::FieldEnd(condition) => {
+                let condition = effect_name(condition);
+                if self.field.terrain == Some(condition) {
+                    self.field.terrain = None;
+                }
+                self.field.conditions.remove(condition);
+            }
+            Event::SideStart { side, condition } => {
+                *self
+                    .side_mut(side.side)
+                    .conditions
+                    .entry(effect_name(condition))
+                    .or_insert(0) += 1;
+            }
+            Event::SideEnd { side, condition } => {
+                self.side_mut(side.side)
+                    .conditions
+                    .remove(effect_name(condition));
+            }
+            Event::VolatileStart {
+                pokemon, effect, ..
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.volatiles.insert(effect_name(effect));
+                }
+            }
+            Event::VolatileEnd {
+                pokemon, effect, ..
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.volatiles.remove(effect_name(effect));
+                }
+            }
+            Event::Item { pokemon, item } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.item = Some(item);
+                }
+            }
+            Event::EndItem { pokemon, .. } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.item = None;
+                }
+            }
+            Event::Ability {
+                pokemon, ability, ..
+            } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.ability = Some(ability);
+                }
+            }
+            Event::Transform { pokemon, .. } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.volatiles.insert("Transform");
+                }
+            }
+            Event::Terastallize { pokemon, tera_type } => {
+                if let Some(state) = self.pokemon_mut(pokemon) {
+                    state.terastallized = Some(tera_type);
+                    state.details.tera_type = Some(tera_type);
+                }
+            }
+            _ => {}
+        }
+
+        self.reveal_from_tags(line);
+    }
+
+    /// Records items and abilities revealed by `[from] item: ...` and `[from] ability: ...` tags.
+    ///
+    /// The item or ability belongs to the `[of]` Pokémon if there is one (as with Rocky Helmet or Rough Skin),
+    /// and otherwise to the Pokémon the line is about (as with Leftovers).
+    fn reveal_from_tags(&mut self, line: &Line<'a>) {
+        let Some(effect) = line.tags.iter().find_map(|tag| match tag {
+            Tag::From(effect) => Some(*effect),
+            _ => None,
+        }) else {
+            return;
+        };
+        let owner = line
+            .tags
+            .iter()
+            .find_map(|tag| match tag {
+                Tag::Of(pokemon) => Some(*pokemon),
+                _ => None,
+            })
+            .or_else(|| main_pokemon(&line.event));
+        let Some(state) = owner.and_then(|owner| self.pokemon_mut(&owner)) else {
+            return;
+        };
+
+        if let Some(item) = effect.strip_prefix("item: ") {
+            // Items like Life Orb are still held after they activate; ones like Air Balloon are used up
+            if !matches!(line.event, Event::EndItem { .. }) {
+                state.item = Some(item);
+            }
+        } else if let Some(ability) = effect.strip_prefix("ability: ") {
+            state.ability = Some(ability);
+        }
+    }
+}
+
+/// The index of a Pokémon's active slot (`a` is 0)
+fn slot_index(pokemon: &PokemonId) -> usize {
+    pokemon
+        .position
+        .map_or(0, |position| (position as u8).saturating_sub(b'a') as usize)
+}
+
+/// Removes the `move: ` prefix that some effects have (like `move: Stealth Rock`)
+fn effect_name(effect: &str) -> &str {
+    effect.strip_prefix("move: ").unwrap_or(effect)
+}
+
+/// The Pokémon that a line is mainly about
+fn main_pokemon<'a>(event: &Event<'a>) -> Option<PokemonId<'a>> {
+    match event {
+        Event::Switch { pokemon, .. }
+        | Event::DetailsChange { pokemon, .. }
+        | Event::FormeChange { pokemon, .. }
+        | Event::Move { pokemon, .. }
+        | Event::Damage { pokemon, .. }
+        | Event::Heal { pokemon, .. }
+        | Event::SetHp { pokemon, .. }
+        | Event::Status { pokemon, .. }
+        | Event::CureStatus { pokemon, .. }
+        | Event::Boost { pokemon, .. }
+        | Event::Unboost { pokemon, .. }
+        | Event::VolatileStart { pokemon, .. }
+        | Event::VolatileEnd { pokemon, .. }
+        | Event::Item { pokemon, .. }
+        | Event::EndItem { pokemon, .. }
+        | Event::Ability { pokemon, .. }
+        | Event::Activate { pokemon, .. } => Some(*pokemon),
+        _ => None,
+    }
+}
+
+/// Replays a battle's `log`, giving a snapshot of the battle's state at the start of each turn, and then one
+/// of its final state.
+pub struct Replay<'a> {
+    lines: PublicLines<'a, 'a>,
+    state: BattleState<'a>,
+    finished: bool,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(log: &'a BattleLog<'a>) -> Self {
+        Self {
+            lines: PublicLines::new(&log.log),
+            state: BattleState::new(log),
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = BattleState<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            let line = Line::parse(line);
+            self.state.apply(&line);
+            if let Event::Turn(_) = line.event {
+                return Some(self.state.clone());
+            }
+        }
+        if self.finished {
+            None
+        } else {
+            self.finished = true;
+            Some(self.state.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn side<'s, 'a>(state: &'s BattleState<'a>, side: Side) -> &'s SideState<'a> {
+        &state.sides[side as usize]
+    }
+
+    fn get<'s, 'a>(side: &'s SideState<'a>, name: &str) -> &'s PokemonState<'a> {
+        side.pokemon
+            .iter()
+            .find(|pokemon| pokemon.name == name)
+            .unwrap()
+    }
+
+    fn active<'a>(side: &SideState<'a>) -> Vec<&'a str> {
+        side.active
+            .iter()
+            .flatten()
+            .map(|&index| side.pokemon[index].name)
+            .collect()
+    }
+
+    #[test]
+    fn singles() {
+        let json = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let states: Vec<_> = Replay::new(&log).collect();
+        // one snapshot per turn, plus the final state
+        assert_eq!(
+            states.iter().map(|state| state.turn).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6, 6]
+        );
+
+        let turn_1 = &states[0];
+        assert_eq!(turn_1.field.weather, Some("RainDance"));
+        let pelipper = get(side(turn_1, Side::P1), "Pelipper");
+        assert_eq!(pelipper.ability, Some("Drizzle"));
+        assert_eq!(pelipper.details.level, Some(86));
+
+        let turn_2 = &states[1];
+        let p1 = side(turn_2, Side::P1);
+        let p2 = side(turn_2, Side::P2);
+        assert_eq!(p1.player, "Annika");
+        assert_eq!(active(p1), vec!["Kingambit"]);
+        assert_eq!(get(p1, "Kingambit").hp, 94);
+        assert_eq!(get(p1, "Pelipper").hp, 88);
+        // only the public halves of |split| blocks are read
+        assert_eq!(get(p1, "Pelipper").max_hp, 100);
+        assert_eq!(get(p1, "Pelipper").moves, vec!["U-turn"]);
+        assert_eq!(p1.conditions.get("Stealth Rock"), Some(&1));
+        assert_eq!(get(p2, "Garchomp").ability, Some("Rough Skin"));
+        assert_eq!(get(p2, "Garchomp").moves, vec!["Stealth Rock"]);
+
+        let turn_3 = &states[2];
+        let kingambit = get(side(turn_3, Side::P1), "Kingambit");
+        assert_eq!(kingambit.boosts.get("atk"), Some(&2));
+        assert_eq!((kingambit.hp, kingambit.max_hp), (31, 100));
+        assert_eq!(
+            get(side(turn_3, Side::P2), "Garchomp").item,
+            Some("Life Orb")
+        );
+
+        let turn_4 = &states[3];
+        let kingambit = get(side(turn_4, Side::P1), "Kingambit");
+        assert_eq!(kingambit.terastallized, Some("Dark"));
+        assert_eq!(kingambit.hp, 2);
+        assert!(get(side(turn_4, Side::P2), "Garchomp").fainted);
+        assert_eq!(active(side(turn_4, Side::P2)), vec!["Iron Valiant"]);
+
+        let turn_5 = &states[4];
+        let kingambit = get(side(turn_5, Side::P1), "Kingambit");
+        assert_eq!(kingambit.status, Some("par"));
+        // boosts last until the Pokémon switches out
+        assert_eq!(kingambit.boosts.get("atk"), Some(&2));
+        assert_eq!(turn_5.field.weather, Some("RainDance"));
+
+        let last = states.last().unwrap();
+        assert!(last.ended);
+        assert_eq!(last.winner, Some("Rust Haters"));
+        let kingambit = get(side(last, Side::P1), "Kingambit");
+        assert!(kingambit.fainted);
+        assert!(kingambit.boosts.is_empty());
+        assert_eq!(get(side(last, Side::P1), "Pelipper").hp, 88);
+        assert_eq!(last.field.weather, Some("RainDance"));
+    }
+
+    #[test]
+    fn doubles() {
+        let json = std::fs::read_to_string("src/test-logs/gen8doublesou.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let states: Vec<_> = Replay::new(&log).collect();
+        assert_eq!(
+            states.iter().map(|state| state.turn).collect::<Vec<_>>(),
+            vec![1, 2, 3, 3]
+        );
+
+        let turn_1 = &states[0];
+        assert_eq!(active(side(turn_1, Side::P1)), vec!["Incineroar", "Koko"]);
+        assert_eq!(
+            active(side(turn_1, Side::P2)),
+            vec!["Dusclops", "Conkeldurr"]
+        );
+        assert_eq!(turn_1.field.terrain, Some("Electric Terrain"));
+        let koko = get(side(turn_1, Side::P1), "Koko");
+        assert_eq!(koko.details.species, "Tapu Koko");
+        assert_eq!(koko.ability, Some("Electric Surge"));
+        // Frisk reveals the item of the Pokémon in the line, not of the one with Frisk
+        assert_eq!(koko.item, Some("Life Orb"));
+        assert_eq!(get(side(turn_1, Side::P2), "Dusclops").item, None);
+        for name in ["Dusclops", "Conkeldurr"] {
+            assert_eq!(
+                get(side(turn_1, Side::P2), name).boosts.get("atk"),
+                Some(&-1)
+            );
+        }
+
+        let turn_2 = &states[1];
+        let conkeldurr = get(side(turn_2, Side::P2), "Conkeldurr");
+        assert_eq!(conkeldurr.status, Some("brn"));
+        assert_eq!(conkeldurr.item, Some("Flame Orb"));
+        assert_eq!(conkeldurr.hp, 45);
+        assert_eq!(
+            get(side(turn_2, Side::P1), "Koko").moves,
+            vec!["Dazzling Gleam"]
+        );
+
+        let turn_3 = &states[2];
+        assert_eq!(active(side(turn_3, Side::P1)), vec!["Rillaboom", "Koko"]);
+        assert!(get(side(turn_3, Side::P1), "Incineroar").fainted);
+        assert_eq!(side(turn_3, Side::P1).conditions.get("Tailwind"), Some(&1));
+        assert!(turn_3.field.conditions.contains("Trick Room"));
+        assert_eq!(turn_3.field.terrain, Some("Grassy Terrain"));
+        let conkeldurr = get(side(turn_3, Side::P2), "Conkeldurr");
+        assert_eq!(conkeldurr.hp, 39);
+        assert_eq!(conkeldurr.status, Some("brn"));
+        assert_eq!(
+            conkeldurr.boosts,
+            [("atk", -1), ("def", -1), ("spd", -1)]
+                .into_iter()
+                .collect()
+        );
+
+        let last = states.last().unwrap();
+        assert_eq!(last.winner, Some("Annika"));
+        assert!(get(side(last, Side::P2), "Conkeldurr").fainted);
+        assert_eq!(get(side(last, Side::P2), "Dusclops").hp, 25);
+    }
+
+    #[test]
+    fn illusion() {
+        let json = std::fs::read_to_string("src/benchmark-data.json").unwrap();
+        let mut log = BattleLog::parse(&json).unwrap();
+        let disguised_turn = [
+            "|switch|p2a: Drednaw|Drednaw, L84, F|100/100",
+            "|move|p2a: Drednaw|Swords Dance|p2a: Drednaw",
+            "|-boost|p2a: Drednaw|atk|2",
+            "|-damage|p2a: Drednaw|60/100",
+            "|-status|p2a: Drednaw|par",
+            "|replace|p2a: Zoroark|Zoroark, L84, M",
+            "|-end|p2a: Zoroark|Illusion",
+            "|turn|3",
+        ];
+
+        // Zoroark disguised as a Drednaw that has already battled
+        log.log = [
+            "|switch|p1a: Rotom|Rotom-Fan, L84|100/100",
+            "|switch|p2a: Drednaw|Drednaw, L84, F|100/100",
+            "|turn|1",
+            "|move|p2a: Drednaw|Liquidation|p1a: Rotom",
+            "|-damage|p2a: Drednaw|90/100",
+            "|switch|p2a: Garchomp|Garchomp, L80, F|100/100",
+            "|turn|2",
+        ]
+        .into_iter()
+        .chain(disguised_turn)
+        .map(Cow::Borrowed)
+        .collect();
+        let last = Replay::new(&log).last().unwrap();
+        let p2 = side(&last, Side::P2);
+        assert_eq!(active(p2), vec!["Zoroark"]);
+        let zoroark = get(p2, "Zoroark");
+        assert_eq!(zoroark.boosts.get("atk"), Some(&2));
+        assert_eq!((zoroark.hp, zoroark.status), (60, Some("par")));
+        assert_eq!(zoroark.moves, vec!["Swords Dance"]);
+        // the real Drednaw is as it was before the Zoroark switched in
+        let drednaw = get(p2, "Drednaw");
+        assert!(drednaw.boosts.is_empty());
+        assert_eq!((drednaw.hp, drednaw.status), (90, None));
+        assert_eq!(drednaw.moves, vec!["Liquidation"]);
+
+        // a disguise that hasn't been seen before never really battled
+        log.log = ["|switch|p1a: Rotom|Rotom-Fan, L84|100/100", "|turn|2"]
+            .into_iter()
+            .chain(disguised_turn)
+            .map(Cow::Borrowed)
+            .collect();
+        let last = Replay::new(&log).last().unwrap();
+        let p2 = side(&last, Side::P2);
+        assert_eq!(active(p2), vec!["Zoroark"]);
+        assert_eq!(get(p2, "Zoroark").hp, 60);
+        assert!(p2.pokemon.iter().all(|pokemon| pokemon.name != "Drednaw"));
+    }
+}
@@ -0,0 +1,92 @@
+// Code for exporting a battle as a standalone replay page, like the ones PS's replay server produces.
+use crate::{battle_log::BattleLog, id::escape};
+
+/// Builds a replay page for the battle.
+///
+/// The page embeds the battle's `log` in a `battle-log-data` script block, which PS's `replay-embed.js`
+/// (loaded from play.pokemonshowdown.com when the page is opened) turns into a playable replay.
+pub fn replay_html(log: &BattleLog) -> String {
+    // anonymized logs don't have a room ID
+    let replay_id = match &log.roomid {
+        Some(roomid) => roomid.strip_prefix("battle-").unwrap_or(roomid),
+        None => log.format_id(),
+    };
+
+    let format_name = escape(log.format_name());
+    let p1 = escape(&log.p1);
+    let p2 = escape(&log.p2);
+    // PS escapes every `/` in the log, so that it can't contain `</script>`
+    let log_data = log.log.join("\n").replace('/', "\\/");
+
+    format!(
+        r#"<!DOCTYPE html>
+<meta charset="utf-8" />
+<!-- version 1 -->
+<title>{format_name}: {p1} vs. {p2} - Replays - Pokémon Showdown!</title>
+<style>
+html,body {{font-family:Verdana, sans-serif;font-size:10pt;margin:0;padding:0;}}body{{padding:12px 0;}} .battle-log {{font-family:Verdana, sans-serif;font-size:10pt;}} .battle-log-inline {{border:1px solid #AAAAAA;background:#EEF2F5;color:black;max-width:640px;margin:0 auto 80px;padding-bottom:5px;}} .battle-log .inner {{padding:4px 8px 0px 8px;}} .battle-log h2 {{margin:0.5em -8px;padding:4px 8px;border:1px solid #AAAAAA;background:#E0E7EA;border-left:0;border-right:0;font-family:Verdana, sans-serif;font-size:13pt;}} .subtle {{color:#3A4A66;}}
+</style>
+<div class="wrapper replay-wrapper" style="max-width:1180px;margin:0 auto">
+<input type="hidden" name="replayid" value="{replay_id}" />
+<div class="battle"></div><div class="battle-log"></div><div class="replay-controls"></div><div class="replay-controls-2"></div>
+<h1 style="font-weight:normal;text-align:center"><strong>{format_name}</strong><br />{p1} vs. {p2}</h1>
+<script type="text/plain" class="battle-log-data">{log_data}</script>
+</div>
+<script>
+let daily = Math.floor(Date.now()/1000/60/60/24);document.write('<script src="https://play.pokemonshowdown.com/js/replay-embed.js?version'+daily+'"></'+'script>');
+</script>
+"#,
+        replay_id = escape(replay_id),
+    )
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::anonymize::Anonymizer;
+    use std::borrow::Cow;
+
+    #[test]
+    fn replay_page() {
+        let json = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let html = replay_html(&log);
+
+        assert!(html.contains(r#"<input type="hidden" name="replayid" value="gen9randombattle-"#));
+        assert!(html
+            .contains("<strong>[Gen 9] Random Battle</strong><br />Annika vs. Rust Haters</h1>"));
+        assert!(html.contains(&format!(
+            r#"<script type="text/plain" class="battle-log-data">{}</script>"#,
+            log.log.join("\n").replace('/', "\\/")
+        )));
+        assert!(html.contains("replay-embed.js"));
+    }
+
+    #[test]
+    fn escaping() {
+        let json = std::fs::read_to_string("src/benchmark-data.json").unwrap();
+        let mut log = BattleLog::parse(&json).unwrap();
+        log.p1 = Cow::Borrowed("<b>Annika</b>");
+        log.log
+            .push(Cow::Borrowed("|c|Annika|</script><script>alert(1)"));
+        let html = replay_html(&log);
+
+        assert!(html.contains("&lt;b&gt;Annika&lt;/b&gt; vs. Rust Haters"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains(r#"|c|Annika|<\/script><script>alert(1)"#));
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+
+    #[test]
+    fn anonymized() {
+        let json = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let (anonymized, _, _) = Anonymizer::new(true, false).anonymize(&json).unwrap();
+        let log = BattleLog::parse(&anonymized).unwrap();
+        let html = replay_html(&log);
+
+        assert!(html.contains(r#"name="replayid" value="gen9randombattle""#));
+        assert!(html.contains("<br />1 vs. 2</h1>"));
+        assert!(html.contains("|player|p1|1|"));
+        assert!(!html.contains("Annika"));
+    }
+}
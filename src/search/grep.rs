@@ -0,0 +1,292 @@
+// Code for searching the text of battle logs.
+use super::{log_date, log_roomid};
+use crate::{
+    battle_log::{room_number, BattleLog},
+    directory::LogParser,
+    input_log::Choice,
+    protocol::{Event, Line},
+    BattleToolsError,
+};
+use regex::Regex;
+use regex_syntax::hir::literal::Literals;
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Which lines of each battle are searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrepScope {
+    /// Every line of the battle log
+    Log,
+    /// Only chat lines (`|c|` and `|c:|`) in the battle log
+    Chat,
+    /// The lines of the `inputLog`
+    InputLog,
+}
+
+/// A line that matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrepMatch {
+    pub date: String,
+    pub roomid: String,
+    /// Where the line is in the battle's log (or input log)
+    pub index: usize,
+    /// The turn the line is from; this is 0 for lines before the first turn, and `None` for the input log
+    pub turn: Option<u32>,
+    /// The user who said the line (for chat), or the player whose choice it was (for the input log)
+    pub speaker: Option<String>,
+    pub line: String,
+}
+
+impl GrepMatch {
+    fn to_human_readable(&self) -> String {
+        let room = self.roomid.strip_prefix("battle-").unwrap_or(&self.roomid);
+        let turn = match self.turn {
+            Some(turn) => format!("turn {}", turn),
+            None => String::from("input"),
+        };
+        match &self.speaker {
+            Some(speaker) => format!(
+                "({}) <<{}>> [{}] {}: {}",
+                self.date, room, turn, speaker, self.line
+            ),
+            None => format!("({}) <<{}>> [{}] {}", self.date, room, turn, self.line),
+        }
+    }
+}
+
+/// Builds a regex that finds the literals every match of `pattern` must start with,
+/// so that logs without them can be skipped before they're parsed.
+///
+/// The prefilter is run on the raw JSON, so there's no prefilter if the pattern doesn't start with
+/// a small set of literals or if they contain characters that would be escaped in JSON.
+fn prefilter(pattern: &str) -> Option<Regex> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    let literals = Literals::prefixes(&hir);
+    if literals.is_empty() || literals.contains_empty() {
+        return None;
+    }
+
+    let alternatives = literals
+        .literals()
+        .iter()
+        .map(|literal| {
+            // literals that were cut short might end partway through a character
+            let literal = std::str::from_utf8(literal).ok()?;
+            if literal.contains(|c: char| c == '"' || c == '\\' || c.is_control()) {
+                return None;
+            }
+            Some(regex::escape(literal))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Regex::new(&alternatives.join("|")).ok()
+}
+
+/// Prints the lines of battle logs that match a regular expression
+pub struct LogGrepper {
+    regex: Regex,
+    prefilter: Option<Regex>,
+    scope: GrepScope,
+}
+
+impl LogGrepper {
+    pub fn new(pattern: &str, scope: GrepScope) -> Result<Self, BattleToolsError> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+            prefilter: prefilter(pattern),
+            scope,
+        })
+    }
+
+    /// Finds the matching lines in a log
+    fn find_matches(&self, log: &BattleLog, date: &str, roomid: &str) -> Vec<GrepMatch> {
+        let found = |index, turn, speaker: Option<&str>, line: &str| GrepMatch {
+            date: date.to_string(),
+            roomid: roomid.to_string(),
+            index,
+            turn,
+            speaker: speaker.map(String::from),
+            line: line.to_string(),
+        };
+
+        let mut matches = vec![];
+        if self.scope == GrepScope::InputLog {
+            for (index, line) in log.input_log.iter().enumerate() {
+                if self.regex.is_match(line) {
+                    let side = Choice::parse(line).ok().and_then(|choice| choice.side());
+                    let speaker = side.map(|side| log.player(side));
+                    matches.push(found(index, None, speaker, line));
+                }
+            }
+            return matches;
+        }
+
+        let mut turn = 0;
+        let mut lines = log.log.iter().enumerate();
+        while let Some((index, line)) = lines.next() {
+            // Lines with exact HP (after a `|split|` line) are followed by the same line as spectators saw it,
+            // which is the one that's searched.
+            if line.starts_with("|split|") {
+                lines.next();
+                continue;
+            }
+            // only lines that might be relevant are parsed
+            if line.starts_with("|turn|") {
+                if let Event::Turn(number) = Line::parse(line).event {
+                    turn = number;
+                }
+            }
+            if !self.regex.is_match(line) {
+                continue;
+            }
+
+            let speaker = match Line::parse(line).event {
+                // (without the user's rank symbol)
+                Event::Chat { user, .. } => {
+                    Some(user.trim_start_matches(|c: char| !c.is_alphanumeric()))
+                }
+                _ => None,
+            };
+            if self.scope == GrepScope::Chat && speaker.is_none() {
+                continue;
+            }
+            matches.push(found(index, Some(turn), speaker, line));
+        }
+        matches
+    }
+
+    /// Writes every match to `out`, sorted by date, room number, and then position in the battle
+    pub fn write_matches<W: Write>(
+        &self,
+        out: &mut W,
+        mut matches: Vec<GrepMatch>,
+    ) -> io::Result<()> {
+        matches.sort_unstable_by(|a, b| {
+            (&a.date, room_number(&a.roomid), &a.roomid, a.index).cmp(&(
+                &b.date,
+                room_number(&b.roomid),
+                &b.roomid,
+                b.index,
+            ))
+        });
+        for found in matches {
+            writeln!(out, "{}", found.to_human_readable())?;
+        }
+        out.flush()
+    }
+}
+
+impl LogParser<Vec<GrepMatch>> for LogGrepper {
+    fn new_accumulator(&self) -> Vec<GrepMatch> {
+        vec![]
+    }
+
+    fn handle_log_file(
+        &self,
+        raw_json: String,
+        path: &Path,
+        matches: &mut Vec<GrepMatch>,
+    ) -> Result<(), BattleToolsError> {
+        if let Some(prefilter) = &self.prefilter {
+            if !prefilter.is_match(&raw_json) {
+                return Ok(());
+            }
+        }
+
+        let log = BattleLog::parse(&raw_json)?;
+        let date = log_date(path)?;
+        let roomid = log_roomid(path, log.roomid.as_deref())?;
+        matches.append(&mut self.find_matches(&log, date, &roomid));
+        Ok(())
+    }
+
+    fn merge_results(&self, mut a: Vec<GrepMatch>, mut b: Vec<GrepMatch>) -> Vec<GrepMatch> {
+        a.append(&mut b);
+        a
+    }
+
+    fn handle_results(&mut self, matches: Vec<GrepMatch>) -> Result<(), BattleToolsError> {
+        self.write_matches(&mut BufWriter::new(io::stdout().lock()), matches)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn grep(format: &str, pattern: &str, scope: GrepScope) -> Vec<String> {
+        let json = std::fs::read_to_string(format!("src/test-logs/{}.log.json", format)).unwrap();
+        let grepper = LogGrepper::new(pattern, scope).unwrap();
+        let mut matches = vec![];
+        grepper
+            .handle_log_file(
+                json,
+                &Path::new("logs/2021-05-10").join(format!("{}.log.json", format)),
+                &mut matches,
+            )
+            .unwrap();
+        matches.iter().map(GrepMatch::to_human_readable).collect()
+    }
+
+    #[test]
+    fn prefilters() {
+        let literals = |pattern| prefilter(pattern).map(|regex| regex.as_str().to_string());
+        assert_eq!(literals("gg|good game"), Some(String::from("gg|good game")));
+        assert_eq!(literals(r"hello\.+world"), Some(String::from(r"hello\.")));
+        // every match might start anywhere
+        assert_eq!(literals(".*gg"), None);
+        assert_eq!(literals("a?"), None);
+        // quotes are escaped in the raw JSON
+        assert_eq!(literals("\"hi\""), None);
+        assert!(literals("(?i)gg").is_some());
+    }
+
+    #[test]
+    fn log_lines() {
+        let matches = grep("gen8doublesou", r"^\|move\|p2a", GrepScope::Log);
+        assert!(!matches.is_empty());
+        assert!(matches
+            .iter()
+            .all(|line| line.starts_with("(2021-05-10) <<gen8doublesou-1260000001>> [turn ")));
+
+        assert_eq!(
+            matches[0],
+            "(2021-05-10) <<gen8doublesou-1260000001>> [turn 2] |move|p2a: Dusclops|Trick Room|p2a: Dusclops"
+        );
+        assert!(grep(
+            "gen8doublesou",
+            "this text isn't in the log",
+            GrepScope::Log
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn chat() {
+        let chat = grep("gen9randombattle", "(?i)GL HF", GrepScope::Chat);
+        assert_eq!(
+            chat,
+            vec!["(2021-05-10) <<gen9randombattle-1960000001>> [turn 1] Annika: |c|☆Annika|gl hf"]
+        );
+        assert!(grep("gen9randombattle", r"\|player\|", GrepScope::Chat).is_empty());
+        assert!(!grep("gen9randombattle", r"\|player\|", GrepScope::Log).is_empty());
+    }
+
+    #[test]
+    fn input_log() {
+        let matches = grep(
+            "gen8doublesou",
+            "^>(p1 switch|forcelose)",
+            GrepScope::InputLog,
+        );
+        assert_eq!(
+            matches,
+            vec![
+                "(2021-05-10) <<gen8doublesou-1260000001>> [input] Annika: >p1 switch 3",
+                "(2021-05-10) <<gen8doublesou-1260000001>> [input] Rust Haters: >forcelose p2",
+            ]
+        );
+    }
+}
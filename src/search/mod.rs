@@ -1,77 +1,243 @@
 // Code for searching battle logs.
+mod grep;
+mod summary;
+mod team;
 
 use crate::directory::LogParser;
+pub use grep::{GrepScope, LogGrepper};
+use summary::Summary;
+pub use team::TeamFilter;
 
 // Adapted from https://github.com/AnnikaCodes/battlesearch/blob/main/src/search.rs
-use crate::{id::to_id, BattleToolsError};
-use std::path::Path;
+use crate::{
+    battle_log::{room_number, BattleHeader, BattleLog, Side},
+    csv::csv_field,
+    id::to_id,
+    BattleToolsError,
+};
+use serde_derive::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Once,
+    },
+};
 
-pub struct BattleSearcher {
-    pub user_id: String,
-    pub wins_only: bool,
-    pub forfeits_only: bool,
+/// How search results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Battles grouped by pairing, one line per battle
+    Human,
+    /// One JSON object per line
+    Json,
+    /// CSV with a header row
+    Csv,
+    /// Only the path of each log file
+    Paths,
+    /// Only the room ID of each battle
+    RoomIds,
 }
 
-impl BattleSearcher {
-    pub fn new(username: &str, wins_only: bool, forfeits_only: bool) -> Self {
-        Self {
-            user_id: to_id(username),
-            wins_only,
-            forfeits_only,
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "paths" => Ok(OutputFormat::Paths),
+            "roomids" => Ok(OutputFormat::RoomIds),
+            _ => Err(format!(
+                "unknown output format '{}' (expected human, json, csv, paths, or roomids)",
+                s
+            )),
         }
     }
 }
 
-impl LogParser<()> for BattleSearcher {
-    fn handle_log_file(&self, raw_json: String, path: &Path) -> Result<(), BattleToolsError> {
-        let date = match path.parent() {
-            Some(p) => p
-                .file_name()
-                .ok_or_else(|| {
-                    BattleToolsError::PathConversion(format!(
-                        "Can't get parent file name for {:?}",
-                        path
-                    ))
-                })?
-                .to_str()
-                .ok_or_else(|| {
-                    BattleToolsError::PathConversion(format!(
-                        "Can't stringify parent for {:?}",
-                        path
-                    ))
-                })?,
-            None => "unknown date",
-        };
+/// The order search results are printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Oldest first, by date and then room number
+    Date,
+    /// Highest first, by the higher of the players' pre-battle Elo
+    Rating,
+    /// Longest first
+    Turns,
+}
 
-        // parse players
-        let p1id = to_id(gjson::get(&raw_json, "p1").str());
-        let p2id = to_id(gjson::get(&raw_json, "p2").str());
-        if p1id != self.user_id && p2id != self.user_id {
-            // Searched user is not a player in the battle.
-            return Ok(());
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date" => Ok(SortKey::Date),
+            "rating" => Ok(SortKey::Rating),
+            "turns" => Ok(SortKey::Turns),
+            _ => Err(format!(
+                "unknown sort key '{}' (expected date, rating, or turns)",
+                s
+            )),
         }
+    }
+}
 
-        // parse winner
-        let winner_id = to_id(gjson::get(&raw_json, "winner").str());
-        if self.wins_only && winner_id != self.user_id {
-            return Ok(());
+impl SortKey {
+    /// Compares two results; ties are broken by date and room number, so the order is always the same
+    fn compare(self, a: &SearchResult, b: &SearchResult) -> Ordering {
+        let by_date =
+            || (&a.date, a.room_number(), &a.path).cmp(&(&b.date, b.room_number(), &b.path));
+        match self {
+            SortKey::Date => by_date(),
+            // `None` sorts before any number, so reversing puts battles without ratings or turns last
+            SortKey::Rating => b
+                .max_rating()
+                .partial_cmp(&a.max_rating())
+                .unwrap_or(Ordering::Equal)
+                .then_with(by_date),
+            SortKey::Turns => b.turns.cmp(&a.turns).then_with(by_date),
         }
+    }
+}
 
-        // parse endType
-        let is_forfeit = gjson::get(&raw_json, "endType").str() == "forfeit";
-        if !is_forfeit && self.forfeits_only {
-            return Ok(());
+/// A battle that matched the search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub path: String,
+    /// The name of the directory the log is in, which is normally the day the battle was played
+    pub date: String,
+    /// The battle's room ID; for anonymized logs, which don't have one, this is the log's file name
+    pub roomid: String,
+    pub format: String,
+    pub p1: String,
+    pub p2: String,
+    /// The winner's name, or `None` for a tie
+    pub winner: Option<String>,
+    pub end_type: Option<String>,
+    pub turns: Option<u32>,
+    /// Player 1's Elo before the battle
+    pub p1rating: Option<f64>,
+    /// Player 2's Elo before the battle
+    pub p2rating: Option<f64>,
+    /// With `--include-renames`, the ID a searched user matched under if it wasn't their name in `p1`/`p2`
+    pub matched_name: Option<String>,
+    /// The pair of player IDs in the battle, with a searched user first
+    #[serde(skip)]
+    pub pairing: (String, String),
+    /// The sides searched users played on
+    #[serde(skip)]
+    pub searched_sides: Vec<Side>,
+    /// The species on the searched users' teams; these are only recorded for `--summary`
+    #[serde(skip)]
+    pub teams: [Vec<String>; 2],
+}
+
+const CSV_HEADER: &str =
+    "path,date,roomid,format,p1,p2,winner,end_type,turns,p1rating,p2rating,matched_name";
+
+impl SearchResult {
+    fn room_number(&self) -> Option<u64> {
+        room_number(&self.roomid)
+    }
+
+    fn max_rating(&self) -> Option<f64> {
+        match (self.p1rating, self.p2rating) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
         }
+    }
 
-        // formatting
-        let win_type_str = if is_forfeit { "by forfeit" } else { "normally" };
-        let win_str = if winner_id.is_empty() {
-            String::from("there was no winner")
-        } else {
-            format!("{} won {}", winner_id, win_type_str)
+    fn write<W: Write>(&self, out: &mut W, format: OutputFormat) -> io::Result<()> {
+        match format {
+            OutputFormat::Human => writeln!(out, "{}", self.to_human_readable()),
+            OutputFormat::Json => {
+                serde_json::to_writer(&mut *out, self)?;
+                writeln!(out)
+            }
+            OutputFormat::Csv => writeln!(out, "{}", self.to_csv()),
+            OutputFormat::Paths => writeln!(out, "{}", self.path),
+            OutputFormat::RoomIds => writeln!(out, "{}", self.roomid),
+        }
+    }
+
+    /// The one-line description used by `OutputFormat::Human`
+    fn to_human_readable(&self) -> String {
+        let win_str = match &self.winner {
+            Some(winner) => {
+                let win_type_str = if self.end_type.as_deref() == Some("forfeit") {
+                    "by forfeit"
+                } else {
+                    "normally"
+                };
+                format!("{} won {}", to_id(winner), win_type_str)
+            }
+            None => String::from("there was no winner"),
         };
+        let room = self.roomid.strip_prefix("battle-").unwrap_or(&self.roomid);
+        let line = format!(
+            "({}) <<{}>> {} vs. {} ({})",
+            self.date,
+            room,
+            to_id(&self.p1),
+            to_id(&self.p2),
+            win_str
+        );
+        match &self.matched_name {
+            Some(name) => format!("{} [as {}]", line, name),
+            None => line,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        [
+            csv_field(&self.path),
+            csv_field(&self.date),
+            csv_field(&self.roomid),
+            csv_field(&self.format),
+            csv_field(&self.p1),
+            csv_field(&self.p2),
+            optional(self.winner.as_deref().map(csv_field)),
+            optional(self.end_type.as_deref().map(csv_field)),
+            optional(self.turns.map(|turns| turns.to_string())),
+            optional(self.p1rating.map(|rating| rating.to_string())),
+            optional(self.p2rating.map(|rating| rating.to_string())),
+            optional(self.matched_name.as_deref().map(csv_field)),
+        ]
+        .join(",")
+    }
+}
 
-        let room = path
+/// The name of the directory a log is in, which is normally the day the battle was played
+fn log_date(path: &Path) -> Result<&str, BattleToolsError> {
+    Ok(match path.parent() {
+        Some(p) => p
+            .file_name()
+            .ok_or_else(|| {
+                BattleToolsError::PathConversion(format!(
+                    "Can't get parent file name for {:?}",
+                    path
+                ))
+            })?
+            .to_str()
+            .ok_or_else(|| {
+                BattleToolsError::PathConversion(format!("Can't stringify parent for {:?}", path))
+            })?,
+        None => "unknown date",
+    })
+}
+
+/// The battle's room ID, or for anonymized logs (which don't have one) the log's file name
+fn log_roomid(path: &Path, roomid: Option<&str>) -> Result<String, BattleToolsError> {
+    Ok(match roomid {
+        Some(roomid) => roomid.to_string(),
+        None => path
             .file_name()
             .ok_or_else(|| {
                 BattleToolsError::PathConversion(format!("Can't get file name of {:?}", path))
@@ -83,17 +249,413 @@ impl LogParser<()> for BattleSearcher {
                     path
                 ))
             })?
-            .replace(".log.json", "");
+            .replace(".log.json", ""),
+    })
+}
 
-        println!(
-            "({}) <<{}>> {} vs. {} ({})",
-            date, room, p1id, p2id, win_str
-        );
+/// Matching battles; they're only sorted when written
+pub type SearchResults = Vec<SearchResult>;
+
+pub struct BattleSearcher {
+    pub user_ids: Vec<String>,
+    /// If true, all of `user_ids` must be players in a battle for it to match; otherwise, any one of them is enough
+    pub require_all: bool,
+    /// If nonempty, a searched user's opponent must be one of these
+    pub opponent_ids: Vec<String>,
+    pub wins_only: bool,
+    pub losses_only: bool,
+    pub ties_only: bool,
+    pub forfeits_only: bool,
+    /// If true, users are also matched under the other names they had during the battle
+    pub include_renames: bool,
+    /// If nonempty, the battle's `endType` must be one of these
+    pub end_types: Vec<String>,
+    pub min_turns: Option<u32>,
+    pub max_turns: Option<u32>,
+    /// The lowest pre-battle Elo a searched user may have had
+    pub min_rating: Option<f64>,
+    /// The highest pre-battle Elo a searched user may have had
+    pub max_rating: Option<f64>,
+    pub rated_only: bool,
+    /// If nonempty, a team in the battle must have a Pokémon matching this
+    pub team_filter: TeamFilter,
+    /// If true, only the searched users' teams are checked against `team_filter`
+    pub user_team_only: bool,
+    pub output_format: OutputFormat,
+    pub sort: SortKey,
+    /// The maximum number of battles to print
+    pub limit: Option<usize>,
+    /// If true, a summary of the matching battles is printed instead of the battles themselves
+    pub summary: bool,
+    /// If true, battles are printed as soon as they're found (in no particular order) instead of being sorted
+    pub unordered: bool,
+    /// How many battles have been printed in unordered mode
+    printed: AtomicUsize,
+    header: Once,
+}
+
+impl BattleSearcher {
+    pub fn new<S: AsRef<str>>(usernames: &[S], wins_only: bool, forfeits_only: bool) -> Self {
+        Self {
+            user_ids: usernames.iter().map(|name| to_id(name.as_ref())).collect(),
+            require_all: false,
+            opponent_ids: vec![],
+            wins_only,
+            losses_only: false,
+            ties_only: false,
+            forfeits_only,
+            include_renames: false,
+            end_types: vec![],
+            min_turns: None,
+            max_turns: None,
+            min_rating: None,
+            max_rating: None,
+            rated_only: false,
+            team_filter: TeamFilter::default(),
+            user_team_only: false,
+            output_format: OutputFormat::Human,
+            sort: SortKey::Date,
+            limit: None,
+            summary: false,
+            unordered: false,
+            printed: AtomicUsize::new(0),
+            header: Once::new(),
+        }
+    }
+
+    /// Checks the filters that don't depend on who was searched for.
+    /// These only compare fields of the log, so they're checked first.
+    fn matches_battle(&self, log: &BattleHeader) -> bool {
+        if self.ties_only && !log.is_tie() {
+            return false;
+        }
+        if self.forfeits_only && !log.is_forfeit() {
+            return false;
+        }
+        if !self.end_types.is_empty()
+            && !log
+                .end_type
+                .as_deref()
+                .is_some_and(|end_type| self.end_types.iter().any(|wanted| wanted == end_type))
+        {
+            return false;
+        }
+        if self.min_turns.is_some() || self.max_turns.is_some() {
+            match log.turns {
+                Some(turns) => {
+                    if self.min_turns.is_some_and(|min| turns < min)
+                        || self.max_turns.is_some_and(|max| turns > max)
+                    {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+        if self.rated_only && log.rating(Side::P1).is_none() && log.rating(Side::P2).is_none() {
+            return false;
+        }
+        true
+    }
+
+    /// Checks the filters that depend on which sides the searched users played on, other than `team_filter`
+    fn matches_sides(&self, log: &BattleHeader, sides: &[Side]) -> bool {
+        let winner = log.winner();
+        if self.wins_only && !winner.is_some_and(|winner| sides.contains(&winner)) {
+            return false;
+        }
+        if self.losses_only
+            && !winner.is_some_and(|winner| sides.iter().any(|&side| side != winner))
+        {
+            return false;
+        }
+
+        if self.min_rating.is_some() || self.max_rating.is_some() {
+            let in_range = |side: Side| {
+                log.rating(side)
+                    .and_then(|rating| rating.elo)
+                    .is_some_and(|elo| {
+                        !self.min_rating.is_some_and(|min| elo < min)
+                            && !self.max_rating.is_some_and(|max| elo > max)
+                    })
+            };
+            if !sides.iter().any(|&side| in_range(side)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks `team_filter`, which needs the full sets and so the full log
+    fn matches_teams(&self, log: &BattleLog, sides: &[Side]) -> bool {
+        let team_sides = if self.user_team_only {
+            sides
+        } else {
+            &[Side::P1, Side::P2]
+        };
+        team_sides
+            .iter()
+            .any(|&side| self.team_filter.matches_team(log.team(side)))
+    }
+
+    /// Returns the sides that a searched user played on (and whose opponent matches `opponent_ids`),
+    /// or an empty list if the battle doesn't match.
+    ///
+    /// `player_ids` has the IDs each player is known by; see `BattleLog::player_ids`.
+    fn searched_sides(&self, player_ids: &[Vec<String>; 2]) -> Vec<Side> {
+        let [p1ids, p2ids] = player_ids;
+        if self.require_all
+            && !self
+                .user_ids
+                .iter()
+                .all(|id| p1ids.contains(id) || p2ids.contains(id))
+        {
+            return vec![];
+        }
+
+        [(Side::P1, p1ids, p2ids), (Side::P2, p2ids, p1ids)]
+            .into_iter()
+            .filter(|(_, player, opponent)| {
+                self.user_ids.iter().any(|id| player.contains(id))
+                    && (self.opponent_ids.is_empty()
+                        || self.opponent_ids.iter().any(|id| opponent.contains(id)))
+            })
+            .map(|(side, _, _)| side)
+            .collect()
+    }
+
+    /// Whether a searched user might be one of the players, judging by `p1` and `p2` alone.
+    ///
+    /// This only looks up two properties, so it's much faster than parsing the log; most logs in a search don't match,
+    /// and they're skipped without being parsed at all. It's always true with `include_renames`, and if either property
+    /// is missing (so that the log is parsed and the error is reported).
+    fn might_match(&self, raw_json: &str) -> bool {
+        if self.include_renames {
+            return true;
+        }
+        let (p1, p2) = (gjson::get(raw_json, "p1"), gjson::get(raw_json, "p2"));
+        if !p1.exists() || !p2.exists() {
+            return true;
+        }
+        !self
+            .searched_sides(&[vec![to_id(p1.str())], vec![to_id(p2.str())]])
+            .is_empty()
+    }
+
+    /// Sorts the results, dropping any past the limit
+    fn sort_and_truncate(&self, results: &mut SearchResults) {
+        results.sort_unstable_by(|a, b| self.sort.compare(a, b));
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
+        }
+    }
+
+    /// Drops results that can't be printed because there are already `limit` better ones.
+    /// This keeps memory usage bounded when there's a limit.
+    fn enforce_limit(&self, results: &mut SearchResults) {
+        if let Some(limit) = self.limit {
+            if results.len() > limit.saturating_mul(2) {
+                self.sort_and_truncate(results);
+            }
+        }
+    }
+
+    fn write_header<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if self.output_format == OutputFormat::Csv {
+            writeln!(out, "{}", CSV_HEADER)?;
+        }
+        Ok(())
+    }
+
+    /// Writes every result, in order, to `out`.
+    ///
+    /// Human-readable output is grouped by pairing (in alphabetical order); other formats aren't grouped.
+    pub fn write_results<W: Write>(
+        &self,
+        out: &mut W,
+        mut results: SearchResults,
+    ) -> io::Result<()> {
+        self.write_header(out)?;
+        self.sort_and_truncate(&mut results);
+
+        if self.output_format == OutputFormat::Human {
+            let mut pairings: BTreeMap<&(String, String), Vec<&SearchResult>> = BTreeMap::new();
+            for battle in &results {
+                pairings.entry(&battle.pairing).or_default().push(battle);
+            }
+            for (i, ((user, opponent), battles)) in pairings.into_iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{} vs. {} ({} battles)", user, opponent, battles.len())?;
+                for battle in battles {
+                    battle.write(out, self.output_format)?;
+                }
+            }
+        } else {
+            for battle in &results {
+                battle.write(out, self.output_format)?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Prints a result straight away, for unordered mode
+    fn write_unordered(&self, result: &SearchResult) -> io::Result<()> {
+        if let Some(limit) = self.limit {
+            if self.printed.fetch_add(1, atomic::Ordering::Relaxed) >= limit {
+                return Ok(());
+            }
+        }
+
+        // format the line before locking stdout, so other threads aren't kept waiting
+        let mut line = vec![];
+        result.write(&mut line, self.output_format)?;
+        let mut stdout = io::stdout().lock();
+        let mut header_result = Ok(());
+        self.header
+            .call_once(|| header_result = self.write_header(&mut stdout));
+        header_result?;
+        stdout.write_all(&line)
+    }
+}
 
+impl LogParser<SearchResults> for BattleSearcher {
+    fn new_accumulator(&self) -> SearchResults {
+        SearchResults::new()
+    }
+
+    fn handle_log_file(
+        &self,
+        raw_json: String,
+        path: &Path,
+        results: &mut SearchResults,
+    ) -> Result<(), BattleToolsError> {
+        if !self.might_match(&raw_json) {
+            return Ok(());
+        }
+        let header = BattleHeader::parse(&raw_json)?;
+        if !self.matches_battle(&header) {
+            return Ok(());
+        }
+
+        // The full log is only parsed if it's needed to find renames or check teams.
+        let log = if self.include_renames {
+            Some(BattleLog::parse(&raw_json)?)
+        } else {
+            None
+        };
+
+        // parse players
+        let player_ids = match &log {
+            Some(log) => log.player_ids(),
+            None => [
+                vec![to_id(header.player(Side::P1))],
+                vec![to_id(header.player(Side::P2))],
+            ],
+        };
+        let sides = self.searched_sides(&player_ids);
+        if sides.is_empty() {
+            // No searched user is a player in the battle (or they didn't play a searched opponent).
+            return Ok(());
+        }
+        if !self.matches_sides(&header, &sides) {
+            return Ok(());
+        }
+        if !self.team_filter.is_empty() {
+            let log = match log {
+                Some(log) => log,
+                None => BattleLog::parse(&raw_json)?,
+            };
+            if !self.matches_teams(&log, &sides) {
+                return Ok(());
+            }
+        }
+
+        let date = log_date(path)?;
+        let roomid = log_roomid(path, header.roomid.as_deref())?;
+        // a searched user who only matched under another name
+        let matched_name = sides.iter().find_map(|&side| {
+            let ids = &player_ids[side as usize];
+            if self.user_ids.contains(&ids[0]) {
+                None
+            } else {
+                ids.iter().find(|id| self.user_ids.contains(id)).cloned()
+            }
+        });
+        let [p1id, p2id] = player_ids.map(|ids| ids[0].clone());
+
+        let result = SearchResult {
+            path: path.display().to_string(),
+            date: date.to_string(),
+            roomid,
+            format: header.format_id().to_string(),
+            p1: header.p1.to_string(),
+            p2: header.p2.to_string(),
+            winner: (!header.is_tie()).then(|| header.winner.to_string()),
+            end_type: header.end_type.as_deref().map(String::from),
+            turns: header.turns,
+            p1rating: header.rating(Side::P1).and_then(|rating| rating.elo),
+            p2rating: header.rating(Side::P2).and_then(|rating| rating.elo),
+            matched_name,
+            pairing: match sides[..] {
+                [Side::P2] => (p2id, p1id),
+                // if both players were searched for, the pairing shouldn't depend on who was p1
+                [_, _] if p2id < p1id => (p2id, p1id),
+                _ => (p1id, p2id),
+            },
+            teams: [Side::P1, Side::P2].map(|side| {
+                if self.summary && sides.contains(&side) {
+                    header
+                        .team(side)
+                        .iter()
+                        .map(|pokemon| pokemon.species.to_string())
+                        .collect()
+                } else {
+                    vec![]
+                }
+            }),
+            searched_sides: sides,
+        };
+
+        if self.unordered {
+            self.write_unordered(&result)?;
+        } else {
+            results.push(result);
+            self.enforce_limit(results);
+        }
         Ok(())
     }
 
-    fn handle_results(&mut self, _results: Vec<()>) -> Result<(), BattleToolsError> {
+    fn merge_results(&self, mut a: SearchResults, mut b: SearchResults) -> SearchResults {
+        a.append(&mut b);
+        self.enforce_limit(&mut a);
+        a
+    }
+
+    fn handle_results(&mut self, results: SearchResults) -> Result<(), BattleToolsError> {
+        if self.unordered {
+            // the CSV header is printed even if nothing was found
+            let mut stdout = io::stdout().lock();
+            let mut header_result = Ok(());
+            self.header
+                .call_once(|| header_result = self.write_header(&mut stdout));
+            header_result?;
+            return Ok(());
+        }
+
+        if self.summary {
+            let summary = Summary::new(&results);
+            if self.output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&summary)?);
+            } else {
+                println!("{}", summary.to_human_readable());
+            }
+            return Ok(());
+        }
+
+        // All output goes through one writer, after every log has been parsed
+        self.write_results(&mut BufWriter::new(io::stdout().lock()), results)?;
         Ok(())
     }
 }
@@ -102,7 +664,7 @@ impl LogParser<()> for BattleSearcher {
 mod unit_tests {
     extern crate test;
     use super::*;
-    use crate::directory::ParallelDirectoryParser;
+    use crate::directory::{DirectoryFilter, ParallelDirectoryParser, Progress};
     use lazy_static::lazy_static;
     use test::Bencher;
     use tests::*;
@@ -114,42 +676,292 @@ mod unit_tests {
         static ref PATH: &'static std::path::Path = std::path::Path::new("lol/lmao");
     }
 
+    fn search(searcher: &BattleSearcher) -> SearchResults {
+        let mut results = SearchResults::new();
+        searcher
+            .handle_log_file(SAMPLE_JSON.clone(), &PATH, &mut results)
+            .unwrap();
+        results
+    }
+
+    #[test]
+    fn any_user() {
+        let searcher = BattleSearcher::new(&["someone", "Rust Haters"], false, false);
+        let results = search(&searcher);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].pairing,
+            (String::from("rusthaters"), String::from("annika"))
+        );
+        assert_eq!(
+            results[0].to_human_readable(),
+            "(lol) <<gen8randombattle-1>> annika vs. rusthaters (annika won normally)"
+        );
+
+        let searcher = BattleSearcher::new(&["someone", "someone else"], false, false);
+        assert!(search(&searcher).is_empty());
+    }
+
+    #[test]
+    fn all_users() {
+        let mut searcher = BattleSearcher::new(&["Rust Haters", "Annika"], false, false);
+        searcher.require_all = true;
+        let results = search(&searcher);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].pairing,
+            (String::from("annika"), String::from("rusthaters"))
+        );
+
+        let mut searcher = BattleSearcher::new(&["Annika", "someone"], false, false);
+        searcher.require_all = true;
+        assert!(search(&searcher).is_empty());
+    }
+
+    #[test]
+    fn unparsed_logs() {
+        // logs without a searched player are skipped before they're parsed, so their errors aren't reported
+        let broken = SAMPLE_JSON.replace(r#""turns":2"#, r#""turns":"two""#);
+        let mut results = SearchResults::new();
+        let searcher = BattleSearcher::new(&["someone"], false, false);
+        assert!(searcher
+            .handle_log_file(broken.clone(), &PATH, &mut results)
+            .is_ok());
+        let searcher = BattleSearcher::new(&["Annika"], false, false);
+        assert!(matches!(
+            searcher.handle_log_file(broken, &PATH, &mut results),
+            Err(BattleToolsError::InvalidLog(_))
+        ));
+
+        // but they're parsed if a player is missing
+        let missing_player = SAMPLE_JSON.replace(r#""p1":"Annika","#, "");
+        assert!(matches!(
+            searcher.handle_log_file(missing_player, &PATH, &mut results),
+            Err(BattleToolsError::InvalidLog(_))
+        ));
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn header_filters() {
+        // the filters on the battle as a whole are checked before the full log is parsed for the team filter
+        let broken = SAMPLE_JSON.replace(r#""log":["#, r#""log":5,"unused":["#);
+        let mut results = SearchResults::new();
+        let mut searcher = BattleSearcher::new(&["Annika"], false, false);
+        searcher.team_filter.species = vec![String::from("rotomfan")];
+        searcher.ties_only = true;
+        assert!(searcher
+            .handle_log_file(broken.clone(), &PATH, &mut results)
+            .is_ok());
+        searcher.ties_only = false;
+        searcher.min_rating = Some(1500.0);
+        assert!(searcher
+            .handle_log_file(broken.clone(), &PATH, &mut results)
+            .is_ok());
+        searcher.min_rating = None;
+        assert!(matches!(
+            searcher.handle_log_file(broken, &PATH, &mut results),
+            Err(BattleToolsError::InvalidLog(_))
+        ));
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn opponent() {
+        let mut searcher = BattleSearcher::new(&["Annika"], false, false);
+        searcher.opponent_ids = vec![String::from("someone")];
+        assert!(search(&searcher).is_empty());
+
+        searcher.opponent_ids.push(String::from("rusthaters"));
+        assert_eq!(search(&searcher).len(), 1);
+
+        // wins only counts the searched user's wins, not their opponent's
+        let mut searcher = BattleSearcher::new(&["Rust Haters"], true, false);
+        searcher.opponent_ids = vec![String::from("annika")];
+        assert!(search(&searcher).is_empty());
+    }
+
+    #[test]
+    fn renames() {
+        // the sample's `|player|` line calls p2 "Rust Hater"
+        let mut searcher = BattleSearcher::new(&["Rust Hater"], false, false);
+        assert!(search(&searcher).is_empty());
+
+        searcher.include_renames = true;
+        let results = search(&searcher);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matched_name.as_deref(), Some("rusthater"));
+        assert_eq!(
+            results[0].pairing,
+            (String::from("rusthaters"), String::from("annika"))
+        );
+        assert!(results[0].to_human_readable().ends_with(" [as rusthater]"));
+
+        // matching the name in `p2` isn't reported
+        searcher.user_ids = vec![String::from("rusthaters")];
+        assert_eq!(search(&searcher)[0].matched_name, None);
+    }
+
+    #[test]
+    fn battle_filters() {
+        // Annika (1400 Elo) beat Rust Haters (1130 Elo) in 2 turns
+        let mut searcher = BattleSearcher::new(&["Rust Haters"], false, false);
+        assert_eq!(search(&searcher).len(), 1);
+
+        searcher.losses_only = true;
+        assert_eq!(search(&searcher).len(), 1);
+        searcher.user_ids = vec![String::from("annika")];
+        assert!(search(&searcher).is_empty());
+        searcher.losses_only = false;
+
+        searcher.ties_only = true;
+        assert!(search(&searcher).is_empty());
+        searcher.ties_only = false;
+
+        searcher.end_types = vec![String::from("forfeit"), String::from("normal")];
+        searcher.min_turns = Some(2);
+        searcher.max_turns = Some(2);
+        searcher.rated_only = true;
+        assert_eq!(search(&searcher).len(), 1);
+        searcher.end_types = vec![String::from("forfeit")];
+        assert!(search(&searcher).is_empty());
+        searcher.end_types = vec![];
+        searcher.min_turns = Some(3);
+        assert!(search(&searcher).is_empty());
+        searcher.min_turns = None;
+
+        // ratings are the searched user's
+        searcher.min_rating = Some(1300.0);
+        assert_eq!(search(&searcher).len(), 1);
+        searcher.user_ids = vec![String::from("rusthaters")];
+        assert!(search(&searcher).is_empty());
+        searcher.min_rating = None;
+        searcher.max_rating = Some(1200.0);
+        assert_eq!(search(&searcher).len(), 1);
+    }
+
+    #[test]
+    fn team_filter() {
+        let mut searcher = BattleSearcher::new(&["Annika"], false, false);
+        searcher.team_filter.species = vec![String::from("exeggutoralola")];
+        assert_eq!(search(&searcher).len(), 1);
+
+        // Exeggutor-Alola is on Annika's opponent's team
+        searcher.user_team_only = true;
+        assert!(search(&searcher).is_empty());
+        searcher.team_filter.species = vec![String::from("regirock")];
+        assert_eq!(search(&searcher).len(), 1);
+    }
+
+    #[test]
+    fn output_formats() {
+        let mut searcher = BattleSearcher::new(&["Annika"], false, false);
+        let output = |searcher: &BattleSearcher| {
+            let mut out = vec![];
+            searcher.write_results(&mut out, search(searcher)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            output(&searcher),
+            "annika vs. rusthaters (1 battles)\n(lol) <<gen8randombattle-1>> annika vs. rusthaters (annika won normally)\n"
+        );
+
+        searcher.output_format = OutputFormat::Json;
+        assert_eq!(
+            output(&searcher),
+            r#"{"path":"lol/lmao","date":"lol","roomid":"battle-gen8randombattle-1","format":"gen8randombattle","p1":"Annika","p2":"Rust Haters","winner":"Annika","end_type":"normal","turns":2,"p1rating":1400.4859871929,"p2rating":1130.7522733629,"matched_name":null}"#.to_owned() + "\n"
+        );
+
+        searcher.output_format = OutputFormat::Csv;
+        assert_eq!(
+            output(&searcher),
+            format!("{}\nlol/lmao,lol,battle-gen8randombattle-1,gen8randombattle,Annika,Rust Haters,Annika,normal,2,1400.4859871929,1130.7522733629,\n", CSV_HEADER)
+        );
+
+        searcher.output_format = OutputFormat::Paths;
+        assert_eq!(output(&searcher), "lol/lmao\n");
+        searcher.output_format = OutputFormat::RoomIds;
+        assert_eq!(output(&searcher), "battle-gen8randombattle-1\n");
+    }
+
+    #[test]
+    fn sorting() {
+        let mut searcher = BattleSearcher::new(&["Annika"], false, false);
+        let battle = search(&searcher).remove(0);
+        let with = |date: &str, room: u32, turns: u32, rating: f64| SearchResult {
+            date: date.to_string(),
+            roomid: format!("battle-gen8randombattle-{}", room),
+            turns: Some(turns),
+            p1rating: Some(rating),
+            ..battle.clone()
+        };
+        let results = vec![
+            with("2021-01-02", 5, 30, 1200.0),
+            with("2021-01-01", 10, 10, 1500.0),
+            with("2021-01-01", 9, 20, 1000.0),
+        ];
+        let rooms = |searcher: &BattleSearcher| {
+            let mut sorted = results.clone();
+            searcher.sort_and_truncate(&mut sorted);
+            sorted
+                .iter()
+                .map(|battle| battle.room_number().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // room 10 comes after room 9, even though "10" < "9"
+        assert_eq!(rooms(&searcher), vec![9, 10, 5]);
+        searcher.sort = SortKey::Rating;
+        assert_eq!(rooms(&searcher), vec![10, 5, 9]);
+        searcher.sort = SortKey::Turns;
+        assert_eq!(rooms(&searcher), vec![5, 9, 10]);
+        searcher.limit = Some(2);
+        assert_eq!(rooms(&searcher), vec![5, 9]);
+
+        // accumulators are truncated once they're well over the limit, keeping the best results
+        let mut merged = searcher.merge_results(results.clone(), results.clone());
+        assert_eq!(merged.len(), 2);
+        searcher.sort_and_truncate(&mut merged);
+        assert_eq!(merged[0].room_number(), Some(5));
+    }
+
     #[bench]
     pub fn bench_parse_wins_only(b: &mut Bencher) {
-        let searcher = BattleSearcher::new("Rusthaters", true, false);
+        let searcher = BattleSearcher::new(&["Rusthaters"], true, false);
         b.iter(|| {
             searcher
-                .handle_log_file(SAMPLE_JSON.clone(), &PATH)
+                .handle_log_file(SAMPLE_JSON.clone(), &PATH, &mut SearchResults::new())
                 .unwrap()
         });
     }
 
     #[bench]
     pub fn bench_parse_forfeits_only(b: &mut Bencher) {
-        let searcher = BattleSearcher::new("Rusthaters", false, true);
+        let searcher = BattleSearcher::new(&["Rusthaters"], false, true);
         b.iter(|| {
             searcher
-                .handle_log_file(SAMPLE_JSON.clone(), &PATH)
+                .handle_log_file(SAMPLE_JSON.clone(), &PATH, &mut SearchResults::new())
                 .unwrap()
         });
     }
 
     #[bench]
     pub fn bench_parse_forfeit_wins_only(b: &mut Bencher) {
-        let searcher = BattleSearcher::new("Rusthaters", true, true);
+        let searcher = BattleSearcher::new(&["Rusthaters"], true, true);
         b.iter(|| {
             searcher
-                .handle_log_file(SAMPLE_JSON.clone(), &PATH)
+                .handle_log_file(SAMPLE_JSON.clone(), &PATH, &mut SearchResults::new())
                 .unwrap()
         });
     }
 
     #[bench]
     pub fn bench_parse(b: &mut Bencher) {
-        let searcher = BattleSearcher::new("Rusthaters", false, false);
+        let searcher = BattleSearcher::new(&["Rusthaters"], false, false);
         b.iter(|| {
             searcher
-                .handle_log_file(SAMPLE_JSON.clone(), &PATH)
+                .handle_log_file(SAMPLE_JSON.clone(), &PATH, &mut SearchResults::new())
                 .unwrap()
         });
     }
@@ -158,10 +970,14 @@ mod unit_tests {
     fn bench_handle_directory_1k(b: &mut Bencher) {
         build_test_dir(1_000).unwrap();
 
-        let mut searcher = BattleSearcher::new("Rusthaters", false, false);
+        let mut searcher = BattleSearcher::new(&["Rusthaters"], false, false);
         b.iter(|| {
             searcher
-                .handle_directories(vec![TEST_ROOT_DIR.clone()], None)
+                .handle_directories(
+                    vec![TEST_ROOT_DIR.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
                 .unwrap()
         });
     }
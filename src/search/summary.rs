@@ -0,0 +1,277 @@
+// Code for summarizing a user's battles, for `search --summary`.
+use super::{SearchResult, SortKey};
+use crate::{battle_log::Side, id::to_id};
+use prettytable::*;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+
+/// How many opponents and species are listed
+const TOP_COUNT: usize = 10;
+
+/// A user's record in one format
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FormatRecord {
+    pub format: String,
+    pub battles: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    /// As a percentage
+    pub winrate: f64,
+}
+
+/// How often something (an opponent or a species) showed up
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub battles: u32,
+}
+
+/// Totals over every side of a matching battle that a searched user played on.
+///
+/// If both players in a battle were searched for, it's counted once for each of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub battles: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    pub forfeit_wins: u32,
+    pub forfeit_losses: u32,
+    /// Over battles whose number of turns is known
+    pub average_turns: Option<f64>,
+    /// The searched user's Elo before their first rated battle
+    pub first_rating: Option<f64>,
+    /// The searched user's Elo before their last rated battle
+    pub last_rating: Option<f64>,
+    /// Sorted by number of battles
+    pub formats: Vec<FormatRecord>,
+    /// The most common opponents
+    pub opponents: Vec<Count>,
+    /// The species the searched user brought most often
+    pub species: Vec<Count>,
+}
+
+/// The `TOP_COUNT` most common names, most common first
+fn top<S: AsRef<str>>(counts: HashMap<S, u32>) -> Vec<Count> {
+    let mut counts = counts
+        .into_iter()
+        .map(|(name, battles)| Count {
+            name: name.as_ref().to_string(),
+            battles,
+        })
+        .collect::<Vec<_>>();
+    counts.sort_unstable_by(|a, b| b.battles.cmp(&a.battles).then_with(|| a.name.cmp(&b.name)));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+impl Summary {
+    pub fn new(results: &[SearchResult]) -> Self {
+        let mut results = results.iter().collect::<Vec<_>>();
+        results.sort_unstable_by(|a, b| SortKey::Date.compare(a, b));
+
+        let mut summary = Summary::default();
+        let mut formats: HashMap<&str, FormatRecord> = HashMap::new();
+        let mut opponents: HashMap<String, u32> = HashMap::new();
+        let mut species: HashMap<&str, u32> = HashMap::new();
+        let mut total_turns = 0;
+        let mut battles_with_turns = 0;
+
+        for result in results {
+            for &side in &result.searched_sides {
+                let (player, opponent, rating) = match side {
+                    Side::P1 => (&result.p1, &result.p2, result.p1rating),
+                    Side::P2 => (&result.p2, &result.p1, result.p2rating),
+                };
+                let is_forfeit = result.end_type.as_deref() == Some("forfeit");
+                let format = formats
+                    .entry(&result.format)
+                    .or_insert_with(|| FormatRecord {
+                        format: result.format.clone(),
+                        ..FormatRecord::default()
+                    });
+
+                summary.battles += 1;
+                format.battles += 1;
+                match &result.winner {
+                    None => {
+                        summary.ties += 1;
+                        format.ties += 1;
+                    }
+                    Some(winner) if to_id(winner) == to_id(player) => {
+                        summary.wins += 1;
+                        format.wins += 1;
+                        summary.forfeit_wins += is_forfeit as u32;
+                    }
+                    Some(_) => {
+                        summary.losses += 1;
+                        format.losses += 1;
+                        summary.forfeit_losses += is_forfeit as u32;
+                    }
+                }
+
+                if let Some(turns) = result.turns {
+                    total_turns += turns as u64;
+                    battles_with_turns += 1;
+                }
+                if rating.is_some() {
+                    summary.first_rating = summary.first_rating.or(rating);
+                    summary.last_rating = rating;
+                }
+                *opponents.entry(to_id(opponent)).or_default() += 1;
+                for pokemon in &result.teams[side as usize] {
+                    *species.entry(pokemon).or_default() += 1;
+                }
+            }
+        }
+
+        summary.average_turns =
+            (battles_with_turns > 0).then(|| total_turns as f64 / battles_with_turns as f64);
+        summary.formats = formats
+            .into_values()
+            .map(|mut format| {
+                format.winrate = format.wins as f64 * 100.0 / format.battles as f64;
+                format
+            })
+            .collect();
+        summary.formats.sort_unstable_by(|a, b| {
+            b.battles
+                .cmp(&a.battles)
+                .then_with(|| a.format.cmp(&b.format))
+        });
+        summary.opponents = top(opponents);
+        summary.species = top(species);
+        summary
+    }
+
+    pub fn to_human_readable(&self) -> String {
+        let optional = |value: Option<f64>, precision: usize| match value {
+            Some(value) => format!("{:.*}", precision, value),
+            None => String::from("-"),
+        };
+        let totals = table!(
+            ["Battles", self.battles],
+            [
+                "Wins / losses / ties",
+                format!("{} / {} / {}", self.wins, self.losses, self.ties)
+            ],
+            [
+                "Forfeit wins / losses",
+                format!("{} / {}", self.forfeit_wins, self.forfeit_losses)
+            ],
+            ["Average turns", optional(self.average_turns, 1)],
+            ["First rating", optional(self.first_rating, 0)],
+            ["Last rating", optional(self.last_rating, 0)]
+        );
+
+        let mut formats = table!(["Format", "Battles", "Wins", "Losses", "Ties", "Winrate"]);
+        for format in &self.formats {
+            formats.add_row(row![
+                format.format,
+                format.battles,
+                format.wins,
+                format.losses,
+                format.ties,
+                format!("{:.1}%", format.winrate)
+            ]);
+        }
+
+        let mut opponents = table!(["Opponent", "Battles"]);
+        for opponent in &self.opponents {
+            opponents.add_row(row![opponent.name, opponent.battles]);
+        }
+        let mut species = table!(["Species", "Battles"]);
+        for pokemon in &self.species {
+            species.add_row(row![pokemon.name, pokemon.battles]);
+        }
+
+        [totals, formats, opponents, species]
+            .iter()
+            .map(Table::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{directory::LogParser, search::BattleSearcher};
+    use std::path::Path;
+
+    fn summarize(usernames: &[&str]) -> Summary {
+        let mut searcher = BattleSearcher::new(usernames, false, false);
+        searcher.summary = true;
+        let mut results = vec![];
+        for format in ["gen9randombattle", "gen8doublesou"] {
+            let path = format!("src/test-logs/{}.log.json", format);
+            let json = std::fs::read_to_string(&path).unwrap();
+            searcher
+                .handle_log_file(json, Path::new(&path), &mut results)
+                .unwrap();
+        }
+        Summary::new(&results)
+    }
+
+    #[test]
+    fn totals() {
+        let summary = summarize(&["Rust Haters"]);
+        assert_eq!(
+            (summary.battles, summary.wins, summary.losses, summary.ties),
+            (2, 1, 1, 0)
+        );
+        assert_eq!((summary.forfeit_wins, summary.forfeit_losses), (1, 1));
+        assert_eq!(summary.average_turns, Some(4.5));
+        // gen8doublesou has the lower room number
+        assert_eq!(summary.first_rating, Some(1349.9));
+        assert_eq!(summary.last_rating, Some(1487.05));
+        assert_eq!(
+            summary.opponents,
+            vec![Count {
+                name: String::from("annika"),
+                battles: 2
+            }]
+        );
+        assert!(summary.species.iter().any(|count| count.name == "Garchomp"));
+        assert!(!summary.species.iter().any(|count| count.name == "Pelipper"));
+    }
+
+    #[test]
+    fn formats() {
+        let summary = summarize(&["Annika"]);
+        assert_eq!(
+            summary.formats,
+            vec![
+                FormatRecord {
+                    format: String::from("gen8doublesou"),
+                    battles: 1,
+                    wins: 1,
+                    losses: 0,
+                    ties: 0,
+                    winrate: 100.0,
+                },
+                FormatRecord {
+                    format: String::from("gen9randombattle"),
+                    battles: 1,
+                    wins: 0,
+                    losses: 1,
+                    ties: 0,
+                    winrate: 0.0,
+                },
+            ]
+        );
+
+        let table = summary.to_human_readable();
+        assert!(table.contains("| Wins / losses / ties  | 1 / 1 / 0 |"));
+        assert!(table.contains("| gen8doublesou    | 1       | 1    | 0      | 0    | 100.0%  |"));
+    }
+
+    #[test]
+    fn head_to_head() {
+        // both players were searched for, so each battle is a win and a loss
+        let summary = summarize(&["Annika", "Rust Haters"]);
+        assert_eq!((summary.battles, summary.wins, summary.losses), (4, 2, 2));
+        assert_eq!(summary.opponents.len(), 2);
+    }
+}
@@ -0,0 +1,105 @@
+// Code for matching battles by the Pokémon on players' teams.
+use crate::{battle_log::PokemonSet, id::to_id};
+
+/// Criteria for the Pokémon on a team.
+///
+/// All values should be IDs. A Pokémon matches if it matches every nonempty criterion:
+/// its species, item, ability, and Tera Type must each be one of those listed, and it must know all of `moves`.
+#[derive(Debug, Clone, Default)]
+pub struct TeamFilter {
+    pub species: Vec<String>,
+    pub moves: Vec<String>,
+    pub items: Vec<String>,
+    pub abilities: Vec<String>,
+    pub tera_types: Vec<String>,
+}
+
+/// Whether `value` is one of `ids`; anything matches an empty list
+fn one_of(ids: &[String], value: Option<&str>) -> bool {
+    ids.is_empty() || value.is_some_and(|value| ids.contains(&to_id(value)))
+}
+
+impl TeamFilter {
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+            && self.moves.is_empty()
+            && self.items.is_empty()
+            && self.abilities.is_empty()
+            && self.tera_types.is_empty()
+    }
+
+    pub fn matches_pokemon(&self, pokemon: &PokemonSet) -> bool {
+        one_of(&self.species, Some(&pokemon.species))
+            && one_of(&self.items, pokemon.item.as_deref())
+            && one_of(&self.abilities, pokemon.ability.as_deref())
+            && one_of(&self.tera_types, pokemon.tera_type.as_deref())
+            && self
+                .moves
+                .iter()
+                .all(|wanted| pokemon.moves.iter().any(|known| to_id(known) == *wanted))
+    }
+
+    /// Whether any Pokémon on the team matches
+    pub fn matches_team(&self, team: &[PokemonSet]) -> bool {
+        team.iter().any(|pokemon| self.matches_pokemon(pokemon))
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::battle_log::{BattleLog, Side};
+
+    fn ids(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| to_id(name)).collect()
+    }
+
+    #[test]
+    fn matching() {
+        let json = std::fs::read_to_string("src/benchmark-data.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let p1team = log.team(Side::P1);
+
+        let mut filter = TeamFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches_team(p1team));
+
+        filter.species = ids(&["Miltank", "Urshifu-Rapid-Strike"]);
+        filter.items = ids(&["leftovers"]);
+        assert!(filter.matches_team(p1team));
+        assert!(!filter.matches_team(log.team(Side::P2)));
+
+        filter.moves = ids(&["Heal Bell", "milk drink"]);
+        assert!(filter.matches_team(p1team));
+        filter.moves.push(to_id("Knock Off"));
+        assert!(!filter.matches_team(p1team));
+    }
+
+    #[test]
+    fn forme_punctuation() {
+        let json = std::fs::read_to_string("src/benchmark-data.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+
+        let filter = TeamFilter {
+            species: ids(&["pikachu sinnoh"]),
+            abilities: ids(&["Lightning-Rod"]),
+            ..TeamFilter::default()
+        };
+        assert!(filter.matches_team(log.team(Side::P2)));
+        assert!(!filter.matches_team(log.team(Side::P1)));
+    }
+
+    #[test]
+    fn tera_type() {
+        let json = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+
+        let filter = TeamFilter {
+            species: ids(&["Iron Moth"]),
+            tera_types: ids(&["Grass", "Fire"]),
+            ..TeamFilter::default()
+        };
+        assert!(filter.matches_team(log.team(Side::P1)));
+        assert!(!filter.matches_team(log.team(Side::P2)));
+    }
+}
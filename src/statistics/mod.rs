@@ -1,8 +1,10 @@
 // Code for the `psbattletools statistics` subcommand.
 mod stats;
 
+use serde_derive::{Deserialize, Serialize};
+
 use crate::{directory::*, BattleToolsError};
-use stats::{GameResult, Stats};
+use stats::Stats;
 
 pub trait StatsOutput {
     fn to_human_readable(&mut self) -> String;
@@ -24,19 +26,57 @@ impl StatisticsDirectoryParser {
     }
 }
 
-impl LogParser<Vec<GameResult>> for StatisticsDirectoryParser {
+impl LogParser<Stats> for StatisticsDirectoryParser {
+    fn new_accumulator(&self) -> Stats {
+        Stats::new()
+    }
+
     fn handle_log_file(
         &self,
         raw_json: String,
         _: &std::path::Path,
-    ) -> Result<Vec<GameResult>, BattleToolsError> {
-        Stats::process_json(self.min_elo, &raw_json)
+        accumulator: &mut Stats,
+    ) -> Result<(), BattleToolsError> {
+        accumulator.add_game_results(Stats::process_json(self.min_elo, &raw_json)?);
+        Ok(())
+    }
+
+    fn merge_results(&self, mut a: Stats, b: Stats) -> Stats {
+        a.merge(b);
+        a
+    }
+
+    fn handle_results(&mut self, results: Stats) -> Result<(), BattleToolsError> {
+        self.stats.merge(results);
+        Ok(())
+    }
+}
+
+/// What a `StatisticsDirectoryParser` saves in a manifest
+#[derive(Serialize, Deserialize)]
+struct SavedState<S> {
+    min_elo: Option<u64>,
+    stats: S,
+}
+
+impl ResumableParser for StatisticsDirectoryParser {
+    fn save_state(&self) -> Result<serde_json::Value, BattleToolsError> {
+        Ok(serde_json::to_value(SavedState {
+            min_elo: self.min_elo,
+            stats: &self.stats,
+        })?)
     }
 
-    fn handle_results(&mut self, results: Vec<Vec<GameResult>>) -> Result<(), BattleToolsError> {
-        for result in results {
-            self.stats.add_game_results(result);
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), BattleToolsError> {
+        let saved: SavedState<Stats> = serde_json::from_value(state)?;
+        // Mixing battles filtered by different minimum ratings would give meaningless statistics
+        if saved.min_elo != self.min_elo {
+            return Err(BattleToolsError::InvalidManifest(format!(
+                "it was made with a minimum elo of {:?}, not {:?}",
+                saved.min_elo, self.min_elo
+            )));
         }
+        self.stats = saved.stats;
         Ok(())
     }
 }
@@ -66,7 +106,27 @@ mod unit_tests {
         let mut parser = StatisticsDirectoryParser::new(None);
         b.iter(|| {
             parser
-                .handle_directories(vec![TEST_ROOT_DIR.clone()], None)
+                .handle_directories(
+                    vec![TEST_ROOT_DIR.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
+                .unwrap()
+        });
+    }
+
+    #[bench]
+    fn bench_handle_multi_day_directory_1k(b: &mut Bencher) {
+        build_multi_day_test_dir(&TEST_MULTI_DAY_ROOT_DIR, 30, 34).unwrap();
+
+        let mut parser = StatisticsDirectoryParser::new(None);
+        b.iter(|| {
+            parser
+                .handle_directories(
+                    vec![TEST_MULTI_DAY_ROOT_DIR.clone()],
+                    &DirectoryFilter::default(),
+                    &Progress::default(),
+                )
                 .unwrap()
         });
     }
@@ -77,7 +137,11 @@ mod unit_tests {
         build_test_dir(1_000).unwrap();
         let mut parser = StatisticsDirectoryParser::new(None);
         parser
-            .handle_directories(vec![TEST_ROOT_DIR.to_owned()], None)
+            .handle_directories(
+                vec![TEST_ROOT_DIR.to_owned()],
+                &DirectoryFilter::default(),
+                &Progress::default(),
+            )
             .unwrap();
         let mut stats = parser.stats;
 
@@ -1,8 +1,12 @@
 // Winrates code - from https://github.com/AnnikaCodes/randbats-winrates/blob/main/src/stats.rs
 use indexmap::IndexMap;
 use prettytable::*;
+use serde_derive::{Deserialize, Serialize};
 
-use crate::BattleToolsError;
+use crate::{
+    battle_log::{BattleHeader, Side},
+    BattleToolsError,
+};
 
 #[derive(Copy, Clone)]
 struct FinalStats {
@@ -12,7 +16,7 @@ struct FinalStats {
 }
 
 /// Stores statistics about a pokemon
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 struct PokemonStats {
     games: u32,
     wins: u32,
@@ -41,10 +45,11 @@ pub struct GameResult {
 }
 
 /// Stores overall statistics
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Stats {
     /// Pokemon:statistics map
     pokemon: IndexMap<String, PokemonStats>,
+    #[serde(skip)]
     is_sorted: bool,
 }
 
@@ -71,10 +76,13 @@ impl Stats {
         min_elo: Option<u64>,
         json: &str,
     ) -> Result<Vec<GameResult>, BattleToolsError> {
-        // ELO check
+        let header = BattleHeader::parse(json)?;
+
+        // ELO check (unrated battles count as below any minimum)
         if let Some(elo) = min_elo {
-            for elo_property in ["p1rating.elo", "p2rating.elo"].iter() {
-                if (gjson::get(json, elo_property).f32() as u64) < elo {
+            for side in [Side::P1, Side::P2] {
+                let player_elo = header.rating(side).and_then(|rating| rating.elo);
+                if player_elo.is_none_or(|player_elo| (player_elo as u64) < elo) {
                     // ignore
                     return Ok(vec![]);
                 }
@@ -82,18 +90,12 @@ impl Stats {
         }
 
         let mut results = vec![];
-
-        // (indices of parsed JSON)
-        for (species_list_property, player_property) in
-            [("p1team.#.species", "p1"), ("p2team.#.species", "p2")].iter()
-        {
-            // json[16] = the winner
-            let won = gjson::get(json, player_property) == gjson::get(json, "winner");
-
-            let species_list = gjson::get(json, species_list_property);
-            for species in species_list.array() {
+        let winner = header.winner();
+        for side in [Side::P1, Side::P2] {
+            let won = winner == Some(side);
+            for pokemon in header.team(side) {
                 results.push(GameResult {
-                    species: Stats::normalize_species(species.str()),
+                    species: Stats::normalize_species(&pokemon.species),
                     won,
                 });
             }
@@ -122,6 +124,26 @@ impl Stats {
         }
     }
 
+    /// Adds the statistics from another `Stats` to this one
+    pub fn merge(&mut self, other: Stats) {
+        if other.pokemon.is_empty() {
+            return;
+        }
+
+        self.is_sorted = false;
+        for (species, other_stats) in other.pokemon {
+            match self.pokemon.get_mut(&species) {
+                Some(s) => {
+                    s.wins += other_stats.wins;
+                    s.games += other_stats.games;
+                }
+                None => {
+                    self.pokemon.insert(species, other_stats);
+                }
+            };
+        }
+    }
+
     fn normalize_species(species: &str) -> String {
         if species.starts_with("Pikachu-") {
             String::from("Pikachu")
@@ -178,11 +200,9 @@ impl super::StatsOutput for Stats {
 
     fn to_human_readable(&mut self) -> String {
         let mut table = table!(["Rank", "Pokemon", "Deviations", "Winrate", "Games", "Wins"]);
-        let mut cur_rank = 1;
-
         self.sort();
 
-        for (pokemon, stats) in &self.pokemon {
+        for (cur_rank, (pokemon, stats)) in (1..).zip(&self.pokemon) {
             let fstats = stats.final_stats();
 
             let deviations = fstats.deviations.to_string();
@@ -197,7 +217,6 @@ impl super::StatsOutput for Stats {
                 stats.games,
                 stats.wins
             ]);
-            cur_rank += 1;
         }
 
         table.to_string()
@@ -224,6 +243,28 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn merge() {
+        let mut merged = Stats::new();
+        let mut other = Stats::new();
+        add_records(&mut merged, 10);
+        add_records(&mut other, 5);
+        merged.merge(other);
+
+        let mut expected = Stats::new();
+        add_records(&mut expected, 15);
+        assert_eq!(merged.to_csv(), expected.to_csv());
+    }
+
+    #[test]
+    fn missing_timestamp() {
+        let json = SAMPLE_JSON.replace(
+            r#""timestamp":"Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)","#,
+            "",
+        );
+        assert_eq!(Stats::process_json(Some(1050), &json).unwrap().len(), 12);
+    }
+
     #[bench]
     pub fn bench_process_json(b: &mut Bencher) {
         b.iter(|| Stats::process_json(Some(1050), &SAMPLE_JSON));
@@ -0,0 +1,542 @@
+// Code for printing a battle as a readable, turn-by-turn transcript.
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+};
+
+use crate::{
+    battle_log::{BattleLog, PokemonSet, Side},
+    protocol::{Event, Hp, Line, PokemonId, PublicLines, SwitchKind, Tag},
+    replay::BattleState,
+};
+
+/// Whether transcripts are colored with ANSI escape codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color if stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!(
+                "unknown color mode '{}' (expected auto, always, or never)",
+                s
+            )),
+        }
+    }
+}
+
+impl ColorMode {
+    pub fn use_color(self) -> bool {
+        match self {
+            ColorMode::Auto => io::stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const BLUE: &str = "34";
+const MAGENTA: &str = "35";
+
+/// Writes battle logs as transcripts
+pub struct Transcript {
+    show_chat: bool,
+    color: bool,
+}
+
+impl Transcript {
+    pub fn new(show_chat: bool, color: bool) -> Self {
+        Self { show_chat, color }
+    }
+
+    /// Writes the players' teams and ratings, and then every turn of the battle
+    pub fn write<W: Write>(&self, out: &mut W, log: &BattleLog) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}",
+            self.paint(
+                BOLD,
+                format_args!("{}: {} vs. {}", log.format_name(), log.p1, log.p2)
+            )
+        )?;
+        if let Some(timestamp) = &log.timestamp {
+            writeln!(out, "{}", timestamp)?;
+        }
+        for side in [Side::P1, Side::P2] {
+            writeln!(out)?;
+            self.write_team(out, log, side)?;
+        }
+        writeln!(out)?;
+
+        let mut state = BattleState::new(log);
+        // In singles, it's obvious what each move targets
+        let mut show_targets = false;
+        for line in PublicLines::new(&log.log) {
+            let line = Line::parse(line);
+            if let Event::GameType(game_type) = line.event {
+                show_targets = game_type != "singles";
+            }
+            if let Some(text) = self.describe(&line, &state, show_targets) {
+                writeln!(out, "{}", text)?;
+            }
+            state.apply(&line);
+        }
+        Ok(())
+    }
+
+    fn write_team<W: Write>(&self, out: &mut W, log: &BattleLog, side: Side) -> io::Result<()> {
+        let player = self.paint(side_color(side), log.player(side));
+        match log.rating(side).and_then(|rating| rating.elo) {
+            Some(elo) => writeln!(out, "{} ({:.0} Elo)", player, elo)?,
+            None => writeln!(out, "{} (unrated)", player)?,
+        }
+        for set in log.team(side) {
+            writeln!(out, "  {}", describe_set(set))?;
+        }
+        Ok(())
+    }
+
+    fn paint(&self, code: &str, text: impl Display) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Names a Pokémon by its owner and species, like "Annika's Rotom-Fan"
+    fn pokemon(&self, state: &BattleState, id: &PokemonId) -> String {
+        let species = state
+            .pokemon(id)
+            .map_or(id.name, |pokemon| pokemon.details.species);
+        self.paint(
+            side_color(id.side),
+            format_args!(
+                "{} {}",
+                possessive(state.sides[id.side as usize].player),
+                species
+            ),
+        )
+    }
+
+    /// Describes one line of the log, given the state of the battle before it; returns `None` for lines that
+    /// aren't shown.
+    fn describe(&self, line: &Line, state: &BattleState, show_targets: bool) -> Option<String> {
+        let from = line.tags.iter().find_map(|tag| match tag {
+            // residual damage from a status is `[from] brn`, for example
+            Tag::From(effect) => Some(status_name(effect_name(effect)).1),
+            _ => None,
+        });
+        let flagged = |flag| line.tags.contains(&Tag::Flag(flag));
+        if flagged("silent") {
+            return None;
+        }
+        let from_suffix = from.map_or_else(String::new, |effect| format!(" ({})", effect));
+        let pokemon = |id: &PokemonId| self.pokemon(state, id);
+
+        let text = match &line.event {
+            Event::Turn(turn) => format!("\n{}", self.paint(BOLD, format_args!("Turn {}", turn))),
+            Event::Win(winner) => self.paint(BOLD, format_args!("{} won the battle!", winner)),
+            Event::Tie => self.paint(BOLD, "The battle ended in a tie!"),
+            Event::Text(text) | Event::Message(text) => text.to_string(),
+            Event::Inactive(message) | Event::InactiveOff(message) => self.paint(DIM, message),
+            Event::Chat { user, message, .. } if self.show_chat => {
+                self.paint(DIM, format_args!("{}: {}", user.trim(), message))
+            }
+
+            Event::Switch {
+                kind,
+                pokemon: id,
+                details,
+                hp,
+            } => {
+                let player = state.sides[id.side as usize].player;
+                let name = self.paint(
+                    side_color(id.side),
+                    format_args!("{} {}", possessive(player), details.species),
+                );
+                let hp = match hp {
+                    Some(hp) if hp_percent(hp) < 100.0 => format!(" ({:.0}% HP)", hp_percent(hp)),
+                    _ => String::new(),
+                };
+                match kind {
+                    SwitchKind::Switch => format!("{} sent out {}!{}", player, details.species, hp),
+                    SwitchKind::Drag => format!("{} was dragged out!{}", name, hp),
+                    SwitchKind::Replace => format!("The Illusion wore off, revealing {}!", name),
+                }
+            }
+            Event::DetailsChange {
+                pokemon: id,
+                details,
+                ..
+            } => format!("{} transformed into {}!", pokemon(id), details.species),
+            Event::FormeChange {
+                pokemon: id,
+                species,
+                ..
+            } => format!(
+                "{} transformed into {}!{}",
+                pokemon(id),
+                species,
+                from_suffix
+            ),
+            Event::Move {
+                pokemon: id,
+                name,
+                target,
+            } => match target {
+                // spread moves hit more than their listed target
+                Some(target)
+                    if show_targets
+                        && target != id
+                        && !line
+                            .tags
+                            .iter()
+                            .any(|tag| matches!(tag, Tag::Other { name: "spread", .. })) =>
+                {
+                    format!("{} used {} on {}!", pokemon(id), name, pokemon(target))
+                }
+                _ => format!("{} used {}!", pokemon(id), name),
+            },
+            Event::Cant {
+                pokemon: id,
+                reason,
+                ..
+            } => format!("{} couldn't move ({})", pokemon(id), effect_name(reason)),
+            Event::Faint(id) => format!("{} {}", pokemon(id), self.paint(RED, "fainted!")),
+
+            Event::Damage { pokemon: id, hp } | Event::Heal { pokemon: id, hp } => {
+                let before = state
+                    .pokemon(id)
+                    .map_or(100.0, |pokemon| pokemon.hp_percent());
+                let after = hp_percent(hp);
+                let (verb, change, color) = if matches!(line.event, Event::Damage { .. }) {
+                    ("lost", before - after, YELLOW)
+                } else {
+                    ("restored", after - before, GREEN)
+                };
+                format!(
+                    "{} {} {}{} ({:.0}% left)",
+                    pokemon(id),
+                    verb,
+                    self.paint(color, format_args!("{:.0}% HP", change)),
+                    from_suffix,
+                    after
+                )
+            }
+            Event::SetHp { pokemon: id, hp } => format!(
+                "{}'s HP became {:.0}%{}",
+                pokemon(id),
+                hp_percent(hp),
+                from_suffix
+            ),
+            Event::Status {
+                pokemon: id,
+                status,
+            } => format!(
+                "{} was {}!{}",
+                pokemon(id),
+                self.paint(MAGENTA, status_name(status).0),
+                from_suffix
+            ),
+            Event::CureStatus {
+                pokemon: id,
+                status,
+            } => format!(
+                "{} was cured of its {}{}",
+                pokemon(id),
+                status_name(status).1,
+                from_suffix
+            ),
+            Event::Boost {
+                pokemon: id,
+                stat,
+                amount,
+            } => format!(
+                "{}'s {} rose by {}{}",
+                pokemon(id),
+                stat_name(stat),
+                amount,
+                from_suffix
+            ),
+            Event::Unboost {
+                pokemon: id,
+                stat,
+                amount,
+            } => format!(
+                "{}'s {} fell by {}{}",
+                pokemon(id),
+                stat_name(stat),
+                amount,
+                from_suffix
+            ),
+            Event::SetBoost {
+                pokemon: id,
+                stat,
+                amount,
+            } => format!(
+                "{}'s {} was set to {:+}{}",
+                pokemon(id),
+                stat_name(stat),
+                amount,
+                from_suffix
+            ),
+            Event::ClearBoost(id) => format!("{}'s stat changes were removed", pokemon(id)),
+            Event::ClearNegativeBoost(id) => {
+                format!("{}'s lowered stats were restored", pokemon(id))
+            }
+            Event::ClearAllBoost => "All stat changes were removed".to_string(),
+
+            // `[upkeep]` lines just say that the weather is still going
+            Event::Weather(_) if flagged("upkeep") => return None,
+            Event::Weather("none") => "The weather cleared up".to_string(),
+            Event::Weather(weather) => format!("The weather became {}{}", weather, from_suffix),
+            Event::FieldStart(condition) => {
+                format!("{} started{}", effect_name(condition), from_suffix)
+            }
+            Event::FieldEnd(condition) => format!("{} ended", effect_name(condition)),
+            Event::SideStart { side, condition } => format!(
+                "{} started on {} side",
+                effect_name(condition),
+                possessive(side.name)
+            ),
+            Event::SideEnd { side, condition } => format!(
+                "{} ended on {} side",
+                effect_name(condition),
+                possessive(side.name)
+            ),
+            Event::VolatileStart {
+                pokemon: id,
+                effect,
+                ..
+            } => format!("{}: {} started", pokemon(id), effect_name(effect)),
+            Event::VolatileEnd {
+                pokemon: id,
+                effect,
+                ..
+            } => format!("{}: {} ended", pokemon(id), effect_name(effect)),
+            Event::Item { pokemon: id, item } => {
+                format!("{} has {}{}", pokemon(id), item, from_suffix)
+            }
+            Event::EndItem { pokemon: id, item } => {
+                format!("{} lost its {}{}", pokemon(id), item, from_suffix)
+            }
+            Event::Ability {
+                pokemon: id,
+                ability,
+                ..
+            } => format!("{}'s ability is {}", pokemon(id), ability),
+            Event::Transform {
+                pokemon: id,
+                target,
+            } => format!("{} transformed into {}!", pokemon(id), pokemon(target)),
+            Event::Terastallize {
+                pokemon: id,
+                tera_type,
+            } => format!("{} terastallized into the {} type!", pokemon(id), tera_type),
+            Event::Activate {
+                pokemon: id,
+                effect,
+                ..
+            } => format!("{}: {} activated", pokemon(id), effect_name(effect)),
+            Event::Crit(id) => format!("A critical hit on {}!", pokemon(id)),
+            Event::SuperEffective(id) => format!("It's super effective on {}!", pokemon(id)),
+            Event::Resisted(id) => format!("It's not very effective on {}...", pokemon(id)),
+            Event::Immune(id) => format!("It doesn't affect {}...", pokemon(id)),
+            Event::Miss { source, .. } => format!("{}'s attack missed!", pokemon(source)),
+            Event::Fail { .. } => "But it failed!".to_string(),
+            _ => return None,
+        };
+        Some(text)
+    }
+}
+
+fn side_color(side: Side) -> &'static str {
+    match side {
+        Side::P1 => BLUE,
+        Side::P2 => RED,
+    }
+}
+
+/// "Annika" -> "Annika's"; "Rust Haters" -> "Rust Haters'"
+fn possessive(name: &str) -> String {
+    if name.ends_with('s') {
+        format!("{}'", name)
+    } else {
+        format!("{}'s", name)
+    }
+}
+
+fn hp_percent(hp: &Hp) -> f64 {
+    match hp.max {
+        Some(max) if max > 0 => f64::from(hp.current) * 100.0 / f64::from(max),
+        _ => 0.0,
+    }
+}
+
+/// Removes the prefix from effects like `move: Stealth Rock` or `item: Life Orb`
+fn effect_name(effect: &str) -> &str {
+    effect.split_once(": ").map_or(effect, |(_, name)| name)
+}
+
+/// Names a status condition as (what happened to the Pokémon, the condition itself)
+fn status_name(status: &str) -> (&str, &str) {
+    match status {
+        "brn" => ("burned", "burn"),
+        "par" => ("paralyzed", "paralysis"),
+        "slp" => ("put to sleep", "sleep"),
+        "frz" => ("frozen", "freeze"),
+        "psn" => ("poisoned", "poison"),
+        "tox" => ("badly poisoned", "poison"),
+        status => (status, status),
+    }
+}
+
+fn stat_name(stat: &str) -> &str {
+    match stat {
+        "atk" => "Attack",
+        "def" => "Defense",
+        "spa" => "Sp. Atk",
+        "spd" => "Sp. Def",
+        "spe" => "Speed",
+        "accuracy" => "accuracy",
+        "evasion" => "evasiveness",
+        stat => stat,
+    }
+}
+
+/// Describes a team member, like "Pelipper @ Damp Rock (Drizzle, Tera Ground): hurricane, surf, uturn, roost"
+fn describe_set(set: &PokemonSet) -> String {
+    let mut description = set.species.to_string();
+    if !set.name.is_empty() && set.name != set.species {
+        description = format!("{} ({})", set.name, set.species);
+    }
+    if let Some(level) = set.level.filter(|&level| level != 100) {
+        description.push_str(&format!(" L{}", level));
+    }
+    if let Some(item) = set.item.as_deref().filter(|item| !item.is_empty()) {
+        description.push_str(&format!(" @ {}", item));
+    }
+
+    let traits: Vec<String> = set
+        .ability
+        .iter()
+        .map(|ability| ability.to_string())
+        .chain(set.tera_type.iter().map(|tera| format!("Tera {}", tera)))
+        .collect();
+    if !traits.is_empty() {
+        description.push_str(&format!(" ({})", traits.join(", ")));
+    }
+    if !set.moves.is_empty() {
+        description.push_str(&format!(": {}", set.moves.join(", ")));
+    }
+    description
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn transcript(show_chat: bool, color: bool) -> String {
+        let json = std::fs::read_to_string("src/test-logs/gen9randombattle.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let mut out = vec![];
+        Transcript::new(show_chat, color)
+            .write(&mut out, &log)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn header() {
+        let transcript = transcript(true, false);
+        let mut lines = transcript.lines();
+        assert_eq!(
+            lines.next(),
+            Some("[Gen 9] Random Battle: Annika vs. Rust Haters")
+        );
+        assert!(transcript.contains(
+            "Annika (1512 Elo)\n  Pelipper L86 @ Heavy-Duty Boots (Drizzle, Tera Ground): hurricane, surf, uturn, roost\n"
+        ));
+        assert!(transcript.contains("Rust Haters (1487 Elo)\n  Garchomp L77 @ Life Orb"));
+    }
+
+    #[test]
+    fn turns() {
+        let transcript = transcript(true, false);
+        for expected in [
+            "Annika sent out Pelipper!\nRust Haters sent out Garchomp!\nThe weather became RainDance (Drizzle)\n\nTurn 1\n",
+            "Rust Haters' Garchomp used Stealth Rock!\nStealth Rock started on Annika's side\n",
+            "Annika's Pelipper lost 12% HP (Rough Skin) (88% left)\n",
+            "Annika sent out Kingambit!\nAnnika's Kingambit lost 6% HP (Stealth Rock) (94% left)\n",
+            "Annika's Kingambit's Attack rose by 2\n",
+            "It's super effective on Annika's Kingambit!\nAnnika's Kingambit lost 63% HP (31% left)\n",
+            "Annika's Kingambit terastallized into the Dark type!\n",
+            "Rust Haters' Garchomp lost 68% HP (0% left)\nRust Haters' Garchomp fainted!\n",
+            "Rust Haters sent out Iron Valiant!\n\nTurn 4\n",
+            "Annika's Kingambit was paralyzed!\n",
+            "But it failed!\nRust Haters' Iron Valiant used Thunder Wave!\n",
+            "Annika's Kingambit fainted!\nThe weather cleared up\n",
+            "Annika sent out Pelipper! (88% HP)\n",
+            "Annika forfeited.\nRust Haters won the battle!\n",
+        ] {
+            assert!(
+                transcript.contains(expected),
+                "{:?} isn't in the transcript:\n{}",
+                expected,
+                transcript
+            );
+        }
+        // the weather continuing each turn isn't shown
+        assert_eq!(transcript.matches("RainDance").count(), 2);
+    }
+
+    #[test]
+    fn chat() {
+        assert!(transcript(true, false).contains("\n☆Annika: gl hf\n"));
+        assert!(!transcript(false, false).contains("gl hf"));
+    }
+
+    #[test]
+    fn color() {
+        assert!(!transcript(true, false).contains('\x1b'));
+        let transcript = transcript(true, true);
+        assert!(transcript.contains("\x1b[1mTurn 1\x1b[0m"));
+        assert!(transcript.contains("\x1b[31mRust Haters' Garchomp\x1b[0m \x1b[31mfainted!\x1b[0m"));
+        assert!(transcript.contains("\x1b[34mAnnika's Kingambit\x1b[0m used Swords Dance!"));
+    }
+
+    #[test]
+    fn doubles() {
+        let json = std::fs::read_to_string("src/test-logs/gen8doublesou.log.json").unwrap();
+        let log = BattleLog::parse(&json).unwrap();
+        let mut out = vec![];
+        Transcript::new(false, false).write(&mut out, &log).unwrap();
+        let transcript = String::from_utf8(out).unwrap();
+        assert!(
+            transcript.contains("Annika's Incineroar used Fake Out on Rust Haters' Dusclops!\n")
+        );
+        // spread moves and moves that target the user don't get a target
+        assert!(transcript.contains("Annika's Tapu Koko used Dazzling Gleam!\n"));
+        assert!(transcript.contains("Annika's Tapu Koko used Tailwind!\n"));
+        assert!(transcript.contains("Rust Haters' Conkeldurr lost 6% HP (burn) (39% left)\n"));
+        // level 100 isn't worth mentioning
+        assert!(transcript.contains("\n  Koko (Tapu Koko) @ Life Orb (Electric Surge): "));
+    }
+}
@@ -50,3 +50,143 @@ fn test_no_terms_and_identicality() {
 
     assert_eq!(output_1, output_999);
 }
+
+#[test]
+fn test_error_report_and_strict() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let in_dir = PathBuf::from("test-scratch/bad-logs");
+    std::fs::create_dir_all(&in_dir).unwrap();
+    std::fs::copy("src/benchmark-data.json", in_dir.join("good.log.json")).unwrap();
+    std::fs::write(in_dir.join("bad.log.json"), "this isn't JSON").unwrap();
+    let report_path = PathBuf::from("test-scratch/bad-logs-report.jsonl");
+
+    let lenient_output = Command::new(&path)
+        .arg("anonymize")
+        .arg(&in_dir)
+        .arg("-o")
+        .arg("test-scratch/bad-logs-anonymized")
+        .arg("--error-report")
+        .arg(&report_path)
+        .output()
+        .expect("Failed to execute command");
+    assert!(lenient_output.status.success(), "command failed");
+    let stderr = std::str::from_utf8(&lenient_output.stderr).unwrap();
+    assert!(stderr.contains("1 errors occurred"), "{}", stderr);
+    assert!(stderr.contains("JSONParsingError"), "{}", stderr);
+
+    let report = std::fs::read_to_string(&report_path).unwrap();
+    assert_eq!(report.lines().count(), 1);
+    assert!(
+        report.contains(r#""kind":"JSONParsingError""#),
+        "{}",
+        report
+    );
+    assert!(report.contains("bad.log.json"), "{}", report);
+
+    let strict_output = Command::new(&path)
+        .arg("anonymize")
+        .arg(&in_dir)
+        .arg("-o")
+        .arg("test-scratch/bad-logs-anonymized")
+        .arg("--strict")
+        .output()
+        .expect("Failed to execute command");
+    // exit code 3: invalid input
+    assert_eq!(strict_output.status.code(), Some(3));
+}
+
+#[test]
+fn test_exit_codes() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let missing_state = Command::new(&path)
+        .arg("anonymize")
+        .arg("test-scratch/nonexistent")
+        .arg("-o")
+        .arg("test-scratch/nonexistent-anonymized")
+        .arg("--load-state-from")
+        .arg("test-scratch/nonexistent-state.json")
+        .output()
+        .expect("Failed to execute command");
+    // exit code 4: I/O failure
+    assert_eq!(missing_state.status.code(), Some(4));
+    let stderr = std::str::from_utf8(&missing_state.stderr).unwrap();
+    assert!(
+        stderr.starts_with(
+            "Error: loading anonymizer state from test-scratch/nonexistent-state.json: I/O error: "
+        ),
+        "{}",
+        stderr
+    );
+
+    let bad_usage = Command::new(&path)
+        .arg("anonymize")
+        .arg("--no-such-option")
+        .output()
+        .expect("Failed to execute command");
+    // exit code 2: invalid usage
+    assert_eq!(bad_usage.status.code(), Some(2));
+}
+
+#[test]
+fn test_manifest() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let in_dir = PathBuf::from("test-scratch/manifest-anonymize");
+    let out_dir = PathBuf::from("test-scratch/manifest-anonymized");
+    let manifest = PathBuf::from("test-scratch/manifest-anonymize.json");
+    for dir in [&in_dir, &out_dir] {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    let _ = std::fs::remove_file(&manifest);
+
+    let add_logs = |day: &str, count: u32| {
+        let dir = in_dir.join(day);
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..count {
+            std::fs::copy(
+                "src/benchmark-data.json",
+                dir.join(format!("{}.log.json", i)),
+            )
+            .unwrap();
+        }
+    };
+    let anonymize = || {
+        let output = Command::new(&path)
+            .arg("anonymize")
+            .arg(&in_dir)
+            .arg("-o")
+            .arg(&out_dir)
+            .arg("--manifest")
+            .arg(&manifest)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+    };
+    let count_outputs = || {
+        std::fs::read_dir(out_dir.join("gen8randombattle"))
+            .unwrap()
+            .count()
+    };
+
+    add_logs("day1", 3);
+    anonymize();
+    assert_eq!(count_outputs(), 3);
+    let first_output =
+        std::fs::read_to_string(out_dir.join("gen8randombattle/1.log.json")).unwrap();
+
+    add_logs("day2", 2);
+    anonymize();
+    // only the new logs are anonymized, and battle numbers carry on from the previous run
+    assert_eq!(count_outputs(), 5);
+    let new_output = std::fs::read_to_string(out_dir.join("gen8randombattle/5.log.json")).unwrap();
+    // players keep the same anonymized IDs
+    assert_eq!(
+        gjson::get(&first_output, "p1").str(),
+        gjson::get(&new_output, "p1").str()
+    );
+}
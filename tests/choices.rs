@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_choices() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let output = Command::new(manifest_dir.join("target/debug/psbattletools"))
+        .arg("choices")
+        .arg(manifest_dir.join("src/test-logs"))
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+
+    let output_str = std::str::from_utf8(&output.stdout).unwrap();
+    // one line for each line of both logs' input logs
+    assert_eq!(output_str.lines().count(), 30);
+    assert!(output_str.contains(
+        r#"{"battle":"gen8doublesou","type":"decision","side":"p1","actions":[{"action":"team","order":[1,2,3,4,5,6]}]}"#
+    ));
+    assert!(output_str.contains(
+        r#"{"battle":"gen9randombattle","type":"decision","side":"p1","actions":[{"action":"move","move":2,"gimmick":"terastallize"}]}"#
+    ));
+    assert!(output_str.contains(r#"{"battle":"gen8doublesou","type":"forcelose","side":"p2"}"#));
+}
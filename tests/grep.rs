@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_grep() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let output = Command::new(&path)
+        .arg("grep")
+        .arg("gl hf")
+        .arg("src/test-logs")
+        .arg("--chat-only")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+    assert_eq!(
+        std::str::from_utf8(&output.stdout).unwrap(),
+        "(test-logs) <<gen9randombattle-1960000001>> [turn 1] Annika: |c|☆Annika|gl hf\n"
+    );
+
+    let output = Command::new(&path)
+        .arg("grep")
+        .arg("^>forcelose")
+        .arg("src/test-logs")
+        .arg("--input-log")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+    let output_str = std::str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(output_str.lines().count(), 2);
+    assert!(output_str.contains("<<gen8doublesou-1260000001>> [input] Rust Haters: >forcelose p2"));
+}
+
+#[test]
+fn test_grep_invalid_pattern() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let output = Command::new(&path)
+        .arg("grep")
+        .arg("(unclosed")
+        .arg("src/test-logs")
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(std::str::from_utf8(&output.stderr)
+        .unwrap()
+        .contains("compiling the pattern"));
+}
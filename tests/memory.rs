@@ -0,0 +1,48 @@
+#![feature(test)]
+// Tests for memory usage. These are in their own file since the peak memory usage of child processes
+// is tracked per test binary.
+use std::path::PathBuf;
+use std::process::Command;
+use tests::*;
+
+extern crate test;
+
+// This builds 41,000 logs and takes a while, so it only runs with `cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_stats_memory_is_bounded() {
+    let small_dir = PathBuf::from("test-scratch/memory-small");
+    let large_dir = PathBuf::from("test-scratch/memory-large");
+    build_multi_day_test_dir(&small_dir, 1, 1_000).unwrap();
+    build_multi_day_test_dir(&large_dir, 40, 1_000).unwrap();
+
+    let path = PathBuf::from(env!("CARGO_BIN_EXE_psbattletools"));
+
+    let run_stats = |dir: &PathBuf| {
+        let output = Command::new(&path)
+            .arg("stats")
+            .arg("--csv")
+            .arg("test-scratch/memory.csv")
+            .arg(dir)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+    };
+
+    run_stats(&small_dir);
+    let small_peak = peak_child_rss_kb();
+    run_stats(&large_dir);
+    let large_peak = peak_child_rss_kb();
+
+    let csv = std::fs::read_to_string("test-scratch/memory.csv").unwrap();
+    assert!(csv.starts_with("Rotom-Fan,40000,40000,"), "{}", csv);
+
+    // 40x as many logs shouldn't need noticeably more memory; the allowance is relative,
+    // since the baseline varies with the allocator and thread count
+    assert!(
+        large_peak < small_peak + small_peak / 2,
+        "peak memory grew from {}KB to {}KB",
+        small_peak,
+        large_peak
+    );
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_rating_history() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let svg_path = std::env::temp_dir().join("psbattletools-rating-history-test.svg");
+
+    let output = Command::new(manifest_dir.join("target/debug/psbattletools"))
+        .arg("rating-history")
+        .arg("Annika")
+        .arg(manifest_dir.join("src/test-logs"))
+        .arg("--svg")
+        .arg(&svg_path)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+
+    let output_str = std::str::from_utf8(&output.stdout).unwrap();
+    let lines = output_str.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "timestamp,roomid,format,elo,gxe,opponent,result");
+    assert!(lines[1].starts_with("2021-01-31T19:02:30-05:00,battle-gen8doublesou-1260000001,"));
+    assert!(lines[2].ends_with(",gen9randombattle,1512.37,58.3,Rust Haters,loss"));
+
+    let svg = std::fs::read_to_string(&svg_path).unwrap();
+    std::fs::remove_file(&svg_path).unwrap();
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains(">gen9randombattle</text>"));
+}
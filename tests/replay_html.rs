@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_replay_html() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let output_path = std::env::temp_dir().join("psbattletools-replay-html-test.html");
+
+    let output = Command::new(manifest_dir.join("target/debug/psbattletools"))
+        .arg("replay-html")
+        .arg(manifest_dir.join("src/test-logs/gen8doublesou.log.json"))
+        .arg("-o")
+        .arg(&output_path)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+
+    let html = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(r#"<script type="text/plain" class="battle-log-data">"#));
+    assert!(html.contains("|win|Annika"));
+}
@@ -88,3 +88,250 @@ fn test_search_wins_only() {
         assert!(rust_haters_output_str.split('\n').count() <= 5);
     }
 }
+
+#[test]
+#[serial]
+fn test_search_multiple_users() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |args: &[&str]| {
+        let output = Command::new(&path)
+            .arg("search")
+            .args(args)
+            .arg(&*TEST_ROOT_DIR)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let any_output = search(&["nobody,Rust Haters"]);
+    assert!(any_output.starts_with("rusthaters vs. annika (1000 battles)\n"));
+    assert!(any_output.contains("annika vs. rusthaters (annika won normally)"));
+
+    let all_output = search(&["rusthaters,annika", "--all"]);
+    assert!(all_output.starts_with("annika vs. rusthaters (1000 battles)\n"));
+    assert!(search(&["annika,nobody", "--all"]).is_empty());
+
+    assert_eq!(search(&["annika", "--opponent", "rusthaters"]), all_output);
+    assert!(search(&["annika", "--opponent", "nobody"]).is_empty());
+}
+
+#[test]
+#[serial]
+fn test_search_output_format() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |format: &str| {
+        let output = Command::new(&path)
+            .arg("search")
+            .arg("annika")
+            .arg(&*TEST_ROOT_DIR)
+            .arg("--output-format")
+            .arg(format)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let json = search("json");
+    assert_eq!(json.lines().count(), 1000);
+    for line in json.lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(record["winner"], "Annika");
+        assert_eq!(record["p2"], "Rust Haters");
+    }
+
+    let csv = search("csv");
+    assert_eq!(csv.lines().count(), 1001);
+    assert!(csv.starts_with(
+        "path,date,roomid,format,p1,p2,winner,end_type,turns,p1rating,p2rating,matched_name\n"
+    ));
+
+    let paths = search("paths");
+    assert_eq!(paths.lines().count(), 1000);
+    assert!(paths.lines().all(|path| path.ends_with(".json")));
+}
+
+#[test]
+#[serial]
+fn test_search_order() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |args: &[&str]| {
+        let output = Command::new(&path)
+            .arg("search")
+            .arg("annika")
+            .arg(&*TEST_ROOT_DIR)
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let sorted = search(&["--output-format", "paths"]);
+    assert_eq!(sorted, search(&["--output-format", "paths"]));
+    let mut lines = sorted.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1000);
+
+    let limited = search(&["--output-format", "paths", "--limit", "10"]);
+    assert_eq!(limited.lines().collect::<Vec<_>>(), lines[..10]);
+
+    let unordered = search(&["--output-format", "paths", "--unordered"]);
+    let mut unordered_lines = unordered.lines().collect::<Vec<_>>();
+    unordered_lines.sort_unstable();
+    lines.sort_unstable();
+    assert_eq!(unordered_lines, lines);
+
+    let unordered_limited = search(&["--output-format", "paths", "--unordered", "--limit", "10"]);
+    assert_eq!(unordered_limited.lines().count(), 10);
+}
+
+#[test]
+fn test_search_team() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |args: &[&str]| {
+        let output = Command::new(&path)
+            .arg("search")
+            .arg("Annika")
+            .arg("src/test-logs")
+            .arg("--output-format")
+            .arg("roomids")
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        search(&["--species", "Iron Moth", "--tera-type", "GRASS"]),
+        "battle-gen9randombattle-1960000001\n"
+    );
+    // Garchomp is on Annika's opponent's team
+    assert_eq!(
+        search(&["--species", "garchomp"]),
+        "battle-gen9randombattle-1960000001\n"
+    );
+    assert_eq!(search(&["--species", "garchomp", "--user-team-only"]), "");
+}
+
+#[test]
+fn test_search_battle_filters() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |args: &[&str]| {
+        Command::new(&path)
+            .arg("search")
+            .arg("Annika")
+            .arg("src/test-logs")
+            .arg("--output-format")
+            .arg("roomids")
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+    };
+    let roomids = |args: &[&str]| {
+        let output = search(args);
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // early forfeits above 1500
+    assert_eq!(
+        roomids(&[
+            "--end-type",
+            "forfeit",
+            "--max-turns",
+            "10",
+            "--min-rating",
+            "1500",
+            "--rated-only"
+        ]),
+        "battle-gen9randombattle-1960000001\n"
+    );
+    assert_eq!(
+        roomids(&["--losses-only"]),
+        "battle-gen9randombattle-1960000001\n"
+    );
+    assert_eq!(
+        roomids(&["--max-turns", "5"]),
+        "battle-gen8doublesou-1260000001\n"
+    );
+    assert_eq!(roomids(&["--ties-only"]), "");
+    assert_eq!(roomids(&["--end-type", "normal"]), "");
+
+    assert_eq!(
+        search(&["--wins-only", "--losses-only"]).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+#[serial]
+fn test_search_include_renames() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let search = |args: &[&str]| {
+        let output = Command::new(&path)
+            .arg("search")
+            .arg("Rust Hater")
+            .arg(&*TEST_ROOT_DIR)
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "command failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // the test logs' `|player|` line has a different name than `p2`
+    assert!(search(&[]).is_empty());
+    let output = search(&["--include-renames"]);
+    assert!(output.starts_with("rusthaters vs. annika (1000 battles)\n"));
+    assert!(output.contains("annika vs. rusthaters (annika won normally) [as rusthater]"));
+}
+
+#[test]
+fn test_search_summary() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let output = Command::new(&path)
+        .arg("search")
+        .arg("Annika")
+        .arg("src/test-logs")
+        .arg("--summary")
+        .arg("--output-format")
+        .arg("json")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["battles"], 2);
+    assert_eq!(summary["wins"], 1);
+    assert_eq!(summary["opponents"][0]["name"], "rusthaters");
+
+    let output = Command::new(&path)
+        .arg("search")
+        .arg("Annika")
+        .arg("src/test-logs")
+        .arg("--summary")
+        .arg("--output-format")
+        .arg("csv")
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_show() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let output = Command::new(manifest_dir.join("target/debug/psbattletools"))
+        .arg("show")
+        .arg(manifest_dir.join("src/test-logs/gen9randombattle.log.json"))
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+
+    let output_str = std::str::from_utf8(&output.stdout).unwrap();
+    // standard output isn't a terminal, so there's no color
+    assert!(!output_str.contains('\x1b'));
+    assert!(output_str.starts_with("[Gen 9] Random Battle: Annika vs. Rust Haters\n"));
+    assert!(output_str.contains("\nTurn 3\n"));
+    assert!(output_str.contains("gl hf"));
+    assert!(output_str.ends_with("Rust Haters won the battle!\n"));
+}
+
+#[test]
+fn test_show_no_chat_color() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let output = Command::new(manifest_dir.join("target/debug/psbattletools"))
+        .arg("show")
+        .arg("--no-chat")
+        .arg("--color")
+        .arg("always")
+        .arg(manifest_dir.join("src/test-logs/gen9randombattle.log.json"))
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+
+    let output_str = std::str::from_utf8(&output.stdout).unwrap();
+    assert!(output_str.contains("\x1b[1mTurn 3\x1b[0m"));
+    assert!(!output_str.contains("gl hf"));
+}
+
+#[test]
+fn test_show_files_from() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let output = Command::new(path)
+        .arg("--files-from")
+        .arg("-")
+        .arg("show")
+        .arg("src/test-logs/gen9randombattle.log.json")
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(2));
+}
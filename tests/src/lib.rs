@@ -1,11 +1,12 @@
 // Helper functions for testing
 use lazy_static::lazy_static;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 lazy_static! {
     pub static ref TEST_ROOT_DIR: PathBuf = PathBuf::from("test-scratch/battles");
     pub static ref TEST_ROOT_DIR_DAY1: PathBuf = PathBuf::from("test-scratch/battles/day1");
     pub static ref TEST_ROOT_DIR_DAY2: PathBuf = PathBuf::from("test-scratch/battles/day2");
+    pub static ref TEST_MULTI_DAY_ROOT_DIR: PathBuf = PathBuf::from("test-scratch/multi-day-battles");
 }
 
 pub fn build_test_dir(num_files: u32) -> std::io::Result<()> {
@@ -29,3 +30,40 @@ pub fn build_test_dir(num_files: u32) -> std::io::Result<()> {
     }
     Ok(())
 }
+
+/// Builds a directory laid out like PS's logs (`YYYY-MM/format/YYYY-MM-DD/battle-*.log.json`),
+/// with `files_per_day` logs in each of `num_days` days (starting on 2021-01-01, with 28 days per month).
+pub fn build_multi_day_test_dir(root: &Path, num_days: u32, files_per_day: u32) -> std::io::Result<()> {
+    let src_file = &PathBuf::from("src/benchmark-data.json");
+
+    for day in 0..num_days {
+        let month = format!("2021-{:02}", day / 28 + 1);
+        let mut dir = root.to_path_buf();
+        dir.push(&month);
+        dir.push("gen8randombattle");
+        dir.push(format!("{}-{:02}", month, day % 28 + 1));
+        std::fs::create_dir_all(&dir)?;
+
+        for i in 0..files_per_day {
+            let mut file = dir.clone();
+            file.push(format!(
+                "battle-gen8randombattle-{}.log.json",
+                day * files_per_day + i
+            ));
+            std::fs::copy(src_file, &file)
+                .expect(&format!("error copying from {:?} to {:?}", src_file, file));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the peak resident set size, in kilobytes, of the largest child process that has exited so far
+pub fn peak_child_rss_kb() -> i64 {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage only writes to the provided struct
+    let usage = unsafe {
+        assert_eq!(libc::getrusage(libc::RUSAGE_CHILDREN, usage.as_mut_ptr()), 0);
+        usage.assume_init()
+    };
+    usage.ru_maxrss
+}
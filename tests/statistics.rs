@@ -1,7 +1,7 @@
 #![feature(test)]
 use lazy_static::lazy_static;
 use serial_test::serial;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tests::*;
 
@@ -185,15 +185,307 @@ fn test_exclusions() {
     );
 
     // ...and less output when excluding many files
+    for exclusion in ["*6*", "re:6"] {
+        let reduced_output = Command::new(&path)
+            .arg("--exclude")
+            .arg(exclusion)
+            .arg("statistics")
+            .arg(&*TEST_ROOT_DIR)
+            .output()
+            .expect("Failed to execute command");
+        assert!(reduced_output.status.success(), "command failed");
+
+        let reduced_output_str = std::str::from_utf8(&reduced_output.stdout).unwrap();
+        assert!(!reduced_output_str.contains("1000")); // less than 1000 battles
+
+        let stderr = std::str::from_utf8(&reduced_output.stderr).unwrap();
+        assert!(stderr.contains(&format!("--exclude '{}' pruned ", exclusion)));
+    }
+}
+
+#[test]
+#[serial]
+fn test_inclusions() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    // Both day directories are included, so every battle is used...
+    let normal_output = Command::new(&path)
+        .arg("--include")
+        .arg("day1")
+        .arg("--include")
+        .arg("re:day2$")
+        .arg("statistics")
+        .arg(&*TEST_ROOT_DIR)
+        .output()
+        .expect("Failed to execute command");
+    assert!(normal_output.status.success(), "command failed");
+
+    let normal_output_str = std::str::from_utf8(&normal_output.stdout).unwrap();
+    assert_eq!(
+        normal_output_str.to_string().strip_suffix('\n').unwrap(),
+        *DESIRED_TABLE_OUTPUT
+    );
+
+    // ...but only half of them are in day1, and excluding takes precedence over including
+    // (the options may also be given after the subcommand)
     let reduced_output = Command::new(&path)
-        .arg("--exclude")
-        .arg("6")
         .arg("statistics")
+        .arg("--include")
+        .arg("day1")
+        .arg("--include")
+        .arg("day2")
+        .arg("--exclude")
+        .arg("day2")
         .arg(&*TEST_ROOT_DIR)
         .output()
         .expect("Failed to execute command");
     assert!(reduced_output.status.success(), "command failed");
 
     let reduced_output_str = std::str::from_utf8(&reduced_output.stdout).unwrap();
-    assert!(!reduced_output_str.contains("1000")); // less than 1000 battles
+    assert!(reduced_output_str.contains("Rotom-Fan"));
+    assert!(!reduced_output_str.contains("1000"));
+}
+
+#[test]
+#[serial]
+fn test_date_range() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    // The test logs aren't in date directories, so their timestamps (Nov 1, 1970) are used
+    let normal_output = Command::new(&path)
+        .arg("statistics")
+        .arg(&*TEST_ROOT_DIR)
+        .arg("--since")
+        .arg("1970-11-01")
+        .arg("--until")
+        .arg("1970-11-01")
+        .output()
+        .expect("Failed to execute command");
+    assert!(normal_output.status.success(), "command failed");
+
+    let normal_output_str = std::str::from_utf8(&normal_output.stdout).unwrap();
+    assert_eq!(
+        normal_output_str.to_string().strip_suffix('\n').unwrap(),
+        *DESIRED_TABLE_OUTPUT
+    );
+
+    for (option, date) in [("--since", "1970-11-02"), ("--until", "1970-10-31")] {
+        let no_output = Command::new(&path)
+            .arg("statistics")
+            .arg(&*TEST_ROOT_DIR)
+            .arg(option)
+            .arg(date)
+            .output()
+            .expect("Failed to execute command");
+        assert!(no_output.status.success(), "command failed");
+
+        let no_output_str = std::str::from_utf8(&no_output.stdout).unwrap();
+        assert!(!no_output_str.contains("Rotom"));
+    }
+}
+
+#[test]
+#[serial]
+fn test_progress() {
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let output = Command::new(&path)
+        .arg("stats")
+        .arg(&*TEST_ROOT_DIR)
+        .arg("--progress")
+        .arg("json")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    let last_line = stderr.lines().last().unwrap();
+    assert!(
+        last_line.starts_with(r#"{"files_done":1000,"files_discovered":1000,"#),
+        "{}",
+        last_line
+    );
+    assert!(last_line.ends_with(r#""finished":true}"#), "{}", last_line);
+
+    let quiet_output = Command::new(&path)
+        .arg("stats")
+        .arg(&*TEST_ROOT_DIR)
+        .arg("--quiet")
+        .output()
+        .expect("Failed to execute command");
+    assert!(quiet_output.status.success(), "command failed");
+    assert!(quiet_output.stderr.is_empty());
+    assert_eq!(
+        std::str::from_utf8(&quiet_output.stdout)
+            .unwrap()
+            .strip_suffix('\n')
+            .unwrap(),
+        *DESIRED_TABLE_OUTPUT
+    );
+}
+
+#[test]
+fn test_manifest() {
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    let root = PathBuf::from("test-scratch/manifest-battles");
+    let manifest = PathBuf::from("test-scratch/manifest-battles.json");
+    let csv = PathBuf::from("test-scratch/manifest-battles.csv");
+    let _ = std::fs::remove_dir_all(&root);
+    let _ = std::fs::remove_file(&manifest);
+    build_multi_day_test_dir(&root, 2, 10).unwrap();
+
+    let run_stats = |dir: &Path, extra_args: &[&str]| {
+        let output = Command::new(&path)
+            .arg("stats")
+            .arg(dir)
+            .arg("--csv")
+            .arg(&csv)
+            .arg("--manifest")
+            .arg(&manifest)
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute command");
+        let stderr = std::str::from_utf8(&output.stderr).unwrap().to_string();
+        let csv = std::fs::read_to_string(&csv).unwrap_or_default();
+        (output.status.code(), stderr, csv)
+    };
+
+    let (status, _, csv_output) = run_stats(&root, &[]);
+    assert_eq!(status, Some(0));
+    assert!(csv_output.starts_with("Rotom-Fan,20,20,"), "{}", csv_output);
+
+    // nothing has changed, so the statistics shouldn't either
+    let (status, stderr, csv_output) = run_stats(&root, &[]);
+    assert_eq!(status, Some(0));
+    assert!(csv_output.starts_with("Rotom-Fan,20,20,"), "{}", csv_output);
+    assert!(
+        stderr.contains("20 unchanged files in the manifest were skipped"),
+        "{}",
+        stderr
+    );
+
+    // the same logs are recognized however their directory is spelled
+    for spelling in [
+        PathBuf::from("./test-scratch/manifest-battles/"),
+        std::fs::canonicalize(&root).unwrap(),
+    ] {
+        let (status, stderr, csv_output) = run_stats(&spelling, &[]);
+        assert_eq!(status, Some(0));
+        assert!(csv_output.starts_with("Rotom-Fan,20,20,"), "{}", csv_output);
+        assert!(
+            stderr.contains("20 unchanged files in the manifest were skipped"),
+            "{}",
+            stderr
+        );
+    }
+
+    // a new day's logs are added to the saved statistics
+    let new_day = root.join("2021-01/gen8randombattle/2021-01-03");
+    std::fs::create_dir_all(&new_day).unwrap();
+    for i in 0..5 {
+        std::fs::copy(
+            "src/benchmark-data.json",
+            new_day.join(format!("battle-gen8randombattle-new{}.log.json", i)),
+        )
+        .unwrap();
+    }
+    let (status, _, csv_output) = run_stats(&root, &[]);
+    assert_eq!(status, Some(0));
+    assert!(csv_output.starts_with("Rotom-Fan,25,25,"), "{}", csv_output);
+
+    // a log that changed since it was recorded can't be counted again without counting it twice,
+    // so the run fails, leaving the manifest and the output as they were
+    let changed = new_day.join("battle-gen8randombattle-new0.log.json");
+    let mut contents = std::fs::read_to_string(&changed).unwrap();
+    contents.push('\n');
+    std::fs::write(&changed, contents).unwrap();
+    let (status, stderr, csv_output) = run_stats(&root, &[]);
+    assert_eq!(status, Some(3));
+    assert!(
+        stderr.contains("invalid manifest: 1 files in it"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("delete the manifest"), "{}", stderr);
+    assert!(csv_output.starts_with("Rotom-Fan,25,25,"), "{}", csv_output);
+
+    // statistics with a different minimum elo can't be combined
+    let (status, stderr, _) = run_stats(&root, &["--minimum-elo", "1000"]);
+    assert_eq!(status, Some(3));
+    assert!(stderr.contains("invalid manifest"), "{}", stderr);
+}
+
+#[test]
+#[serial]
+fn test_files_from() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    build_test_dir(1_000).unwrap();
+    let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    path.push("target/debug/psbattletools");
+
+    // 3 files from stdin, NUL-separated, plus one that doesn't exist
+    let list = ["0.json", "1.json", "2.json", "nonexistent.json"]
+        .iter()
+        .map(|file| TEST_ROOT_DIR_DAY2.join(file).display().to_string())
+        .collect::<Vec<_>>()
+        .join("\0");
+    let mut child = Command::new(&path)
+        .arg("--files-from")
+        .arg("-")
+        .arg("stats")
+        .arg("--csv")
+        .arg("test-scratch/files-from.csv")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(list.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "command failed");
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("1 errors occurred"), "{}", stderr);
+    let csv = std::fs::read_to_string("test-scratch/files-from.csv").unwrap();
+    assert!(csv.starts_with("Rotom-Fan,3,3,"), "{}", csv);
+
+    // newline-separated, from a file
+    std::fs::write(
+        "test-scratch/files-from.txt",
+        format!("{}\n", TEST_ROOT_DIR_DAY1.join("999.json").display()),
+    )
+    .unwrap();
+    let output = Command::new(&path)
+        .arg("stats")
+        .arg("--files-from")
+        .arg("test-scratch/files-from.txt")
+        .arg("--csv")
+        .arg("test-scratch/files-from.csv")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "command failed");
+    let csv = std::fs::read_to_string("test-scratch/files-from.csv").unwrap();
+    assert!(csv.starts_with("Rotom-Fan,1,1,"), "{}", csv);
+
+    // directories and --files-from can't be combined
+    let output = Command::new(&path)
+        .arg("stats")
+        .arg(&*TEST_ROOT_DIR)
+        .arg("--files-from")
+        .arg("test-scratch/files-from.txt")
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(2));
 }